@@ -1,9 +1,12 @@
-use std::path::PathBuf;
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
 
 use prompt_toolkit::{
-    application::Application,
+    application::{Application, ApplicationError},
+    buffer::Buffer,
     clipboard::Clipboard,
-    key_bindings::{EchoBinding, KeyBindings},
+    key_bindings::{load_emacs_bindings, load_vi_bindings, KeyBindings, NamedCommands, ViState},
+    keys::Keys,
+    layout::{BufferControl, FormattedTextControl, HSplit, Layout, VSplit, Window},
     output::ColorDepth,
 };
 use tracing::Level;
@@ -29,16 +32,48 @@ fn main() {
 }
 
 fn use_application() {
-    let mut key_bindings = KeyBindings::new();
-    key_bindings.add_for_all_keys(Box::new(EchoBinding));
-    let clipboard = Clipboard;
+    let mut key_bindings: KeyBindings<String> = if std::env::args().any(|arg| arg == "--vi") {
+        load_vi_bindings(&Rc::new(RefCell::new(ViState::new())))
+    } else {
+        load_emacs_bindings()
+    };
+    key_bindings.add_fn(&[Keys::ControlC], |event| {
+        event
+            .app_mut()
+            .exit(Err(ApplicationError::KeyboardInterrupt.into()));
+    });
+    key_bindings.add_fn(&[Keys::ControlD], |event| {
+        event
+            .app_mut()
+            .exit(Err(ApplicationError::EndOfFile.into()));
+    });
+    let commands = NamedCommands::new().with_accept_line();
+    if let Some(accept_line) = commands.get("accept-line") {
+        key_bindings.add(&[Keys::ControlM], accept_line);
+    }
+    let layout = Layout::new(Box::new(HSplit::new(vec![
+        Box::new(Window::new(FormattedTextControl::new(vec![
+            ("bold".to_string(), "prompt".to_string()),
+            (String::new(), " - press enter to accept".to_string()),
+        ]))),
+        Box::new(VSplit::new(vec![
+            Box::new(Window::new(FormattedTextControl::from_text("> ")).with_width(2)),
+            Box::new(Window::new(BufferControl::new(Rc::new(RefCell::new(
+                Buffer::new(),
+            ))))),
+        ])),
+    ])));
+    let clipboard = Clipboard::default();
     let mut app = Application::new(
-        prompt_toolkit::layout::Layout,
+        layout,
         key_bindings,
         clipboard,
-        ColorDepth::True,
+        ColorDepth::from_env(),
         false,
         prompt_toolkit::filters::Filter::Always,
     );
-    app.run(5);
+    match app.run() {
+        Ok(text) => println!("you said: {text}"),
+        Err(e) => eprintln!("{e}"),
+    }
 }
@@ -1,41 +1,90 @@
 #![expect(dead_code)]
 
-use std::{fs::OpenOptions, os::fd::IntoRawFd};
+use std::{
+    cell::RefCell, collections::HashMap, error::Error, fmt::Display, fs::OpenOptions,
+    os::fd::IntoRawFd, rc::Rc, time::Duration,
+};
 
+use anyhow::Result;
 use nix::libc;
 use nix::unistd::isatty;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
+    buffer::Buffer,
     clipboard::Clipboard,
     filters::Filter,
-    input::VT100 as VT100Input,
-    key_bindings::{KeyBindings, KeyPressEvent},
+    input::{vt100_parser::parse_cursor_position_response, DummyInput, VT100 as VT100Input},
+    key_bindings::{KeyBindingsBase, KeyProcessor},
+    keys::Keys,
     layout::Layout,
-    output::{ColorDepth, VT100 as VT100Output},
-    render, Input, Output, Screen, WritePosition,
+    output::{environment, wants_no_color, ColorDepth, VT100 as VT100Output},
+    render::Renderer,
+    styles::{StandardStyle, Style, StyleTransformation},
+    Input, Output,
 };
 
+/// How long to wait for the rest of an escape sequence before treating the
+/// buffered input as individual key presses.
+const TTIMEOUTLEN: Duration = Duration::from_millis(500);
+
 fn is_tty(fd: i32) -> bool {
     isatty(fd).unwrap_or(false)
 }
 
-pub struct Application {
+/// The color depth to render with whatever the application asked for:
+/// [`ColorDepth::Monochrome`] when `NO_COLOR` is set.
+fn forced_color_depth(env: &HashMap<String, String>) -> Option<ColorDepth> {
+    wants_no_color(env).then_some(ColorDepth::Monochrome)
+}
+
+/// Well-known reasons for an application to end without a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplicationError {
+    /// The user asked to abort, typically with Ctrl-C.
+    KeyboardInterrupt,
+    /// The user signalled end of input (Ctrl-D), or the input stream was closed.
+    EndOfFile,
+}
+
+impl Error for ApplicationError {}
+impl Display for ApplicationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApplicationError::KeyboardInterrupt => write!(f, "keyboard interrupt"),
+            ApplicationError::EndOfFile => write!(f, "end of file"),
+        }
+    }
+}
+
+pub struct Application<T> {
     layout: Layout,
-    key_bindings: KeyBindings,
+    /// Taken by the key processor while the application runs.
+    key_bindings: Option<Box<dyn KeyBindingsBase<T>>>,
     clipboard: Clipboard,
     color_depth: ColorDepth,
+    /// Rendered with instead of `color_depth`: [`ColorDepth::Monochrome`]
+    /// when `NO_COLOR` is set.
+    forced_color_depth: Option<ColorDepth>,
     erase_when_done: bool,
     filter: Filter,
     input: Box<dyn Input>,
-    output: Box<dyn Output>,
+    renderer: Renderer,
+    is_done: bool,
+    invalidated: bool,
+    result: Option<Result<T>>,
 }
 
-impl Application {
+impl<T> Application<T> {
+    /// Runs on `/dev/tty`, or on stdin and stdout without one. When the
+    /// output is not a terminal or `TERM` is `dumb`, the layout is written
+    /// as plain lines of text, see [`Application::set_line_oriented`].
+    /// When `NO_COLOR` is set, colors are never written, whatever
+    /// `color_depth` is.
     #[must_use]
     pub fn new(
         layout: Layout,
-        key_bindings: KeyBindings,
+        key_bindings: impl KeyBindingsBase<T> + 'static,
         clipboard: Clipboard,
         color_depth: ColorDepth,
         erase_when_done: bool,
@@ -59,15 +108,20 @@ impl Application {
         };
         let input = VT100Input::new(in_fd);
         let output = VT100Output::new(out);
+        let renderer = Renderer::new(Box::new(output), Box::new(StandardStyle::default()));
         Self {
             layout,
-            key_bindings,
+            key_bindings: Some(Box::new(key_bindings)),
             clipboard,
             color_depth,
+            forced_color_depth: forced_color_depth(&environment()),
             erase_when_done,
             filter,
             input: Box::new(input),
-            output: Box::new(output),
+            renderer,
+            is_done: false,
+            invalidated: false,
+            result: None,
         }
     }
 
@@ -75,8 +129,56 @@ impl Application {
         self.input = input;
     }
 
+    /// Draw to `output` from now on. The layout is written as plain lines
+    /// when the output doesn't support cursor movement, e.g. a file.
     pub fn set_output(&mut self, output: Box<dyn Output>) {
-        self.output = output;
+        self.renderer.set_output(output);
+    }
+
+    /// Write the layout as plain lines of text, without cursor movement or
+    /// escape sequences, rather than drawing it on the terminal, whatever
+    /// the output supports. What is before the cursor is written as it is
+    /// typed, the rest once the application is done.
+    pub fn set_line_oriented(&mut self, line_oriented: bool) {
+        self.renderer.set_line_oriented(line_oriented);
+    }
+
+    /// Resolve the style strings of the layout, e.g. `class:prompt`,
+    /// through `style`. By default only inline styles like `bold` apply.
+    pub fn set_style(&mut self, style: Box<dyn Style>) {
+        self.renderer.set_style(style);
+    }
+
+    /// Change the attributes of every style before they are written,
+    /// e.g. with a [`SwapLightAndDarkStyleTransformation`] for light
+    /// terminals.
+    ///
+    /// [`SwapLightAndDarkStyleTransformation`]: crate::styles::SwapLightAndDarkStyleTransformation
+    pub fn set_style_transformation(&mut self, style_transformation: Box<dyn StyleTransformation>) {
+        self.renderer.set_style_transformation(style_transformation);
+    }
+
+    pub fn output(&mut self) -> &mut dyn Output {
+        self.renderer.output_mut()
+    }
+
+    #[must_use]
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn layout_mut(&mut self) -> &mut Layout {
+        &mut self.layout
+    }
+
+    pub fn clipboard_mut(&mut self) -> &mut Clipboard {
+        &mut self.clipboard
+    }
+
+    /// The buffer of the focused window, for key bindings to edit.
+    #[must_use]
+    pub fn current_buffer(&self) -> Option<Rc<RefCell<Buffer>>> {
+        self.layout.current_buffer()
     }
 
     #[must_use]
@@ -84,76 +186,192 @@ impl Application {
         self.color_depth
     }
 
-    pub fn run(&mut self, wait_for: u64) {
-        self.output.set_title("Prompt Toolkit mini-demo");
-        std::thread::sleep(std::time::Duration::from_secs(wait_for));
+    /// The color depth to render with: [`ColorDepth::Monochrome`] when
+    /// `NO_COLOR` is set, the requested one otherwise.
+    fn output_color_depth(&self) -> ColorDepth {
+        self.forced_color_depth.unwrap_or(self.color_depth)
+    }
 
-        let mut raw_input = self.input.raw_mode();
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.is_done
+    }
 
-        let key_presses = raw_input.read_keys();
-        let mut additional = String::new();
-        for key_press in key_presses {
-            let key_press_event = KeyPressEvent::new(key_press);
-            additional.push_str(key_press_event.key_press.text());
-            self.key_bindings
-                .get_all_keys_bindings_mut()
-                .iter_mut()
-                .for_each(|binding| {
-                    binding.handler(&key_press_event);
-                });
+    /// Stop the running event loop once the current key press has been handled.
+    /// `result` becomes the return value of [`Application::run`].
+    pub fn exit(&mut self, result: Result<T>) {
+        if self.result.is_some() {
+            warn!("application result was already set, ignoring a second exit");
+            return;
         }
+        self.result = Some(result);
+        self.is_done = true;
+    }
 
-        let size = self.output.get_size();
-        let mut screen = Screen::new(None, size.columns, 10);
-        let mut wp = WritePosition::new(0, 0, size.columns, 1);
-        let data = format!("You entered (raw mode not enabled): {additional}");
-        screen.direct_draw(&wp, &data, "bold");
-        wp.ypos += 1;
-        screen.direct_draw(&wp, "italic", "italic");
-        wp.ypos += 1;
-        screen.direct_draw(
-            &wp,
-            "Ansi blue background with Ansi red characters",
-            "bg:ansiblue fg:ansired",
-        );
-        wp.ypos += 1;
-        screen.direct_draw(
-            &wp,
-            "greyer text and background using #RRGGBB",
-            "bg:#111111 fg:#BBBBBB",
+    /// Schedule a redraw of the layout.
+    pub fn invalidate(&mut self) {
+        self.invalidated = true;
+    }
+
+    /// Read key presses and dispatch them to the key bindings until one of
+    /// them calls [`Application::exit`] or the input is closed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error passed to [`Application::exit`], or
+    /// [`ApplicationError::EndOfFile`] when the input closes first.
+    ///
+    /// # Panics
+    ///
+    /// When called again while the application is already running.
+    pub fn run(&mut self) -> Result<T> {
+        let mut input = std::mem::replace(&mut self.input, Box::new(DummyInput));
+        let mut key_processor = KeyProcessor::new(
+            self.key_bindings
+                .take()
+                .expect("key bindings are back after each run"),
         );
-        wp.ypos += 1;
-        screen.direct_draw(&wp, "strike", "strike");
-        wp.ypos += 1;
-        screen.direct_draw(&wp, "underline", "underline");
-        wp.ypos += 1;
-        screen.direct_draw(&wp, "blink", "blink");
-        wp.ypos += 1;
-        screen.direct_draw(&wp, "reverse (inverted colors)", "reverse");
-        wp.ypos += 1;
-        screen.direct_draw(&wp, "hidden", "hidden");
-        wp.xpos += 6;
-        screen.direct_draw(&wp, "<- there is hidden text there", "nohidden");
-
-        render::output_screen(self.output.as_mut(), &screen, &size);
+        {
+            let mut raw_input = input.raw_mode();
+            self.run_loop(&mut raw_input, &mut key_processor);
+        }
+        self.input = input;
+        self.key_bindings = Some(key_processor.into_bindings());
+        self.result
+            .take()
+            .unwrap_or_else(|| Err(ApplicationError::EndOfFile.into()))
+    }
+
+    fn run_loop(&mut self, input: &mut dyn Input, key_processor: &mut KeyProcessor<T>) {
+        self.is_done = false;
+        self.result = None;
+        self.renderer.request_absolute_cursor_position();
+        self.renderer
+            .render(&self.layout, self.output_color_depth(), false);
+        self.invalidated = false;
+
+        while !self.is_done {
+            let timed_out = !input.wait_for_keys(TTIMEOUTLEN);
+            let key_presses = if timed_out {
+                input.flush_keys()
+            } else {
+                input.read_keys()
+            };
+
+            for key_press in key_presses {
+                if key_press.key() == Keys::CPRResponse {
+                    self.handle_cpr_response(key_press.text());
+                    continue;
+                }
+                key_processor.process_key(key_press, self);
+                self.invalidate();
+                if self.is_done {
+                    break;
+                }
+            }
+
+            if timed_out && !self.is_done && !key_processor.pending_keys().is_empty() {
+                // The rest of a key sequence didn't come in time.
+                key_processor.flush(self);
+                self.invalidate();
+            }
+
+            if input.closed() {
+                self.exit(Err(ApplicationError::EndOfFile.into()));
+            }
+
+            self.renderer.check_cpr_timeout();
+
+            if self.invalidated && !self.is_done {
+                self.invalidated = false;
+                self.renderer
+                    .render(&self.layout, self.output_color_depth(), false);
+            }
+        }
+
+        if self.erase_when_done {
+            self.renderer.erase();
+        } else {
+            self.renderer
+                .render(&self.layout, self.output_color_depth(), true);
+        }
+    }
+
+    fn handle_cpr_response(&mut self, data: &str) {
+        if let Some((row, _column)) = parse_cursor_position_response(data) {
+            self.renderer.report_absolute_cursor_row(row);
+            self.invalidate();
+        } else {
+            warn!("could not parse cursor position response {data:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+impl<T: 'static> Application<T> {
+    /// Shows `layout` on dummy input and output, without opening
+    /// `/dev/tty` or reading the environment, for the tests of the key
+    /// bindings.
+    pub(crate) fn for_layout(layout: Layout) -> Self {
+        use crate::{key_bindings::KeyBindings, output::DummyOutput};
+
+        Self {
+            layout,
+            key_bindings: Some(Box::new(KeyBindings::new())),
+            clipboard: Clipboard::default(),
+            color_depth: ColorDepth::default(),
+            forced_color_depth: None,
+            erase_when_done: false,
+            filter: Filter::default(),
+            input: Box::new(DummyInput),
+            renderer: Renderer::new(Box::new(DummyOutput), Box::new(StandardStyle::default())),
+            is_done: false,
+            invalidated: false,
+            result: None,
+        }
+    }
+
+    /// Like [`Application::for_layout`], with a single window editing
+    /// `buffer`.
+    pub(crate) fn for_buffer(buffer: &Rc<RefCell<Buffer>>) -> Self {
+        use crate::layout::{BufferControl, HSplit, Window};
+
+        Self::for_layout(Layout::new(Box::new(HSplit::new(vec![Box::new(
+            Window::new(BufferControl::new(Rc::clone(buffer))),
+        )]))))
+    }
+
+    /// The value passed to [`Application::exit`], if any.
+    pub(crate) fn take_result(&mut self) -> Option<Result<T>> {
+        self.result.take()
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::io::{Read, Seek};
+    use std::{
+        cell::Cell,
+        io::{Read, Seek},
+        rc::Rc,
+    };
 
     use super::*;
+    use crate::{
+        key_bindings::{load_emacs_bindings, Binding, KeyBindings, KeyPressEvent},
+        keys::Keys,
+        layout::{BufferControl, FormattedTextControl, HSplit, VSplit, Window},
+        output::{DummyOutput, OutputEvent, PlainText, RecordingOutput, Size},
+    };
     use tempfile::tempfile;
 
     #[test]
     fn test_new_application() {
-        let layout = Layout;
+        let layout = Layout::default();
         let key_bindings = KeyBindings::new();
-        let clipboard = Clipboard;
+        let clipboard = Clipboard::default();
         let color_depth = ColorDepth::default();
 
-        let mut app = Application::new(
+        let mut app: Application<()> = Application::new(
             layout,
             key_bindings,
             clipboard,
@@ -172,7 +390,12 @@ mod test {
         app.set_output(Box::new(output));
 
         assert_eq!(app.color_depth(), ColorDepth::default());
-        app.run(0);
+        let result = app.run();
+        assert!(app.is_done());
+        assert_eq!(
+            result.expect_err("closed input").downcast_ref(),
+            Some(&ApplicationError::EndOfFile)
+        );
 
         output_clone
             .seek(std::io::SeekFrom::Start(0))
@@ -182,7 +405,237 @@ mod test {
             .read_to_string(&mut output_str)
             .expect("read error");
 
-        // since output is not a tty, uncertain term size values should swallow the content of the default app
-        assert_eq!(output_str, "\u{1b}]2;Prompt Toolkit mini-demo\u{7}\u{1b}[?25l\u{1b}[?7l\u{1b}[0m\u{1b}[?25l\u{1b}[?25h");
+        // the input is an empty file, so the application renders once and exits on EOF.
+        // since output is not a tty, the empty layout is written as plain
+        // lines: nothing at all, not even escape sequences.
+        assert_eq!(output_str, "");
+    }
+
+    #[test]
+    fn test_line_oriented_output() {
+        use std::io::Write;
+
+        let mut key_bindings = load_emacs_bindings();
+        key_bindings.add(&[Keys::ControlM], Box::new(ExitBinding));
+        let layout = Layout::new(Box::new(HSplit::new(vec![
+            Box::new(Window::new(FormattedTextControl::from_text("question"))),
+            Box::new(VSplit::new(vec![
+                Box::new(Window::new(FormattedTextControl::from_text("> ")).with_width(2)),
+                Box::new(Window::new(BufferControl::new(Rc::new(RefCell::new(
+                    Buffer::new(),
+                ))))),
+            ])),
+        ])));
+        let mut app = Application::new(
+            layout,
+            key_bindings,
+            Clipboard::default(),
+            ColorDepth::True,
+            false,
+            Filter::default(),
+        );
+
+        let mut input = tempfile().expect("input temp file");
+        input.write_all(b"hey\x7fllo\r").expect("write error");
+        input.seek(std::io::SeekFrom::Start(0)).expect("seek error");
+        app.set_input(Box::new(VT100Input::new(input.into_raw_fd())));
+        let output = tempfile().expect("output temp file");
+        let mut output_clone = output.try_clone().expect("cloned output temp file");
+        app.set_output(Box::new(PlainText::new(output.into_raw_fd())));
+
+        assert_eq!(app.run().expect("exit value"), 42);
+
+        output_clone
+            .seek(std::io::SeekFrom::Start(0))
+            .expect("seek error");
+        let mut output_str = String::new();
+        output_clone
+            .read_to_string(&mut output_str)
+            .expect("read error");
+        // All keys arrive at once: one render before them, one after.
+        assert_eq!(output_str, "question\n> hello\n");
+    }
+
+    #[test]
+    fn test_recorded_screen() {
+        use std::io::Write;
+
+        let mut key_bindings = load_emacs_bindings();
+        key_bindings.add(&[Keys::ControlM], Box::new(ExitBinding));
+        let layout = Layout::new(Box::new(HSplit::new(vec![
+            Box::new(Window::new(FormattedTextControl::new(vec![(
+                "bold".to_string(),
+                "question".to_string(),
+            )]))),
+            Box::new(VSplit::new(vec![
+                Box::new(Window::new(FormattedTextControl::from_text("> ")).with_width(2)),
+                Box::new(Window::new(BufferControl::new(Rc::new(RefCell::new(
+                    Buffer::new(),
+                ))))),
+            ])),
+        ])));
+        let mut app = Application::new(
+            layout,
+            key_bindings,
+            Clipboard::default(),
+            ColorDepth::True,
+            false,
+            Filter::default(),
+        );
+
+        let mut input = tempfile().expect("input temp file");
+        input.write_all(b"hello\r").expect("write error");
+        input.seek(std::io::SeekFrom::Start(0)).expect("seek error");
+        app.set_input(Box::new(VT100Input::new(input.into_raw_fd())));
+        let recording = RecordingOutput::new(Size {
+            rows: 5,
+            columns: 20,
+        });
+        app.set_output(Box::new(recording.clone()));
+
+        assert_eq!(app.run().expect("exit value"), 42);
+
+        let terminal = recording.terminal();
+        assert_eq!(terminal.lines(), vec!["question", "> hello", "", "", ""]);
+        assert!(terminal.cell(0, 0).attrs.bold.is_on());
+        assert!(!terminal.cell(1, 2).attrs.bold.is_on());
+        assert_eq!(terminal.cursor_position(), (2, 0));
+        assert!(terminal.cursor_visible());
+    }
+
+    #[test]
+    fn test_no_color_renders_without_colors() {
+        let env: HashMap<String, String> = [("NO_COLOR", "1"), ("COLORTERM", "truecolor")]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let layout = Layout::new(Box::new(Window::new(FormattedTextControl::new(vec![(
+            "fg:ansired".to_string(),
+            "red".to_string(),
+        )]))));
+        let mut app: Application<()> = Application::new(
+            layout,
+            KeyBindings::new(),
+            Clipboard::default(),
+            ColorDepth::True,
+            false,
+            Filter::default(),
+        );
+        assert_eq!(ColorDepth::from_env_map(&env), ColorDepth::Monochrome);
+        app.forced_color_depth = forced_color_depth(&env);
+        app.set_input(Box::new(VT100Input::new(
+            tempfile().expect("input temp file").into_raw_fd(),
+        )));
+        let recording = RecordingOutput::new(Size {
+            rows: 5,
+            columns: 20,
+        });
+        app.set_output(Box::new(recording.clone()));
+        assert!(app.run().is_err());
+
+        let depths: Vec<ColorDepth> = recording
+            .events()
+            .into_iter()
+            .filter_map(|event| match event {
+                OutputEvent::SetAttributes(_, color_depth) => Some(color_depth),
+                _ => None,
+            })
+            .collect();
+        assert!(!depths.is_empty());
+        assert!(depths.iter().all(|depth| *depth == ColorDepth::Monochrome));
+        assert_eq!(recording.terminal().row_text(0), "red");
+        assert_eq!(app.color_depth(), ColorDepth::True);
+        assert_eq!(forced_color_depth(&HashMap::new()), None);
+        assert_eq!(
+            forced_color_depth(&HashMap::from([("NO_COLOR".to_string(), String::new())])),
+            None
+        );
+    }
+
+    struct CountingBinding(Rc<Cell<usize>>);
+
+    impl<T> Binding<T> for CountingBinding {
+        fn handler(&mut self, _event: &mut KeyPressEvent<'_, T>) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    struct ExitBinding;
+
+    impl Binding<usize> for ExitBinding {
+        fn handler(&mut self, event: &mut KeyPressEvent<'_, usize>) {
+            event.app_mut().exit(Ok(42));
+        }
+    }
+
+    struct InterruptBinding;
+
+    impl Binding<usize> for InterruptBinding {
+        fn handler(&mut self, event: &mut KeyPressEvent<'_, usize>) {
+            event
+                .app_mut()
+                .exit(Err(ApplicationError::KeyboardInterrupt.into()));
+        }
+    }
+
+    #[test]
+    fn test_run_dispatches_keys_until_exit() {
+        use std::io::Write;
+
+        let count = Rc::new(Cell::new(0));
+        let mut key_bindings = KeyBindings::new();
+        key_bindings.add_for_all_keys(Box::new(CountingBinding(Rc::clone(&count))));
+        key_bindings.add(&[Keys::ControlC], Box::new(ExitBinding));
+
+        let mut app = Application::new(
+            Layout::default(),
+            key_bindings,
+            Clipboard::default(),
+            ColorDepth::default(),
+            false,
+            Filter::default(),
+        );
+
+        let mut input = tempfile().expect("input temp file");
+        input.write_all(b"abc\x03def").expect("write error");
+        input.seek(std::io::SeekFrom::Start(0)).expect("seek error");
+        app.set_input(Box::new(VT100Input::new(input.into_raw_fd())));
+        app.set_output(Box::new(DummyOutput));
+
+        let result = app.run();
+
+        assert!(app.is_done());
+        assert_eq!(result.expect("exit value"), 42);
+        assert_eq!(count.get(), 3);
+    }
+
+    #[test]
+    fn test_run_returns_exit_error() {
+        use std::io::Write;
+
+        let mut key_bindings = KeyBindings::new();
+        key_bindings.add(&[Keys::ControlC], Box::new(InterruptBinding));
+        key_bindings.add(&[Keys::ControlM], Box::new(ExitBinding));
+
+        let mut app = Application::new(
+            Layout::default(),
+            key_bindings,
+            Clipboard::default(),
+            ColorDepth::default(),
+            false,
+            Filter::default(),
+        );
+
+        let mut input = tempfile().expect("input temp file");
+        input.write_all(b"\x03\r").expect("write error");
+        input.seek(std::io::SeekFrom::Start(0)).expect("seek error");
+        app.set_input(Box::new(VT100Input::new(input.into_raw_fd())));
+        app.set_output(Box::new(DummyOutput));
+
+        let result = app.run();
+        assert_eq!(
+            result.expect_err("interrupted").downcast_ref(),
+            Some(&ApplicationError::KeyboardInterrupt)
+        );
     }
 }
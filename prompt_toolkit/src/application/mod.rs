@@ -1,3 +1,3 @@
 mod base;
 
-pub use base::Application;
+pub use base::{Application, ApplicationError};
@@ -0,0 +1,392 @@
+use crate::{
+    clipboard::ClipboardData,
+    document::Document,
+    selection::{PasteMode, SelectionState, SelectionType},
+};
+
+/// Editable text with a cursor, an optional selection and undo history.
+///
+/// Positions are byte offsets into the text, as in [`Document`]. Editing
+/// methods don't record undo steps themselves: call
+/// [`Buffer::save_to_undo_stack`] before a change that should be undoable
+/// on its own.
+#[derive(Debug, Default)]
+pub struct Buffer {
+    text: String,
+    cursor_position: usize,
+    selection_state: Option<SelectionState>,
+    /// Column to return to when moving up and down through shorter lines.
+    preferred_column: Option<usize>,
+    undo_stack: Vec<(String, usize)>,
+    redo_stack: Vec<(String, usize)>,
+}
+
+impl Buffer {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn from_document(document: &Document) -> Self {
+        let mut buffer = Self::new();
+        buffer.set_document(document);
+        buffer
+    }
+
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    #[must_use]
+    pub fn cursor_position(&self) -> usize {
+        self.cursor_position
+    }
+
+    #[must_use]
+    pub fn selection_state(&self) -> Option<&SelectionState> {
+        self.selection_state.as_ref()
+    }
+
+    /// The column that vertical movement tries to stay in.
+    #[must_use]
+    pub fn preferred_column(&self) -> Option<usize> {
+        self.preferred_column
+    }
+
+    /// Restore the column for vertical movement, e.g. after a cursor
+    /// correction that shouldn't count as horizontal movement.
+    pub fn set_preferred_column(&mut self, column: Option<usize>) {
+        self.preferred_column = column;
+    }
+
+    /// The selection, e.g. to change its type.
+    pub fn selection_state_mut(&mut self) -> Option<&mut SelectionState> {
+        self.selection_state.as_mut()
+    }
+
+    /// A snapshot of the text, cursor and selection.
+    #[must_use]
+    pub fn document(&self) -> Document {
+        Document::new(&self.text, self.cursor_position).with_selection(self.selection_state)
+    }
+
+    /// Replace text and cursor position. The selection is kept.
+    pub fn set_document(&mut self, document: &Document) {
+        self.text = document.text().to_string();
+        self.set_cursor_position(document.cursor_position());
+    }
+
+    /// Replace the text, keeping the cursor inside it.
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.set_cursor_position(self.cursor_position);
+    }
+
+    /// Move the cursor, clamped to the text and to a character boundary.
+    pub fn set_cursor_position(&mut self, position: usize) {
+        self.cursor_position = self.text.floor_char_boundary(position);
+        self.preferred_column = None;
+    }
+
+    /// Insert `data` at the cursor. With `overwrite`, replace as many
+    /// characters of the current line instead.
+    pub fn insert_text(&mut self, data: &str, overwrite: bool, move_cursor: bool) {
+        let position = self.cursor_position;
+        let mut end = position;
+        if overwrite {
+            let document = self.document();
+            let replaced: usize = document
+                .current_line_after_cursor()
+                .chars()
+                .take(data.chars().count())
+                .map(char::len_utf8)
+                .sum();
+            end += replaced;
+        }
+        self.text.replace_range(position..end, data);
+        if move_cursor {
+            self.set_cursor_position(position + data.len());
+        }
+    }
+
+    /// Delete up to `count` characters after the cursor and return them.
+    pub fn delete(&mut self, count: usize) -> String {
+        let end = self.cursor_position
+            + self.text[self.cursor_position..]
+                .chars()
+                .take(count)
+                .map(char::len_utf8)
+                .sum::<usize>();
+        self.text.drain(self.cursor_position..end).collect()
+    }
+
+    /// Delete up to `count` characters before the cursor and return them.
+    pub fn delete_before_cursor(&mut self, count: usize) -> String {
+        let start = self.cursor_position
+            - self.text[..self.cursor_position]
+                .chars()
+                .rev()
+                .take(count)
+                .map(char::len_utf8)
+                .sum::<usize>();
+        let deleted = self.text.drain(start..self.cursor_position).collect();
+        self.set_cursor_position(start);
+        deleted
+    }
+
+    pub fn cursor_left(&mut self, count: usize) {
+        let position = self.document().cursor_left_position(count);
+        self.set_cursor_position(position);
+    }
+
+    pub fn cursor_right(&mut self, count: usize) {
+        let position = self.document().cursor_right_position(count);
+        self.set_cursor_position(position);
+    }
+
+    /// Move up `count` lines, staying in the column where vertical movement started.
+    pub fn cursor_up(&mut self, count: usize) {
+        let document = self.document();
+        let column = self
+            .preferred_column
+            .unwrap_or_else(|| document.cursor_position_col());
+        self.set_cursor_position(document.cursor_up_position(count, Some(column)));
+        self.preferred_column = Some(column);
+    }
+
+    /// Move down `count` lines, staying in the column where vertical movement started.
+    pub fn cursor_down(&mut self, count: usize) {
+        let document = self.document();
+        let column = self
+            .preferred_column
+            .unwrap_or_else(|| document.cursor_position_col());
+        self.set_cursor_position(document.cursor_down_position(count, Some(column)));
+        self.preferred_column = Some(column);
+    }
+
+    /// Insert a line break, indenting the new line like the current one with `copy_margin`.
+    pub fn newline(&mut self, copy_margin: bool) {
+        let margin = if copy_margin {
+            self.document()
+                .leading_whitespace_in_current_line()
+                .to_string()
+        } else {
+            String::new()
+        };
+        self.insert_text(&format!("\n{margin}"), false, true);
+    }
+
+    /// Start selecting from the cursor.
+    pub fn start_selection(&mut self, selection_type: SelectionType) {
+        self.selection_state = Some(SelectionState::new(self.cursor_position, selection_type));
+    }
+
+    pub fn exit_selection(&mut self) {
+        self.selection_state = None;
+    }
+
+    /// The selected text, leaving the buffer as it is.
+    #[must_use]
+    pub fn copy_selection(&self) -> ClipboardData {
+        self.document().cut_selection().1
+    }
+
+    /// Remove the selected text and return it. The selection ends.
+    pub fn cut_selection(&mut self) -> ClipboardData {
+        let (document, data) = self.document().cut_selection();
+        self.set_document(&document);
+        self.exit_selection();
+        data
+    }
+
+    /// Insert clipboard data, see [`Document::paste_clipboard_data`].
+    pub fn paste_clipboard_data(
+        &mut self,
+        data: &ClipboardData,
+        paste_mode: PasteMode,
+        count: usize,
+    ) {
+        let document = self
+            .document()
+            .paste_clipboard_data(data, paste_mode, count);
+        self.set_document(&document);
+    }
+
+    /// Swap the two characters before the cursor.
+    pub fn swap_characters_before_cursor(&mut self) {
+        let mut chars = self.text[..self.cursor_position].char_indices().rev();
+        if let (Some((_, b)), Some((start, a))) = (chars.next(), chars.next()) {
+            self.text
+                .replace_range(start..self.cursor_position, &format!("{b}{a}"));
+        }
+    }
+
+    /// Remember the current text and cursor as an undo step.
+    ///
+    /// Nothing is recorded when the text didn't change since the last step.
+    /// A new step makes the undone steps unreachable.
+    pub fn save_to_undo_stack(&mut self) {
+        self.push_undo_step();
+        self.redo_stack.clear();
+    }
+
+    fn push_undo_step(&mut self) {
+        if self
+            .undo_stack
+            .last()
+            .is_none_or(|(text, _)| *text != self.text)
+        {
+            self.undo_stack
+                .push((self.text.clone(), self.cursor_position));
+        }
+    }
+
+    /// Go back to the last undo step with a different text. Returns
+    /// whether there was one.
+    pub fn undo(&mut self) -> bool {
+        while let Some((text, cursor_position)) = self.undo_stack.pop() {
+            if text != self.text {
+                self.redo_stack
+                    .push((self.text.clone(), self.cursor_position));
+                self.restore(&text, cursor_position);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Reapply the last undone change. Returns whether there was one.
+    pub fn redo(&mut self) -> bool {
+        let Some((text, cursor_position)) = self.redo_stack.pop() else {
+            return false;
+        };
+        self.push_undo_step();
+        self.restore(&text, cursor_position);
+        true
+    }
+
+    fn restore(&mut self, text: &str, cursor_position: usize) {
+        self.text = text.to_string();
+        self.set_cursor_position(cursor_position);
+        self.exit_selection();
+    }
+
+    /// Start over with `document`, forgetting the selection and undo history.
+    pub fn reset(&mut self, document: &Document) {
+        *self = Self::from_document(document);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_delete() {
+        let mut buffer = Buffer::new();
+        buffer.insert_text("héllo", false, true);
+        assert_eq!(buffer.cursor_position(), 6);
+
+        buffer.cursor_left(4);
+        buffer.insert_text("XY", true, true);
+        assert_eq!(buffer.text(), "hXYlo");
+        assert_eq!(buffer.cursor_position(), 3);
+
+        buffer.insert_text("-", false, false);
+        assert_eq!(buffer.text(), "hXY-lo");
+        assert_eq!(buffer.cursor_position(), 3);
+
+        assert_eq!(buffer.delete(2), "-l");
+        assert_eq!(buffer.delete_before_cursor(5), "hXY");
+        assert_eq!(buffer.text(), "o");
+        assert_eq!(buffer.cursor_position(), 0);
+        assert_eq!(buffer.delete_before_cursor(1), "");
+    }
+
+    #[test]
+    fn test_vertical_movement_keeps_preferred_column() {
+        let mut buffer = Buffer::from_document(&Document::new("long line\nab\nlong line", 7));
+        buffer.cursor_down(1);
+        assert_eq!(buffer.cursor_position(), 12);
+        buffer.cursor_down(1);
+        assert_eq!(buffer.cursor_position(), 20);
+        buffer.cursor_up(2);
+        assert_eq!(buffer.cursor_position(), 7);
+    }
+
+    #[test]
+    fn test_newline_copies_margin() {
+        let mut buffer = Buffer::from_document(&Document::from_text("  indented"));
+        buffer.newline(true);
+        buffer.insert_text("x", false, true);
+        assert_eq!(buffer.text(), "  indented\n  x");
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut buffer = Buffer::from_document(&Document::new("one two three", 4));
+        buffer.start_selection(SelectionType::Characters);
+        buffer.cursor_right(3);
+        assert_eq!(buffer.copy_selection().text, "two");
+        assert_eq!(buffer.cut_selection().text, "two");
+        assert_eq!(buffer.text(), "one  three");
+        assert_eq!(buffer.cursor_position(), 4);
+        assert!(buffer.selection_state().is_none());
+    }
+
+    #[test]
+    fn test_selection_start_after_deleting_text() {
+        let mut buffer = Buffer::from_document(&Document::from_text("hello"));
+        buffer.start_selection(SelectionType::Characters);
+        buffer.delete_before_cursor(5);
+        assert_eq!(buffer.document().selection_ranges(), vec![(0, 0)]);
+        assert_eq!(buffer.cut_selection().text, "");
+
+        let mut buffer = Buffer::from_document(&Document::from_text("hello world"));
+        buffer.start_selection(SelectionType::Lines);
+        buffer.set_text("hi");
+        assert_eq!(buffer.copy_selection().text, "hi");
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut buffer = Buffer::new();
+        buffer.save_to_undo_stack();
+        buffer.insert_text("hello", false, true);
+        buffer.save_to_undo_stack();
+        buffer.insert_text(" world", false, true);
+
+        buffer.undo();
+        assert_eq!(buffer.text(), "hello");
+        buffer.undo();
+        assert_eq!(buffer.text(), "");
+        buffer.undo();
+        assert_eq!(buffer.text(), "");
+
+        buffer.redo();
+        assert_eq!(buffer.text(), "hello");
+        buffer.redo();
+        assert_eq!(buffer.text(), "hello world");
+        assert_eq!(buffer.cursor_position(), 11);
+
+        buffer.undo();
+        buffer.save_to_undo_stack();
+        buffer.insert_text("!", false, true);
+        buffer.redo();
+        assert_eq!(buffer.text(), "hello!");
+    }
+
+    #[test]
+    fn test_swap_characters_before_cursor() {
+        let mut buffer = Buffer::from_document(&Document::new("aéc", 3));
+        buffer.swap_characters_before_cursor();
+        assert_eq!(buffer.text(), "éac");
+        assert_eq!(buffer.cursor_position(), 3);
+
+        let mut buffer = Buffer::from_document(&Document::new("ab", 1));
+        buffer.swap_characters_before_cursor();
+        assert_eq!(buffer.text(), "ab");
+    }
+}
@@ -1 +1,91 @@
-pub struct Clipboard;
+use std::collections::VecDeque;
+
+use crate::selection::SelectionType;
+
+/// Default number of entries kept by a [`Clipboard`].
+const MAX_SIZE: usize = 60;
+
+/// An in-memory kill ring. The most recent entry is the current one.
+#[derive(Debug)]
+pub struct Clipboard {
+    ring: VecDeque<ClipboardData>,
+    max_size: usize,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new(MAX_SIZE)
+    }
+}
+
+impl Clipboard {
+    #[must_use]
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            ring: VecDeque::new(),
+            max_size: max_size.max(1),
+        }
+    }
+
+    /// Make `data` the current entry, dropping the oldest one when full.
+    pub fn set_data(&mut self, data: ClipboardData) {
+        self.ring.push_front(data);
+        self.ring.truncate(self.max_size);
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.set_data(ClipboardData::new(text, SelectionType::Characters));
+    }
+
+    /// The current entry, empty when nothing was stored yet.
+    #[must_use]
+    pub fn get_data(&self) -> ClipboardData {
+        self.ring.front().cloned().unwrap_or_default()
+    }
+
+    /// Make the previous entry current, as Meta-Y does after a yank.
+    pub fn rotate(&mut self) {
+        if let Some(data) = self.ring.pop_front() {
+            self.ring.push_back(data);
+        }
+    }
+}
+
+/// Text put on a clipboard, remembering how it was selected so that it can
+/// be pasted back the same way.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClipboardData {
+    pub text: String,
+    pub selection_type: SelectionType,
+}
+
+impl ClipboardData {
+    #[must_use]
+    pub fn new(text: &str, selection_type: SelectionType) -> Self {
+        Self {
+            text: text.to_string(),
+            selection_type,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kill_ring() {
+        let mut clipboard = Clipboard::new(2);
+        assert_eq!(clipboard.get_data(), ClipboardData::default());
+
+        clipboard.set_text("one");
+        clipboard.set_text("two");
+        clipboard.set_text("three");
+        assert_eq!(clipboard.get_data().text, "three");
+
+        clipboard.rotate();
+        assert_eq!(clipboard.get_data().text, "two");
+        clipboard.rotate();
+        assert_eq!(clipboard.get_data().text, "three");
+    }
+}
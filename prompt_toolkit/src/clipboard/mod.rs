@@ -1,3 +1,3 @@
 mod base;
 
-pub use base::Clipboard;
+pub use base::{Clipboard, ClipboardData};
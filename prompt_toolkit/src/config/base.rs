@@ -0,0 +1,353 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use toml::de::{DeTable, DeValue};
+
+use crate::{
+    key_bindings::{KeyBindings, NamedCommands},
+    keys::{parse_key_sequence, InvalidKeyError},
+    styles::{is_valid_class_names, StandardStyle},
+};
+
+/// Key bindings and a style read from a configuration file, so users can
+/// customise a prompt without recompiling it:
+///
+/// ```toml
+/// [key_bindings]
+/// "c-a" = "beginning-of-line"
+/// "escape f" = "forward-word"
+///
+/// [style]
+/// prompt = "bold"
+/// "completion-menu.completion" = "bg:ansiblue"
+/// ```
+///
+/// JSON files have the same layout, with an object for each table. Keys
+/// are written as for [`parse_key_sequence`] and commands are looked up in
+/// [`NamedCommands`]. Style rules keep their order in the file. Files can
+/// bind `"accept-line"` only when the commands were built
+/// [`with_accept_line`](NamedCommands::with_accept_line).
+pub struct Config<T> {
+    pub key_bindings: KeyBindings<T>,
+    pub style: StandardStyle,
+}
+
+impl<T> Config<T> {
+    /// Read a file, as JSON if its extension is `.json` and as TOML
+    /// otherwise.
+    ///
+    /// # Errors
+    ///
+    /// When the file can't be read or has a [`ConfigError`].
+    pub fn load(path: &Path, commands: &NamedCommands<T>) -> Result<Self> {
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let config = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            Self::from_json(&source, commands)
+        } else {
+            Self::from_toml(&source, commands)
+        };
+        config.with_context(|| format!("in {}", path.display()))
+    }
+
+    /// # Errors
+    ///
+    /// The first problem in `source`, with its line.
+    pub fn from_toml(source: &str, commands: &NamedCommands<T>) -> Result<Self, ConfigError> {
+        Self::from_tables(parse_toml(source)?, commands)
+    }
+
+    /// # Errors
+    ///
+    /// The first problem in `source`, with its line.
+    pub fn from_json(source: &str, commands: &NamedCommands<T>) -> Result<Self, ConfigError> {
+        Self::from_tables(super::json::parse(source)?, commands)
+    }
+
+    fn from_tables(tables: Vec<Table>, commands: &NamedCommands<T>) -> Result<Self, ConfigError> {
+        let mut key_bindings = KeyBindings::new();
+        let mut style_rules = Vec::new();
+        for table in tables {
+            match table.name.as_str() {
+                "key_bindings" => {
+                    for entry in table.entries {
+                        let keys = parse_key_sequence(&entry.key).map_err(|e| {
+                            ConfigError::new(entry.line, ConfigErrorKind::InvalidKey(e))
+                        })?;
+                        let binding = commands.get(&entry.value).ok_or_else(|| {
+                            ConfigError::new(
+                                entry.line,
+                                ConfigErrorKind::UnknownCommand(entry.value),
+                            )
+                        })?;
+                        key_bindings.add(&keys, binding);
+                    }
+                }
+                "style" => {
+                    for entry in table.entries {
+                        if !is_valid_class_names(&entry.key) {
+                            return Err(ConfigError::new(
+                                entry.line,
+                                ConfigErrorKind::InvalidClassNames(entry.key),
+                            ));
+                        }
+                        style_rules.push((entry.key, entry.value));
+                    }
+                }
+                _ => {
+                    return Err(ConfigError::new(
+                        table.line,
+                        ConfigErrorKind::UnknownTable(table.name),
+                    ))
+                }
+            }
+        }
+        let style = StandardStyle::new(style_rules).expect("class names are validated");
+        Ok(Config {
+            key_bindings,
+            style,
+        })
+    }
+}
+
+/// A table of a configuration file, before its entries are interpreted.
+pub(super) struct Table {
+    pub(super) line: usize,
+    pub(super) name: String,
+    pub(super) entries: Vec<Entry>,
+}
+
+pub(super) struct Entry {
+    pub(super) line: usize,
+    pub(super) key: String,
+    pub(super) value: String,
+}
+
+/// The 1-based line of the byte `offset` in `source`.
+pub(super) fn line_at(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+fn parse_toml(source: &str) -> Result<Vec<Table>, ConfigError> {
+    let document = DeTable::parse(source).map_err(|e| {
+        let line = e.span().map_or(1, |span| line_at(source, span.start));
+        ConfigError::new(line, ConfigErrorKind::Syntax(e.message().to_string()))
+    })?;
+    let mut tables = Vec::new();
+    for (name, value) in document.get_ref() {
+        let line = line_at(source, name.span().start);
+        let DeValue::Table(table) = value.get_ref() else {
+            return Err(ConfigError::new(
+                line,
+                ConfigErrorKind::ExpectedTable(name.get_ref().to_string()),
+            ));
+        };
+        let mut entries = Vec::new();
+        for (key, value) in table {
+            let line = line_at(source, key.span().start);
+            let DeValue::String(value) = value.get_ref() else {
+                return Err(ConfigError::new(
+                    line,
+                    ConfigErrorKind::ExpectedString(key.get_ref().to_string()),
+                ));
+            };
+            entries.push(Entry {
+                line,
+                key: key.get_ref().to_string(),
+                value: value.to_string(),
+            });
+        }
+        entries.sort_by_key(|entry| entry.line);
+        tables.push(Table {
+            line,
+            name: name.get_ref().to_string(),
+            entries,
+        });
+    }
+    tables.sort_by_key(|table| table.line);
+    Ok(tables)
+}
+
+/// A problem in a configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// 1-based line of the problem.
+    pub line: usize,
+    pub kind: ConfigErrorKind,
+}
+
+impl ConfigError {
+    pub(super) fn new(line: usize, kind: ConfigErrorKind) -> Self {
+        Self { line, kind }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigErrorKind {
+    /// The file is not valid TOML or JSON.
+    Syntax(String),
+    /// A table other than `key_bindings` and `style`.
+    UnknownTable(String),
+    /// A top-level entry that is not a table.
+    ExpectedTable(String),
+    /// An entry of a table whose value is not a string.
+    ExpectedString(String),
+    InvalidKey(InvalidKeyError),
+    /// A command that is not in [`NamedCommands`].
+    UnknownCommand(String),
+    /// Class names that [`StandardStyle::new`] rejects.
+    InvalidClassNames(String),
+}
+
+impl Error for ConfigError {}
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ConfigErrorKind::Syntax(message) => write!(f, "{message}"),
+            ConfigErrorKind::UnknownTable(name) => write!(f, "unknown table {name:?}"),
+            ConfigErrorKind::ExpectedTable(name) => write!(f, "{name:?} should be a table"),
+            ConfigErrorKind::ExpectedString(key) => write!(f, "{key:?} should be a string"),
+            ConfigErrorKind::InvalidKey(e) => write!(f, "{e}"),
+            ConfigErrorKind::UnknownCommand(name) => write!(f, "unknown command {name:?}"),
+            ConfigErrorKind::InvalidClassNames(class_names) => {
+                write!(f, "invalid class names {class_names:?}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{keys::Keys, styles::Style};
+
+    const TOML: &str = r#"
+# Emacs-ish
+[key_bindings]
+"c-a" = "beginning-of-line"
+"escape f" = "forward-word"
+
+[style]
+prompt = "bold"
+"completion-menu.completion" = "bg:ansiblue"
+"#;
+
+    fn load_toml(source: &str) -> Result<Config<()>, ConfigError> {
+        Config::from_toml(source, &NamedCommands::new())
+    }
+
+    fn load_json(source: &str) -> Result<Config<()>, ConfigError> {
+        Config::from_json(source, &NamedCommands::new())
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let config = load_toml(TOML).expect("valid config");
+        assert!(config.key_bindings.get(&[Keys::ControlA]).is_some());
+        assert!(config
+            .key_bindings
+            .get(&[Keys::Escape, Keys::Character('f')])
+            .is_some());
+        assert_eq!(
+            config.style.style_rules(),
+            vec![
+                ("prompt".to_string(), "bold".to_string()),
+                (
+                    "completion-menu.completion".to_string(),
+                    "bg:ansiblue".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_toml_errors_have_lines() {
+        let error = |source| load_toml(source).err().expect("invalid config");
+        assert_eq!(
+            error("[key_bindings]\n\"c-a\" = \"beginning-of-line\"\n\"c-b\" = \"fly\"\n"),
+            ConfigError::new(3, ConfigErrorKind::UnknownCommand("fly".to_string()))
+        );
+        assert_eq!(
+            error("[key_bindings]\n\"c-nope\" = \"undo\"\n"),
+            ConfigError::new(
+                2,
+                ConfigErrorKind::InvalidKey(InvalidKeyError("c-nope".to_string()))
+            )
+        );
+        assert_eq!(
+            error("\n[style]\n\"Prompt!\" = \"bold\"\n"),
+            ConfigError::new(3, ConfigErrorKind::InvalidClassNames("Prompt!".to_string()))
+        );
+        assert_eq!(
+            error("[colors]\n"),
+            ConfigError::new(1, ConfigErrorKind::UnknownTable("colors".to_string()))
+        );
+        assert_eq!(
+            error("[style]\nprompt = 1\n"),
+            ConfigError::new(2, ConfigErrorKind::ExpectedString("prompt".to_string()))
+        );
+        assert_eq!(error("[style]\nprompt = \n").line, 2);
+    }
+
+    #[test]
+    fn test_from_json() {
+        let config = load_json(
+            r#"{
+                "key_bindings": {"c-x c-e": "undo"},
+                "style": {"prompt": "bold", "": "italic"}
+            }"#,
+        )
+        .expect("valid config");
+        assert!(config
+            .key_bindings
+            .get(&[Keys::ControlX, Keys::ControlE])
+            .is_some());
+        assert_eq!(config.style.style_rules().len(), 2);
+        assert_eq!(
+            load_json("{\n\"key_bindings\": {\n\"c-a\": \"fly\"\n}}").err(),
+            Some(ConfigError::new(
+                3,
+                ConfigErrorKind::UnknownCommand("fly".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn test_accept_line_needs_to_be_added() {
+        let source = "[key_bindings]\n\"c-m\" = \"accept-line\"\n";
+        assert_eq!(
+            load_toml(source).err(),
+            Some(ConfigError::new(
+                2,
+                ConfigErrorKind::UnknownCommand("accept-line".to_string())
+            ))
+        );
+        let commands = NamedCommands::<String>::new().with_accept_line();
+        let config = Config::from_toml(source, &commands).expect("valid config");
+        assert!(config.key_bindings.get(&[Keys::ControlM]).is_some());
+    }
+
+    #[test]
+    fn test_load_picks_the_format_by_extension() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let toml = dir.path().join("prompt.toml");
+        std::fs::write(&toml, TOML).expect("write");
+        let json = dir.path().join("prompt.json");
+        std::fs::write(&json, r#"{"style": {"prompt": "bold"}}"#).expect("write");
+        let commands = NamedCommands::<()>::new();
+        assert!(Config::load(&toml, &commands).is_ok());
+        assert!(Config::load(&json, &commands).is_ok());
+        let error = Config::load(&dir.path().join("missing.toml"), &commands)
+            .err()
+            .expect("missing file");
+        assert!(error.to_string().contains("missing.toml"));
+    }
+}
@@ -0,0 +1,215 @@
+//! Just enough of JSON for configuration files: an object of objects of
+//! strings, with the line of each entry.
+
+use std::collections::HashSet;
+
+use crate::config::base::{line_at, ConfigError, ConfigErrorKind, Entry, Table};
+
+pub(super) fn parse(source: &str) -> Result<Vec<Table>, ConfigError> {
+    let mut parser = Parser { source, pos: 0 };
+    let mut tables = Vec::new();
+    parser.object(|parser, name, line| {
+        if parser.peek() != Some('{') {
+            return Err(parser.error(ConfigErrorKind::ExpectedTable(name)));
+        }
+        let mut entries = Vec::new();
+        parser.object(|parser, key, line| {
+            if parser.peek() != Some('"') {
+                return Err(parser.error(ConfigErrorKind::ExpectedString(key)));
+            }
+            let value = parser.string()?;
+            entries.push(Entry { line, key, value });
+            Ok(())
+        })?;
+        tables.push(Table {
+            line,
+            name,
+            entries,
+        });
+        Ok(())
+    })?;
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(parser.syntax_error("trailing characters"));
+    }
+    Ok(tables)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    /// Byte offset of the next character.
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .peek()
+            .is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ConfigError> {
+        self.skip_whitespace();
+        if self.next() == Some(expected) {
+            Ok(())
+        } else {
+            Err(self.syntax_error(&format!("expected {expected:?}")))
+        }
+    }
+
+    /// Parse an object, calling `member` with each key and its line, and
+    /// with the position at the value. Keys must be unique, as in TOML.
+    fn object(
+        &mut self,
+        mut member: impl FnMut(&mut Self, String, usize) -> Result<(), ConfigError>,
+    ) -> Result<(), ConfigError> {
+        self.expect('{')?;
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(());
+        }
+        let mut keys = HashSet::new();
+        loop {
+            self.skip_whitespace();
+            let line = line_at(self.source, self.pos);
+            let key = self.string()?;
+            if !keys.insert(key.clone()) {
+                return Err(ConfigError::new(
+                    line,
+                    ConfigErrorKind::Syntax(format!("duplicate key {key:?}")),
+                ));
+            }
+            self.expect(':')?;
+            self.skip_whitespace();
+            member(self, key, line)?;
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => {}
+                Some('}') => return Ok(()),
+                _ => return Err(self.syntax_error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ConfigError> {
+        if self.next() != Some('"') {
+            return Err(self.syntax_error("expected a string"));
+        }
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let c = match self.next() {
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        Some(c @ ('"' | '\\' | '/')) => c,
+                        _ => return Err(self.syntax_error("invalid escape")),
+                    };
+                    string.push(c);
+                }
+                Some(c) => string.push(c),
+                None => return Err(self.syntax_error("unterminated string")),
+            }
+        }
+    }
+
+    /// The character of a `\u` escape, which may be a surrogate pair.
+    fn unicode_escape(&mut self) -> Result<char, ConfigError> {
+        let mut units = vec![self.hex4()?];
+        if (0xd800..0xdc00).contains(&units[0]) && self.source[self.pos..].starts_with("\\u") {
+            self.pos += 2;
+            units.push(self.hex4()?);
+        }
+        char::decode_utf16(units)
+            .next()
+            .and_then(Result::ok)
+            .ok_or_else(|| self.syntax_error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u16, ConfigError> {
+        let digits = self.source.get(self.pos..self.pos + 4);
+        let unit = digits.and_then(|digits| u16::from_str_radix(digits, 16).ok());
+        let unit = unit.ok_or_else(|| self.syntax_error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(unit)
+    }
+
+    fn error(&self, kind: ConfigErrorKind) -> ConfigError {
+        ConfigError::new(line_at(self.source, self.pos), kind)
+    }
+
+    fn syntax_error(&self, message: &str) -> ConfigError {
+        self.error(ConfigErrorKind::Syntax(message.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let tables = parse("{\"style\": {\n\"a\": \"x\\\"\\u00e9\\ud83d\\ude00\",\n\"b\" : \"\"}, \"key_bindings\": {}}")
+            .expect("valid json");
+        assert_eq!(tables.len(), 2);
+        let entries: Vec<_> = tables[0]
+            .entries
+            .iter()
+            .map(|entry| (entry.line, entry.key.as_str(), entry.value.as_str()))
+            .collect();
+        assert_eq!(entries, vec![(2, "a", "x\"é😀"), (3, "b", "")]);
+        assert!(tables[1].entries.is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source| parse(source).err().expect("invalid json");
+        assert_eq!(
+            error("{\"style\": [1]}").kind,
+            ConfigErrorKind::ExpectedTable("style".to_string())
+        );
+        assert_eq!(
+            error("{\"style\": {\n\"a\": true}}"),
+            ConfigError::new(2, ConfigErrorKind::ExpectedString("a".to_string()))
+        );
+        assert_eq!(error("{\"style\": {}\n\n").line, 3);
+        assert!(matches!(error("{} {}").kind, ConfigErrorKind::Syntax(_)));
+        assert!(matches!(error("").kind, ConfigErrorKind::Syntax(_)));
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let error = |source| parse(source).err().expect("invalid json");
+        assert_eq!(
+            error("{\"style\": {\n\"prompt\": \"bold\",\n\"prompt\": \"italic\"}}"),
+            ConfigError::new(
+                3,
+                ConfigErrorKind::Syntax("duplicate key \"prompt\"".to_string())
+            )
+        );
+        assert_eq!(
+            error("{\"key_bindings\": {},\n\"key_bindings\": {}}").line,
+            2
+        );
+        assert!(parse("{\"style\": {\"a\": \"\"}, \"key_bindings\": {\"a\": \"\"}}").is_ok());
+    }
+}
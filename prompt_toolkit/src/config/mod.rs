@@ -0,0 +1,4 @@
+mod base;
+mod json;
+
+pub use base::{Config, ConfigError, ConfigErrorKind};
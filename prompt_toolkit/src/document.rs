@@ -0,0 +1,705 @@
+use crate::{
+    clipboard::ClipboardData,
+    key_bindings::MAX_ARG_COUNT,
+    selection::{PasteMode, SelectionState, SelectionType},
+};
+
+/// Kind of character, as far as word motions are concerned: a word is a run
+/// of characters of the same kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+/// With `big_word`, anything but whitespace is part of a word (a vi `WORD`).
+fn char_class(c: char, big_word: bool) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Whitespace
+    } else if big_word || c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// Byte ranges of the words in `text`.
+fn word_spans(text: &str, big_word: bool) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut current: Option<(usize, CharClass)> = None;
+    for (i, c) in text.char_indices() {
+        let class = char_class(c, big_word);
+        match current {
+            Some((_, current_class)) if current_class == class => {}
+            Some((start, current_class)) => {
+                if current_class != CharClass::Whitespace {
+                    spans.push((start, i));
+                }
+                current = Some((i, class));
+            }
+            None => current = Some((i, class)),
+        }
+    }
+    if let Some((start, class)) = current {
+        if class != CharClass::Whitespace {
+            spans.push((start, text.len()));
+        }
+    }
+    spans
+}
+
+/// An immutable snapshot of some text, the cursor and the selection.
+///
+/// All positions are byte offsets into the text, on character boundaries.
+/// Columns count characters from the start of their line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    text: String,
+    cursor_position: usize,
+    selection: Option<SelectionState>,
+}
+
+impl Document {
+    /// The cursor is clamped to the text and moved back to a character boundary.
+    #[must_use]
+    pub fn new(text: &str, cursor_position: usize) -> Self {
+        Self {
+            text: text.to_string(),
+            cursor_position: text.floor_char_boundary(cursor_position),
+            selection: None,
+        }
+    }
+
+    /// A document with the cursor at the end of `text`.
+    #[must_use]
+    pub fn from_text(text: &str) -> Self {
+        Self::new(text, text.len())
+    }
+
+    /// Select from the selection's start to the cursor. A start past the
+    /// end of the text, left by an edit, is moved to the end.
+    #[must_use]
+    pub fn with_selection(mut self, selection: Option<SelectionState>) -> Self {
+        self.selection = selection.map(|mut selection| {
+            selection.original_cursor_position = self
+                .text
+                .floor_char_boundary(selection.original_cursor_position);
+            selection
+        });
+        self
+    }
+
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    #[must_use]
+    pub fn cursor_position(&self) -> usize {
+        self.cursor_position
+    }
+
+    #[must_use]
+    pub fn selection(&self) -> Option<&SelectionState> {
+        self.selection.as_ref()
+    }
+
+    #[must_use]
+    pub fn text_before_cursor(&self) -> &str {
+        &self.text[..self.cursor_position]
+    }
+
+    #[must_use]
+    pub fn text_after_cursor(&self) -> &str {
+        &self.text[self.cursor_position..]
+    }
+
+    /// The character under the cursor.
+    #[must_use]
+    pub fn current_char(&self) -> Option<char> {
+        self.text_after_cursor().chars().next()
+    }
+
+    #[must_use]
+    pub fn char_before_cursor(&self) -> Option<char> {
+        self.text_before_cursor().chars().next_back()
+    }
+
+    #[must_use]
+    pub fn current_line_before_cursor(&self) -> &str {
+        let before = self.text_before_cursor();
+        before.rsplit('\n').next().unwrap_or(before)
+    }
+
+    #[must_use]
+    pub fn current_line_after_cursor(&self) -> &str {
+        let after = self.text_after_cursor();
+        after.split('\n').next().unwrap_or(after)
+    }
+
+    #[must_use]
+    pub fn current_line(&self) -> &str {
+        &self.text[self.start_of_line_position()..self.end_of_line_position()]
+    }
+
+    /// The whitespace at the start of the line with the cursor.
+    #[must_use]
+    pub fn leading_whitespace_in_current_line(&self) -> &str {
+        let line = self.current_line();
+        &line[..line.len() - line.trim_start().len()]
+    }
+
+    #[must_use]
+    pub fn lines(&self) -> Vec<&str> {
+        self.text.split('\n').collect()
+    }
+
+    #[must_use]
+    pub fn line_count(&self) -> usize {
+        self.text.matches('\n').count() + 1
+    }
+
+    #[must_use]
+    pub fn cursor_position_row(&self) -> usize {
+        self.translate_index_to_position(self.cursor_position).0
+    }
+
+    #[must_use]
+    pub fn cursor_position_col(&self) -> usize {
+        self.translate_index_to_position(self.cursor_position).1
+    }
+
+    #[must_use]
+    pub fn is_cursor_at_the_end(&self) -> bool {
+        self.cursor_position == self.text.len()
+    }
+
+    #[must_use]
+    pub fn is_cursor_at_the_end_of_line(&self) -> bool {
+        self.current_line_after_cursor().is_empty()
+    }
+
+    #[must_use]
+    pub fn on_first_line(&self) -> bool {
+        !self.text_before_cursor().contains('\n')
+    }
+
+    #[must_use]
+    pub fn on_last_line(&self) -> bool {
+        !self.text_after_cursor().contains('\n')
+    }
+
+    /// `(row, column)` of the byte offset `index`.
+    #[must_use]
+    pub fn translate_index_to_position(&self, index: usize) -> (usize, usize) {
+        let before = &self.text[..self.text.floor_char_boundary(index)];
+        let row = before.matches('\n').count();
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (row, before[line_start..].chars().count())
+    }
+
+    /// Byte offset of `(row, column)`, clamped to the existing lines and columns.
+    #[must_use]
+    pub fn translate_row_col_to_index(&self, row: usize, col: usize) -> usize {
+        let mut line_start = 0;
+        let mut line = "";
+        for (i, current) in self.text.split('\n').enumerate() {
+            line = current;
+            if i == row {
+                break;
+            }
+            line_start += current.len() + 1;
+        }
+        if row >= self.line_count() {
+            // Went past the last line: back to its start.
+            line_start -= line.len() + 1;
+        }
+        line_start
+            + line
+                .char_indices()
+                .nth(col)
+                .map_or(line.len(), |(offset, _)| offset)
+    }
+
+    #[must_use]
+    pub fn start_of_line_position(&self) -> usize {
+        self.cursor_position - self.current_line_before_cursor().len()
+    }
+
+    #[must_use]
+    pub fn end_of_line_position(&self) -> usize {
+        self.cursor_position + self.current_line_after_cursor().len()
+    }
+
+    /// Position after moving `count` characters left, without leaving the line.
+    #[must_use]
+    pub fn cursor_left_position(&self, count: usize) -> usize {
+        let before = self.current_line_before_cursor();
+        let moved: usize = before.chars().rev().take(count).map(char::len_utf8).sum();
+        self.cursor_position - moved
+    }
+
+    /// Position after moving `count` characters right, without leaving the line.
+    #[must_use]
+    pub fn cursor_right_position(&self, count: usize) -> usize {
+        let after = self.current_line_after_cursor();
+        let moved: usize = after.chars().take(count).map(char::len_utf8).sum();
+        self.cursor_position + moved
+    }
+
+    /// Position after moving `count` lines up, trying to stay in
+    /// `preferred_column` (by default the current column).
+    #[must_use]
+    pub fn cursor_up_position(&self, count: usize, preferred_column: Option<usize>) -> usize {
+        let (row, col) = self.translate_index_to_position(self.cursor_position);
+        self.translate_row_col_to_index(row.saturating_sub(count), preferred_column.unwrap_or(col))
+    }
+
+    /// Position after moving `count` lines down, trying to stay in
+    /// `preferred_column` (by default the current column).
+    #[must_use]
+    pub fn cursor_down_position(&self, count: usize, preferred_column: Option<usize>) -> usize {
+        let (row, col) = self.translate_index_to_position(self.cursor_position);
+        let row = std::cmp::min(row + count, self.line_count() - 1);
+        self.translate_row_col_to_index(row, preferred_column.unwrap_or(col))
+    }
+
+    /// Start of the `count`th word after the cursor, not counting a word
+    /// that starts right at the cursor.
+    #[must_use]
+    pub fn find_next_word_beginning(&self, count: usize, big_word: bool) -> Option<usize> {
+        let spans = word_spans(self.text_after_cursor(), big_word);
+        let skip = usize::from(spans.first().is_some_and(|(start, _)| *start == 0));
+        spans
+            .get(count.checked_sub(1)? + skip)
+            .map(|(start, _)| self.cursor_position + start)
+    }
+
+    /// Position right after the end of the `count`th word after the cursor.
+    ///
+    /// Unless `include_current_position` is set, a word ending right after
+    /// the character under the cursor doesn't count.
+    #[must_use]
+    pub fn find_next_word_ending(
+        &self,
+        count: usize,
+        big_word: bool,
+        include_current_position: bool,
+    ) -> Option<usize> {
+        let offset = if include_current_position {
+            self.cursor_position
+        } else {
+            self.cursor_position + self.current_char().map_or(0, char::len_utf8)
+        };
+        let spans = word_spans(&self.text[offset..], big_word);
+        spans
+            .get(count.checked_sub(1)?)
+            .map(|(_, end)| offset + end)
+    }
+
+    /// Start of the `count`th word before the cursor. With the cursor in
+    /// the middle of a word, that word is the first one.
+    #[must_use]
+    pub fn find_previous_word_beginning(&self, count: usize, big_word: bool) -> Option<usize> {
+        let spans = word_spans(self.text_before_cursor(), big_word);
+        spans
+            .iter()
+            .rev()
+            .nth(count.checked_sub(1)?)
+            .map(|(start, _)| *start)
+    }
+
+    /// The word, or run of whitespace, under the cursor as `(start, end)`.
+    ///
+    /// At the end of a line, the word before the cursor is used. Whitespace
+    /// around a word is included on request, trailing and leading
+    /// separately; it stays within the current line.
+    #[must_use]
+    pub fn find_boundaries_of_current_word(
+        &self,
+        big_word: bool,
+        include_leading_whitespace: bool,
+        include_trailing_whitespace: bool,
+    ) -> (usize, usize) {
+        let line_start = self.start_of_line_position();
+        let before = self.current_line_before_cursor();
+        let after = self.current_line_after_cursor();
+        let Some(class) = after
+            .chars()
+            .next()
+            .or_else(|| before.chars().next_back())
+            .map(|c| char_class(c, big_word))
+        else {
+            return (self.cursor_position, self.cursor_position);
+        };
+
+        let extend_back = |end: usize, class: CharClass| {
+            self.text[line_start..end]
+                .char_indices()
+                .rev()
+                .take_while(|(_, c)| char_class(*c, big_word) == class)
+                .last()
+                .map_or(end, |(i, _)| line_start + i)
+        };
+        let extend_forward = |start: usize, class: CharClass| {
+            let rest = &self.text[start..self.end_of_line_position()];
+            start
+                + rest
+                    .char_indices()
+                    .find(|(_, c)| char_class(*c, big_word) != class)
+                    .map_or(rest.len(), |(i, _)| i)
+        };
+
+        let mut start = extend_back(self.cursor_position, class);
+        let mut end = extend_forward(self.cursor_position, class);
+        if class != CharClass::Whitespace {
+            if include_leading_whitespace {
+                start = extend_back(start, CharClass::Whitespace);
+            }
+            if include_trailing_whitespace {
+                end = extend_forward(end, CharClass::Whitespace);
+            }
+        }
+        (start, end)
+    }
+
+    /// The word that ends right at the cursor, if any.
+    #[must_use]
+    pub fn get_word_before_cursor(&self, big_word: bool) -> &str {
+        match self.char_before_cursor() {
+            Some(c) if !c.is_whitespace() => {
+                let start = self.find_previous_word_beginning(1, big_word).unwrap_or(0);
+                &self.text[start..self.cursor_position]
+            }
+            _ => "",
+        }
+    }
+
+    /// The selected `(from, to)` byte ranges, `to` excluded.
+    ///
+    /// Character selections span from one end to the other, line selections
+    /// cover whole lines including their newline, and block selections give
+    /// one range per line.
+    #[must_use]
+    pub fn selection_ranges(&self) -> Vec<(usize, usize)> {
+        let Some(selection) = self.selection else {
+            return Vec::new();
+        };
+        let from = std::cmp::min(self.cursor_position, selection.original_cursor_position);
+        let to = std::cmp::max(self.cursor_position, selection.original_cursor_position);
+        match selection.selection_type {
+            SelectionType::Characters => vec![(from, to)],
+            SelectionType::Lines => {
+                let from = self.text[..from].rfind('\n').map_or(0, |i| i + 1);
+                let to = self.text[to..]
+                    .find('\n')
+                    .map_or(self.text.len(), |i| to + i + 1);
+                vec![(from, to)]
+            }
+            SelectionType::Block => {
+                let (from_row, from_col) = self.translate_index_to_position(from);
+                let (to_row, to_col) = self.translate_index_to_position(to);
+                let (left, right) = (from_col.min(to_col), from_col.max(to_col));
+                (from_row..=to_row)
+                    .map(|row| {
+                        (
+                            self.translate_row_col_to_index(row, left),
+                            self.translate_row_col_to_index(row, right),
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Remove the selected text, returning what remains and what was cut.
+    ///
+    /// The cursor ends up where the selection started. Without a selection
+    /// nothing is cut.
+    #[must_use]
+    pub fn cut_selection(&self) -> (Document, ClipboardData) {
+        let Some(selection) = self.selection else {
+            return (self.clone(), ClipboardData::default());
+        };
+
+        let mut cut_parts = Vec::new();
+        let mut remaining = String::new();
+        let mut cursor_position = self.cursor_position;
+        let mut last_to = 0;
+        for (i, (from, to)) in self.selection_ranges().into_iter().enumerate() {
+            if i == 0 {
+                cursor_position = from;
+            }
+            remaining.push_str(&self.text[last_to..from]);
+            cut_parts.push(&self.text[from..to]);
+            last_to = to;
+        }
+        remaining.push_str(&self.text[last_to..]);
+
+        let mut cut_text = cut_parts.join("\n");
+        if selection.selection_type == SelectionType::Lines && cut_text.ends_with('\n') {
+            cut_text.pop();
+        }
+        (
+            Document::new(&remaining, cursor_position),
+            ClipboardData::new(&cut_text, selection.selection_type),
+        )
+    }
+}
+
+impl Document {
+    /// Insert `count` copies of `data`, the way it was selected.
+    ///
+    /// Characters are inserted in the text, lines above or below the
+    /// current line and blocks as a column starting at the cursor.
+    #[must_use]
+    pub fn paste_clipboard_data(
+        &self,
+        data: &ClipboardData,
+        paste_mode: PasteMode,
+        count: usize,
+    ) -> Document {
+        let count = count.min(MAX_ARG_COUNT);
+        match data.selection_type {
+            SelectionType::Characters => {
+                let pasted = data.text.repeat(count);
+                let position = if paste_mode == PasteMode::ViAfter {
+                    self.cursor_right_position(1)
+                } else {
+                    self.cursor_position
+                };
+                let mut text = self.text.clone();
+                text.insert_str(position, &pasted);
+                let mut cursor_position = position + pasted.len();
+                if paste_mode != PasteMode::Emacs {
+                    // Vi leaves the cursor on the last pasted character.
+                    cursor_position = text.floor_char_boundary(cursor_position.saturating_sub(1));
+                }
+                Document::new(&text, cursor_position)
+            }
+            SelectionType::Lines => {
+                let row = self.cursor_position_row();
+                let row = if paste_mode == PasteMode::ViBefore {
+                    row
+                } else {
+                    row + 1
+                };
+                let mut lines: Vec<&str> = self.lines();
+                let pasted_lines = vec![data.text.as_str(); count];
+                lines.splice(row..row, pasted_lines);
+                let text = lines.join("\n");
+                let cursor_position = lines[..row].iter().map(|line| line.len() + 1).sum();
+                Document::new(&text, cursor_position)
+            }
+            SelectionType::Block => {
+                let (row, col) = self.translate_index_to_position(self.cursor_position);
+                let col = if paste_mode == PasteMode::ViAfter {
+                    col + 1
+                } else {
+                    col
+                };
+                let mut lines: Vec<String> = self.lines().into_iter().map(str::to_string).collect();
+                for (i, part) in data.text.split('\n').enumerate() {
+                    if row + i >= lines.len() {
+                        lines.push(String::new());
+                    }
+                    let line = &mut lines[row + i];
+                    let width = line.chars().count();
+                    if width < col {
+                        line.push_str(&" ".repeat(col - width));
+                    }
+                    let offset = line.char_indices().nth(col).map_or(line.len(), |(i, _)| i);
+                    line.insert_str(offset, &part.repeat(count));
+                }
+                Document::new(&lines.join("\n"), self.cursor_position)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn document() -> Document {
+        // Cursor on the 'b' of "bar".
+        Document::new("line one\nfoo bar-baz\nlast", 13)
+    }
+
+    #[test]
+    fn test_line_queries() {
+        let document = document();
+        assert_eq!(document.text_before_cursor(), "line one\nfoo ");
+        assert_eq!(document.current_line_before_cursor(), "foo ");
+        assert_eq!(document.current_line_after_cursor(), "bar-baz");
+        assert_eq!(document.current_line(), "foo bar-baz");
+        assert_eq!(document.current_char(), Some('b'));
+        assert_eq!(document.char_before_cursor(), Some(' '));
+        assert_eq!(document.line_count(), 3);
+        assert_eq!(document.lines(), vec!["line one", "foo bar-baz", "last"]);
+        assert_eq!(
+            (
+                document.cursor_position_row(),
+                document.cursor_position_col()
+            ),
+            (1, 4)
+        );
+        assert!(!document.on_first_line());
+        assert!(!document.on_last_line());
+        assert!(!document.is_cursor_at_the_end_of_line());
+        assert!(Document::from_text("abc").is_cursor_at_the_end());
+    }
+
+    #[test]
+    fn test_translate_positions() {
+        let document = document();
+        assert_eq!(document.translate_index_to_position(0), (0, 0));
+        assert_eq!(document.translate_index_to_position(9), (1, 0));
+        assert_eq!(document.translate_row_col_to_index(1, 4), 13);
+        assert_eq!(document.translate_row_col_to_index(2, 100), 25);
+        assert_eq!(document.translate_row_col_to_index(9, 2), 23);
+
+        let unicode = Document::new("héllo\nwörld", 0);
+        assert_eq!(unicode.translate_row_col_to_index(1, 2), 10);
+        assert_eq!(unicode.translate_index_to_position(10), (1, 2));
+    }
+
+    #[test]
+    fn test_cursor_movements_stay_in_bounds() {
+        let document = document();
+        assert_eq!(document.cursor_left_position(2), 11);
+        assert_eq!(document.cursor_left_position(100), 9);
+        assert_eq!(document.cursor_right_position(100), 20);
+        assert_eq!(document.cursor_up_position(1, None), 4);
+        assert_eq!(document.cursor_down_position(1, None), 25);
+        assert_eq!(document.cursor_down_position(5, Some(0)), 21);
+        assert_eq!(document.start_of_line_position(), 9);
+        assert_eq!(document.end_of_line_position(), 20);
+    }
+
+    #[test]
+    fn test_word_motions() {
+        let document = document();
+        assert_eq!(document.find_next_word_beginning(1, false), Some(16));
+        assert_eq!(document.find_next_word_beginning(2, false), Some(17));
+        assert_eq!(document.find_next_word_beginning(1, true), Some(21));
+        assert_eq!(document.find_next_word_ending(1, false, false), Some(16));
+        assert_eq!(document.find_next_word_ending(1, true, false), Some(20));
+        assert_eq!(document.find_previous_word_beginning(1, false), Some(9));
+        assert_eq!(document.find_previous_word_beginning(2, false), Some(5));
+        assert_eq!(document.find_previous_word_beginning(9, false), None);
+        assert_eq!(
+            Document::new("abc", 1).find_next_word_beginning(1, false),
+            None
+        );
+        assert_eq!(document.find_next_word_beginning(0, false), None);
+    }
+
+    #[test]
+    fn test_boundaries_of_current_word() {
+        let document = Document::new("foo bar-baz  qux", 5);
+        assert_eq!(
+            document.find_boundaries_of_current_word(false, false, false),
+            (4, 7)
+        );
+        assert_eq!(
+            document.find_boundaries_of_current_word(true, false, false),
+            (4, 11)
+        );
+        assert_eq!(
+            document.find_boundaries_of_current_word(true, false, true),
+            (4, 13)
+        );
+        assert_eq!(
+            document.find_boundaries_of_current_word(false, true, false),
+            (3, 7)
+        );
+        // On whitespace, the whitespace itself.
+        let document = Document::new("foo bar-baz  qux", 12);
+        assert_eq!(
+            document.find_boundaries_of_current_word(false, true, true),
+            (11, 13)
+        );
+        // At the end of the text, the word before the cursor.
+        let document = Document::from_text("foo bar");
+        assert_eq!(
+            document.find_boundaries_of_current_word(false, false, false),
+            (4, 7)
+        );
+        assert_eq!(document.get_word_before_cursor(false), "bar");
+    }
+
+    #[test]
+    fn test_cut_selection() {
+        let text = "line one\nfoo bar-baz\nlast";
+        let characters = Document::new(text, 16)
+            .with_selection(Some(SelectionState::new(13, SelectionType::Characters)));
+        let (remaining, data) = characters.cut_selection();
+        assert_eq!(remaining.text(), "line one\nfoo -baz\nlast");
+        assert_eq!(remaining.cursor_position(), 13);
+        assert_eq!(data, ClipboardData::new("bar", SelectionType::Characters));
+
+        let lines = Document::new(text, 2)
+            .with_selection(Some(SelectionState::new(13, SelectionType::Lines)));
+        let (remaining, data) = lines.cut_selection();
+        assert_eq!(remaining.text(), "last");
+        assert_eq!(remaining.cursor_position(), 0);
+        assert_eq!(data.text, "line one\nfoo bar-baz");
+
+        let block = Document::new(text, 12)
+            .with_selection(Some(SelectionState::new(1, SelectionType::Block)));
+        let (remaining, data) = block.cut_selection();
+        assert_eq!(remaining.text(), "le one\nf bar-baz\nlast");
+        assert_eq!(data.text, "in\noo");
+    }
+
+    #[test]
+    fn test_paste_clipboard_data() {
+        let document = Document::new("abc\ndef", 1);
+        let characters = ClipboardData::new("XY", SelectionType::Characters);
+        let pasted = document.paste_clipboard_data(&characters, PasteMode::Emacs, 2);
+        assert_eq!(
+            (pasted.text(), pasted.cursor_position()),
+            ("aXYXYbc\ndef", 5)
+        );
+        let pasted = document.paste_clipboard_data(&characters, PasteMode::ViAfter, 1);
+        assert_eq!((pasted.text(), pasted.cursor_position()), ("abXYc\ndef", 3));
+
+        let lines = ClipboardData::new("new", SelectionType::Lines);
+        let pasted = document.paste_clipboard_data(&lines, PasteMode::ViAfter, 1);
+        assert_eq!(
+            (pasted.text(), pasted.cursor_position()),
+            ("abc\nnew\ndef", 4)
+        );
+        let pasted = document.paste_clipboard_data(&lines, PasteMode::ViBefore, 1);
+        assert_eq!(
+            (pasted.text(), pasted.cursor_position()),
+            ("new\nabc\ndef", 0)
+        );
+
+        let block = ClipboardData::new("1\n2\n3", SelectionType::Block);
+        let pasted = document.paste_clipboard_data(&block, PasteMode::ViBefore, 1);
+        assert_eq!(pasted.text(), "a1bc\nd2ef\n 3");
+    }
+
+    #[test]
+    fn test_paste_count_is_clamped() {
+        let data = ClipboardData::new("x", SelectionType::Characters);
+        let pasted = Document::default().paste_clipboard_data(&data, PasteMode::Emacs, usize::MAX);
+        assert_eq!(pasted.text().len(), MAX_ARG_COUNT);
+
+        let data = ClipboardData::new("x", SelectionType::Lines);
+        let pasted =
+            Document::default().paste_clipboard_data(&data, PasteMode::ViAfter, usize::MAX);
+        assert_eq!(pasted.lines().len(), MAX_ARG_COUNT + 1);
+
+        let data = ClipboardData::new("x\ny", SelectionType::Block);
+        let pasted =
+            Document::default().paste_clipboard_data(&data, PasteMode::ViBefore, usize::MAX);
+        assert_eq!(
+            pasted.lines(),
+            vec!["x".repeat(MAX_ARG_COUNT), "y".repeat(MAX_ARG_COUNT)]
+        );
+    }
+}
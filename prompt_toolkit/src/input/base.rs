@@ -1,7 +1,9 @@
+use std::time::Duration;
+
 use crate::keys::Keys;
 use nix::sys::termios::Termios;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct KeyPress {
     key: Keys,
     text: String,
@@ -46,7 +48,7 @@ impl<'a> RawTermGuard<'a> {
     }
 }
 
-impl<'a> Drop for RawTermGuard<'a> {
+impl Drop for RawTermGuard<'_> {
     fn drop(&mut self) {
         self.input.to_cooked_mode(self.original_mode.clone());
     }
@@ -55,11 +57,13 @@ impl<'a> Drop for RawTermGuard<'a> {
 pub trait Input {
     fn fileno(&self) -> i32;
     fn typeahead_hash(&self) -> String;
+    /// Block until keys can be read or `timeout` elapses. Returns `false` on timeout.
+    fn wait_for_keys(&mut self, timeout: Duration) -> bool;
     fn read_keys(&mut self) -> Vec<KeyPress>;
     fn flush_keys(&mut self) -> Vec<KeyPress>;
     fn closed(&self) -> bool;
     fn to_raw_mode(&mut self) -> Option<Termios>;
-    fn raw_mode(&mut self) -> RawTermGuard;
+    fn raw_mode(&mut self) -> RawTermGuard<'_>;
     fn to_cooked_mode(&mut self, original_mode: Option<Termios>);
 }
 
@@ -74,6 +78,10 @@ impl Input for DummyInput {
         String::default()
     }
 
+    fn wait_for_keys(&mut self, _timeout: Duration) -> bool {
+        false
+    }
+
     fn read_keys(&mut self) -> Vec<KeyPress> {
         vec![]
     }
@@ -86,7 +94,7 @@ impl Input for DummyInput {
         true
     }
 
-    fn raw_mode(&mut self) -> RawTermGuard {
+    fn raw_mode(&mut self) -> RawTermGuard<'_> {
         RawTermGuard::new(self)
     }
 
@@ -106,6 +114,10 @@ impl Input for RawTermGuard<'_> {
         self.input.typeahead_hash()
     }
 
+    fn wait_for_keys(&mut self, timeout: Duration) -> bool {
+        self.input.wait_for_keys(timeout)
+    }
+
     fn read_keys(&mut self) -> Vec<KeyPress> {
         self.input.read_keys()
     }
@@ -122,7 +134,7 @@ impl Input for RawTermGuard<'_> {
         None
     }
 
-    fn raw_mode(&mut self) -> RawTermGuard {
+    fn raw_mode(&mut self) -> RawTermGuard<'_> {
         panic!("input already in raw mode")
     }
 
@@ -137,6 +149,7 @@ pub mod tests {
         let mut di = DummyInput;
         assert_eq!(di.fileno(), -1);
         assert_eq!(di.typeahead_hash(), String::default());
+        assert!(!di.wait_for_keys(Duration::ZERO));
         assert_eq!(di.read_keys(), vec![]);
         assert_eq!(di.flush_keys(), vec![]);
         assert!(di.closed());
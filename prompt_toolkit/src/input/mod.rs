@@ -4,5 +4,5 @@ pub(crate) mod posix_utils;
 pub(crate) mod vt100;
 pub(crate) mod vt100_parser;
 
-pub use base::{Input, KeyPress, RawTermGuard};
+pub use base::{DummyInput, Input, KeyPress, RawTermGuard};
 pub use vt100::VT100;
@@ -1,9 +1,11 @@
 use std::{
     io,
     os::fd::{BorrowedFd, RawFd},
+    time::Duration,
 };
 
 use nix::libc;
+use nix::sys::time::TimeValLike;
 use tracing::info;
 
 pub struct PosixStdinReader {
@@ -20,15 +22,23 @@ impl PosixStdinReader {
         self.closed
     }
 
-    pub fn read(&mut self, count: usize) -> Result<String, io::Error> {
+    /// Block until the file descriptor is readable or `timeout` elapses.
+    ///
+    /// Returns `true` when a subsequent `read` will not block.
+    pub fn wait(&mut self, timeout: Duration) -> Result<bool, io::Error> {
         if self.closed {
-            return Ok(String::default());
+            return Ok(false);
         }
 
         let mut read_fds = nix::sys::select::FdSet::new();
         read_fds.insert(unsafe { BorrowedFd::borrow_raw(self.fd) });
 
-        let mut timeout = nix::sys::time::TimeVal::new(0, 0);
+        let mut timeout = nix::sys::time::TimeVal::milliseconds(
+            timeout
+                .as_millis()
+                .try_into()
+                .unwrap_or(i64::from(i32::MAX)),
+        );
 
         let result = nix::sys::select::select(
             self.fd + 1,
@@ -41,18 +51,25 @@ impl PosixStdinReader {
         match result {
             Ok(n) if n < 0 => {
                 self.closed = true;
-                return Err(io::Error::last_os_error());
-            }
-            Ok(0) => {
-                info!("no FDs were ready!");
-                return Ok(String::default());
+                Err(io::Error::last_os_error())
             }
-            Ok(_) => {}
+            Ok(n) => Ok(n > 0),
             Err(e) => {
                 self.closed = true;
-                return Err(io::Error::from(e));
+                Err(io::Error::from(e))
             }
         }
+    }
+
+    pub fn read(&mut self, count: usize) -> Result<String, io::Error> {
+        if self.closed {
+            return Ok(String::default());
+        }
+
+        if !self.wait(Duration::ZERO)? {
+            info!("no FDs were ready!");
+            return Ok(String::default());
+        }
 
         let mut buf = vec![0u8; count];
 
@@ -60,12 +77,20 @@ impl PosixStdinReader {
             // todo async
             let bytes_read =
                 libc::read(self.fd, buf.as_mut_ptr().cast::<libc::c_void>(), buf.len());
-            if bytes_read < 0 {
-                Err(io::Error::last_os_error())
-            } else {
-                buf.truncate(usize::try_from(bytes_read).expect("already checked if isize < 0"));
-                // TODO: Make this not lossy
-                Ok(String::from_utf8_lossy(&buf).into_owned())
+            match bytes_read.cmp(&0) {
+                std::cmp::Ordering::Less => Err(io::Error::last_os_error()),
+                std::cmp::Ordering::Equal => {
+                    // The descriptor was readable but had nothing left: end of file.
+                    self.closed = true;
+                    Ok(String::default())
+                }
+                std::cmp::Ordering::Greater => {
+                    buf.truncate(
+                        usize::try_from(bytes_read).expect("already checked if isize < 0"),
+                    );
+                    // TODO: Make this not lossy
+                    Ok(String::from_utf8_lossy(&buf).into_owned())
+                }
             }
         }
     }
@@ -119,5 +144,16 @@ mod tests {
 
         let result = reader.read(1024).expect("read error");
         assert_eq!(result, test_data);
+        assert!(!reader.closed());
+
+        assert_eq!(reader.read(1024).expect("read error"), String::default());
+        assert!(reader.closed());
+    }
+
+    #[test]
+    fn test_wait_on_readable_file() {
+        let file = tempfile().expect("temp file");
+        let mut reader = PosixStdinReader::new(file.as_raw_fd());
+        assert!(reader.wait(Duration::from_millis(10)).expect("wait error"));
     }
 }
@@ -1,7 +1,7 @@
 #![expect(dead_code)]
 
 use nix::sys::termios::Termios;
-use std::os::fd::RawFd;
+use std::{os::fd::RawFd, time::Duration};
 use tracing::warn;
 
 use crate::input::{
@@ -39,6 +39,16 @@ impl Input for VT100 {
         format!("fd-{}", self.fileno())
     }
 
+    fn wait_for_keys(&mut self, timeout: Duration) -> bool {
+        match self.reader.wait(timeout) {
+            Ok(ready) => ready,
+            Err(e) => {
+                warn!("Got an error when waiting for input: {e}");
+                false
+            }
+        }
+    }
+
     fn read_keys(&mut self) -> Vec<KeyPress> {
         match self.reader.read(1024) {
             Ok(data) => self.parser.feed(&data),
@@ -57,7 +67,7 @@ impl Input for VT100 {
         self.reader.closed()
     }
 
-    fn raw_mode(&mut self) -> RawTermGuard {
+    fn raw_mode(&mut self) -> RawTermGuard<'_> {
         RawTermGuard::new(self)
     }
     fn to_raw_mode(&mut self) -> Option<Termios> {
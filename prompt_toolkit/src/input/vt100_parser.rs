@@ -14,6 +14,16 @@ static MOUSE_EVENT_PREFIX_REGEX: LazyLock<Regex> =
 static MOUSE_EVENT_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^\x1b\[(<?[\d;]+[mM]|M...)\z").expect("valid regex"));
 
+/// Extract `(row, column)` from a Cursor Position Response such as `\x1b[35;1R`.
+/// Both values are 1-based, as reported by the terminal.
+pub fn parse_cursor_position_response(data: &str) -> Option<(usize, usize)> {
+    if !CURSOR_POSITION_RESPONSE_REGEX.is_match(data) {
+        return None;
+    }
+    let (row, column) = data[2..data.len() - 1].split_once(';')?;
+    Some((row.parse().ok()?, column.parse().ok()?))
+}
+
 #[derive(Debug, Copy, Clone)]
 enum StateMachineInput {
     Character(char),
@@ -56,7 +66,7 @@ impl Parser {
                 results.extend(self.feed(&remaining));
             }
         } else {
-            for (i, c) in data.chars().enumerate() {
+            for (i, c) in data.char_indices() {
                 if self.in_bracketed_paste {
                     results.extend(self.feed(&data[i..]));
                     break;
@@ -105,11 +115,15 @@ impl Parser {
                     let mut found = false;
                     self.retry = true;
 
-                    for i in self.prefix.len()..0 {
+                    // Try the longest prefix that is a complete sequence on its own.
+                    let boundaries: Vec<usize> =
+                        self.prefix.char_indices().skip(1).map(|(i, _)| i).collect();
+                    for &i in boundaries.iter().rev() {
                         if let Some(matches) = get_matches(&self.prefix[..i]) {
                             self.handler(Some(matches), self.prefix[..i].to_string(), &mut result);
                             self.prefix = self.prefix[i..].to_string();
                             found = true;
+                            break;
                         }
                     }
 
@@ -218,6 +232,21 @@ mod test {
         assert!(cache.get("\x1b[M"));
     }
 
+    #[test]
+    fn test_parse_cursor_position_response() {
+        assert_eq!(parse_cursor_position_response("\x1b[35;1R"), Some((35, 1)));
+        assert_eq!(parse_cursor_position_response("\x1b[1;80R"), Some((1, 80)));
+        assert_eq!(parse_cursor_position_response("\x1b[35;1"), None);
+        assert_eq!(parse_cursor_position_response("35;1R"), None);
+
+        let mut parser = Parser::new();
+        let results = parser.feed("\x1b[12;4R");
+        assert_eq!(
+            results,
+            vec![KeyPress::new(Keys::CPRResponse, "\x1b[12;4R".to_string())]
+        );
+    }
+
     #[test]
     fn test_parser() {
         let mut parser = Parser::new();
@@ -255,4 +284,17 @@ mod test {
             KeyPress::new(Keys::BracketedPaste, "clipboard".to_string())
         );
     }
+
+    #[test]
+    fn test_parser_splits_meta_key() {
+        let mut parser = Parser::new();
+        let results = parser.feed("\x1bb");
+        assert_eq!(
+            results,
+            vec![
+                KeyPress::new(Keys::Escape, "\x1b".to_string()),
+                KeyPress::new(Keys::Character('b'), "b".to_string()),
+            ]
+        );
+    }
 }
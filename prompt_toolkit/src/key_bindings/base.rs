@@ -1,90 +1,504 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, rc::Rc};
 
 use tracing::info;
 
 use crate::application::Application;
+use crate::buffer::Buffer;
+use crate::filters::Filter;
 use crate::keys::Keys;
 use crate::KeyPress;
 
-#[derive(Debug)]
-pub struct KeyPressEvent {
-    pub key_press: KeyPress,
+/// Repeat counts stay below this, as in Python prompt-toolkit, so that
+/// commands repeating themselves `arg` times stay responsive.
+pub const MAX_ARG_COUNT: usize = 1_000_000;
+
+/// `count` with `digit` typed after it. Digits that would make the count
+/// reach [`MAX_ARG_COUNT`] are ignored.
+pub(crate) fn append_digit(count: Option<usize>, digit: usize) -> usize {
+    let count = count.unwrap_or(0);
+    let appended = count * 10 + digit;
+    if appended < MAX_ARG_COUNT {
+        appended
+    } else {
+        count
+    }
+}
+
+/// What a binding handler gets: the keys that triggered it and the
+/// application they were typed in.
+pub struct KeyPressEvent<'a, T> {
+    key_sequence: Vec<KeyPress>,
+    /// The last of `key_sequence`.
+    key_press: KeyPress,
+    arg: Option<usize>,
+    next_arg: Option<usize>,
+    app: &'a mut Application<T>,
 }
 
-impl KeyPressEvent {
+impl<'a, T> KeyPressEvent<'a, T> {
+    /// An event for `key_sequence`, which ends with the key just pressed.
+    ///
+    /// # Panics
+    ///
+    /// When `key_sequence` is empty.
+    #[must_use]
+    pub fn new(key_sequence: Vec<KeyPress>, app: &'a mut Application<T>) -> Self {
+        let key_press = key_sequence
+            .last()
+            .cloned()
+            .expect("a key press event needs a key");
+        KeyPressEvent {
+            key_sequence,
+            key_press,
+            arg: None,
+            next_arg: None,
+            app,
+        }
+    }
+
+    /// Set the repeat count typed before the keys.
+    #[must_use]
+    pub fn with_arg(mut self, arg: Option<usize>) -> Self {
+        self.arg = arg;
+        self
+    }
+
+    /// The key that completed the sequence.
+    #[must_use]
+    pub fn key_press(&self) -> &KeyPress {
+        &self.key_press
+    }
+
+    /// All keys of the bound sequence, e.g. Escape and `f` for Meta-F.
+    #[must_use]
+    pub fn key_sequence(&self) -> &[KeyPress] {
+        &self.key_sequence
+    }
+
+    /// The text of the key that completed the sequence.
+    #[must_use]
+    pub fn data(&self) -> &str {
+        self.key_press().text()
+    }
+
+    /// How many times to repeat the command, 1 unless a count was typed.
     #[must_use]
-    pub fn new(key_press: KeyPress) -> Self {
-        KeyPressEvent { key_press }
+    pub fn arg(&self) -> usize {
+        self.arg.unwrap_or(1)
+    }
+
+    /// Whether a repeat count was typed.
+    #[must_use]
+    pub fn arg_present(&self) -> bool {
+        self.arg.is_some()
+    }
+
+    /// Add a digit to the repeat count, which is then handed to the next
+    /// binding instead of this one. Other characters are ignored, and so
+    /// are digits that would make the count reach [`MAX_ARG_COUNT`].
+    pub fn append_to_arg_count(&mut self, digit: char) {
+        if let Some(value) = digit.to_digit(10).and_then(|d| usize::try_from(d).ok()) {
+            self.next_arg = Some(append_digit(self.next_arg.or(self.arg), value));
+        }
+    }
+
+    #[must_use]
+    pub fn app(&self) -> &Application<T> {
+        self.app
+    }
+
+    pub fn app_mut(&mut self) -> &mut Application<T> {
+        self.app
+    }
+
+    /// The buffer of the focused window, if it has one.
+    #[must_use]
+    pub fn current_buffer(&self) -> Option<Rc<RefCell<Buffer>>> {
+        self.app.current_buffer()
     }
 }
 
-pub trait Binding {
-    fn handler(&mut self, key_press_event: &KeyPressEvent);
+pub trait Binding<T> {
+    fn handler(&mut self, event: &mut KeyPressEvent<'_, T>);
 }
 
-pub struct KeyBindings {
-    bindings: HashMap<Keys, Box<dyn Binding>>,
-    all_keys_bindings: Vec<Box<dyn Binding>>,
+impl<T, F> Binding<T> for F
+where
+    F: FnMut(&mut KeyPressEvent<'_, T>),
+{
+    fn handler(&mut self, event: &mut KeyPressEvent<'_, T>) {
+        self(event);
+    }
 }
 
-impl Default for KeyBindings {
+/// What [`KeyProcessor`] dispatches to: [`KeyBindings`], or one of the
+/// wrappers combining them.
+pub trait KeyBindingsBase<T> {
+    /// How many [`Keys::Any`] the active binding for exactly `keys` has.
+    /// Fewer wins when several sets match.
+    fn any_count(&self, keys: &[Keys]) -> Option<usize>;
+
+    /// Whether an active sequence needs more keys after `keys`.
+    fn is_prefix_of_longer_sequence(&self, keys: &[Keys]) -> bool;
+
+    /// Run the active binding for exactly `keys`. Returns whether there
+    /// was one.
+    fn handle(&mut self, keys: &[Keys], event: &mut KeyPressEvent<'_, T>) -> bool;
+
+    /// Run the bindings for keys that no sequence is bound to.
+    fn handle_all_keys(&mut self, event: &mut KeyPressEvent<'_, T>);
+}
+
+/// Bindings from key sequences to handlers.
+///
+/// A sequence is one or more keys, e.g. `[Keys::ControlX, Keys::ControlE]`.
+/// [`Keys::Any`] in a sequence matches any key. Bindings added with a
+/// [`Filter`] only count while it evaluates to true, which is checked on
+/// every key press. When several active sequences match the same keys, the
+/// one with the fewest [`Keys::Any`] wins, and among those the one added
+/// last.
+pub struct KeyBindings<T> {
+    bindings: Vec<BoundSequence<T>>,
+    all_keys_bindings: Vec<Box<dyn Binding<T>>>,
+}
+
+struct BoundSequence<T> {
+    keys: Vec<Keys>,
+    filter: Filter,
+    binding: Box<dyn Binding<T>>,
+}
+
+impl<T> Default for KeyBindings<T> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl KeyBindings {
+impl<T> KeyBindings<T> {
     #[must_use]
-    pub fn new() -> KeyBindings {
+    pub fn new() -> KeyBindings<T> {
         KeyBindings {
-            bindings: HashMap::new(),
+            bindings: Vec::new(),
             all_keys_bindings: Vec::new(),
         }
     }
 
+    /// The binding that handles exactly `keys`.
     #[must_use]
-    pub fn get(&self, keys: &Keys) -> Option<&dyn Binding> {
-        self.bindings.get(keys).map(|v| &**v)
+    pub fn get(&self, keys: &[Keys]) -> Option<&dyn Binding<T>> {
+        self.find(keys).map(|i| &*self.bindings[i].binding)
+    }
+
+    pub fn get_mut(&mut self, keys: &[Keys]) -> Option<&mut Box<dyn Binding<T>>> {
+        self.find(keys).map(|i| &mut self.bindings[i].binding)
+    }
+
+    /// Bind a sequence of one or more keys. An empty sequence is ignored.
+    pub fn add(&mut self, keys: &[Keys], binding: Box<dyn Binding<T>>) {
+        self.add_with_filter(keys, Filter::Always, binding);
     }
 
-    pub fn get_mut(&mut self, keys: &Keys) -> Option<&mut Box<dyn Binding>> {
-        self.bindings.get_mut(keys)
+    /// Bind `keys` to a closure.
+    pub fn add_fn(
+        &mut self,
+        keys: &[Keys],
+        handler: impl FnMut(&mut KeyPressEvent<'_, T>) + 'static,
+    ) {
+        self.add(keys, Box::new(handler));
     }
 
-    pub fn add(&mut self, keys: Keys, binding: Box<dyn Binding>) {
-        self.bindings.insert(keys, binding);
+    /// Bind a sequence that is only active while `filter` evaluates to true.
+    /// While inactive, the keys go to the next matching binding.
+    pub fn add_with_filter(&mut self, keys: &[Keys], filter: Filter, binding: Box<dyn Binding<T>>) {
+        if keys.is_empty() {
+            return;
+        }
+        self.bindings.push(BoundSequence {
+            keys: keys.to_vec(),
+            filter,
+            binding,
+        });
     }
 
-    pub fn add_for_all_keys(&mut self, binding: Box<dyn Binding>) {
+    /// Handle keys that no sequence is bound to.
+    pub fn add_for_all_keys(&mut self, binding: Box<dyn Binding<T>>) {
         self.all_keys_bindings.push(binding);
     }
 
     #[must_use]
-    pub fn get_all_keys_bindings(&self) -> &Vec<Box<dyn Binding>> {
+    pub fn get_all_keys_bindings(&self) -> &Vec<Box<dyn Binding<T>>> {
         &self.all_keys_bindings
     }
 
-    pub fn get_all_keys_bindings_mut(&mut self) -> &mut Vec<Box<dyn Binding>> {
+    pub fn get_all_keys_bindings_mut(&mut self) -> &mut Vec<Box<dyn Binding<T>>> {
         &mut self.all_keys_bindings
     }
+
+    /// Index of the active binding that wins for exactly `keys`.
+    fn find(&self, keys: &[Keys]) -> Option<usize> {
+        self.bindings
+            .iter()
+            .enumerate()
+            .filter(|(_, bound)| {
+                bound.keys.len() == keys.len()
+                    && sequence_starts_with(&bound.keys, keys)
+                    && bound.filter.eval()
+            })
+            .min_by_key(|(i, bound)| {
+                let any_count = bound.keys.iter().filter(|key| **key == Keys::Any).count();
+                (any_count, std::cmp::Reverse(*i))
+            })
+            .map(|(i, _)| i)
+    }
 }
 
-pub struct KeyProcessor {
-    bindings: KeyBindings,
+impl<T> KeyBindingsBase<T> for KeyBindings<T> {
+    fn any_count(&self, keys: &[Keys]) -> Option<usize> {
+        self.find(keys).map(|i| {
+            self.bindings[i]
+                .keys
+                .iter()
+                .filter(|key| **key == Keys::Any)
+                .count()
+        })
+    }
+
+    fn is_prefix_of_longer_sequence(&self, keys: &[Keys]) -> bool {
+        self.bindings.iter().any(|bound| {
+            bound.keys.len() > keys.len()
+                && sequence_starts_with(&bound.keys, keys)
+                && bound.filter.eval()
+        })
+    }
+
+    fn handle(&mut self, keys: &[Keys], event: &mut KeyPressEvent<'_, T>) -> bool {
+        let Some(i) = self.find(keys) else {
+            return false;
+        };
+        self.bindings[i].binding.handler(event);
+        true
+    }
+
+    fn handle_all_keys(&mut self, event: &mut KeyPressEvent<'_, T>) {
+        for binding in &mut self.all_keys_bindings {
+            binding.handler(event);
+        }
+    }
+}
+
+/// Several sets of bindings used as one. When sets bind the same keys,
+/// the later set wins.
+pub struct MergedKeyBindings<T> {
+    key_bindings: Vec<Box<dyn KeyBindingsBase<T>>>,
+}
+
+impl<T> MergedKeyBindings<T> {
+    #[must_use]
+    pub fn new(key_bindings: Vec<Box<dyn KeyBindingsBase<T>>>) -> Self {
+        Self { key_bindings }
+    }
+
+    /// Index of the set whose binding wins for exactly `keys`.
+    fn find(&self, keys: &[Keys]) -> Option<usize> {
+        self.key_bindings
+            .iter()
+            .enumerate()
+            .filter_map(|(i, key_bindings)| Some((i, key_bindings.any_count(keys)?)))
+            .min_by_key(|&(i, any_count)| (any_count, std::cmp::Reverse(i)))
+            .map(|(i, _)| i)
+    }
+}
+
+impl<T> KeyBindingsBase<T> for MergedKeyBindings<T> {
+    fn any_count(&self, keys: &[Keys]) -> Option<usize> {
+        self.key_bindings
+            .iter()
+            .filter_map(|key_bindings| key_bindings.any_count(keys))
+            .min()
+    }
+
+    fn is_prefix_of_longer_sequence(&self, keys: &[Keys]) -> bool {
+        self.key_bindings
+            .iter()
+            .any(|key_bindings| key_bindings.is_prefix_of_longer_sequence(keys))
+    }
+
+    fn handle(&mut self, keys: &[Keys], event: &mut KeyPressEvent<'_, T>) -> bool {
+        match self.find(keys) {
+            Some(i) => self.key_bindings[i].handle(keys, event),
+            None => false,
+        }
+    }
+
+    fn handle_all_keys(&mut self, event: &mut KeyPressEvent<'_, T>) {
+        for key_bindings in &mut self.key_bindings {
+            key_bindings.handle_all_keys(event);
+        }
+    }
+}
+
+/// Bindings that are only active while `filter` evaluates to true, e.g.
+/// a plugin's bindings that can be switched off.
+pub struct ConditionalKeyBindings<T> {
+    key_bindings: Box<dyn KeyBindingsBase<T>>,
+    filter: Filter,
 }
 
-impl KeyProcessor {
+impl<T> ConditionalKeyBindings<T> {
     #[must_use]
-    pub fn new(bindings: KeyBindings) -> Box<KeyProcessor> {
-        Box::new(KeyProcessor { bindings })
+    pub fn new(key_bindings: Box<dyn KeyBindingsBase<T>>, filter: Filter) -> Self {
+        Self {
+            key_bindings,
+            filter,
+        }
     }
+}
 
-    pub fn process_key(&mut self, key_press: &KeyPressEvent, _app: &mut Application) {
-        if let Some(binding) = self.bindings.get_mut(&key_press.key_press.key()) {
-            binding.handler(key_press);
+impl<T> KeyBindingsBase<T> for ConditionalKeyBindings<T> {
+    fn any_count(&self, keys: &[Keys]) -> Option<usize> {
+        if self.filter.eval() {
+            self.key_bindings.any_count(keys)
         } else {
-            for binding in self.bindings.get_all_keys_bindings_mut() {
-                binding.handler(key_press);
+            None
+        }
+    }
+
+    fn is_prefix_of_longer_sequence(&self, keys: &[Keys]) -> bool {
+        self.filter.eval() && self.key_bindings.is_prefix_of_longer_sequence(keys)
+    }
+
+    fn handle(&mut self, keys: &[Keys], event: &mut KeyPressEvent<'_, T>) -> bool {
+        self.filter.eval() && self.key_bindings.handle(keys, event)
+    }
+
+    fn handle_all_keys(&mut self, event: &mut KeyPressEvent<'_, T>) {
+        if self.filter.eval() {
+            self.key_bindings.handle_all_keys(event);
+        }
+    }
+}
+
+/// Shared bindings, as returned by the closure of [`DynamicKeyBindings`].
+pub type SharedKeyBindings<T> = Rc<RefCell<dyn KeyBindingsBase<T>>>;
+
+/// Bindings looked up by a closure on every key press, e.g. to switch
+/// between key maps at runtime. When the closure returns `None`, nothing
+/// is bound.
+pub struct DynamicKeyBindings<T> {
+    get_key_bindings: Box<dyn Fn() -> Option<SharedKeyBindings<T>>>,
+}
+
+impl<T> DynamicKeyBindings<T> {
+    pub fn new<F>(get_key_bindings: F) -> Self
+    where
+        F: Fn() -> Option<SharedKeyBindings<T>> + 'static,
+    {
+        Self {
+            get_key_bindings: Box::new(get_key_bindings),
+        }
+    }
+}
+
+impl<T> KeyBindingsBase<T> for DynamicKeyBindings<T> {
+    fn any_count(&self, keys: &[Keys]) -> Option<usize> {
+        (self.get_key_bindings)()?.borrow().any_count(keys)
+    }
+
+    fn is_prefix_of_longer_sequence(&self, keys: &[Keys]) -> bool {
+        (self.get_key_bindings)()
+            .is_some_and(|key_bindings| key_bindings.borrow().is_prefix_of_longer_sequence(keys))
+    }
+
+    fn handle(&mut self, keys: &[Keys], event: &mut KeyPressEvent<'_, T>) -> bool {
+        (self.get_key_bindings)()
+            .is_some_and(|key_bindings| key_bindings.borrow_mut().handle(keys, event))
+    }
+
+    fn handle_all_keys(&mut self, event: &mut KeyPressEvent<'_, T>) {
+        if let Some(key_bindings) = (self.get_key_bindings)() {
+            key_bindings.borrow_mut().handle_all_keys(event);
+        }
+    }
+}
+
+/// Whether `keys` are the first keys of `sequence`.
+fn sequence_starts_with(sequence: &[Keys], keys: &[Keys]) -> bool {
+    sequence.len() >= keys.len()
+        && sequence
+            .iter()
+            .zip(keys)
+            .all(|(bound, key)| *bound == Keys::Any || bound == key)
+}
+
+/// Dispatches key presses to their bindings.
+///
+/// Keys that start a longer bound sequence are held back until the
+/// sequence is complete, or until it is clear that it won't be. Then the
+/// longest bound sequence at the start of the held keys is handled and the
+/// rest is processed again, much like [`crate::input::vt100_parser`]
+/// handles escape sequences. Keys no sequence is bound to go to the
+/// all-keys bindings.
+pub struct KeyProcessor<T> {
+    bindings: Box<dyn KeyBindingsBase<T>>,
+    key_buffer: Vec<KeyPress>,
+    /// Repeat count for the next binding, see [`KeyPressEvent::arg`].
+    arg: Option<usize>,
+}
+
+impl<T> KeyProcessor<T> {
+    #[must_use]
+    pub fn new(bindings: Box<dyn KeyBindingsBase<T>>) -> Box<KeyProcessor<T>> {
+        Box::new(KeyProcessor {
+            bindings,
+            key_buffer: Vec::new(),
+            arg: None,
+        })
+    }
+
+    /// Give the bindings back, e.g. to the application once it stops running.
+    /// Keys waiting for the rest of a sequence are dropped.
+    #[must_use]
+    pub fn into_bindings(self) -> Box<dyn KeyBindingsBase<T>> {
+        self.bindings
+    }
+
+    /// Keys held back as the start of a longer sequence.
+    #[must_use]
+    pub fn pending_keys(&self) -> &[KeyPress] {
+        &self.key_buffer
+    }
+
+    pub fn process_key(&mut self, key_press: KeyPress, app: &mut Application<T>) {
+        self.key_buffer.push(key_press);
+        self.process(app, false);
+    }
+
+    /// Stop waiting for the rest of a sequence and handle the held back
+    /// keys as they are. To be called when no key arrived in time.
+    pub fn flush(&mut self, app: &mut Application<T>) {
+        self.process(app, true);
+    }
+
+    fn process(&mut self, app: &mut Application<T>, flush: bool) {
+        while !self.key_buffer.is_empty() {
+            let keys: Vec<Keys> = self.key_buffer.iter().map(KeyPress::key).collect();
+            if !flush && self.bindings.is_prefix_of_longer_sequence(&keys) {
+                return;
+            }
+
+            let longest_match = (1..=keys.len())
+                .rev()
+                .find(|&len| self.bindings.any_count(&keys[..len]).is_some());
+            if let Some(len) = longest_match {
+                let key_sequence = self.key_buffer.drain(..len).collect();
+                let mut event = KeyPressEvent::new(key_sequence, app).with_arg(self.arg.take());
+                self.bindings.handle(&keys[..len], &mut event);
+                self.arg = event.next_arg;
+            } else {
+                let key_press = self.key_buffer.remove(0);
+                let mut event = KeyPressEvent::new(vec![key_press], app).with_arg(self.arg.take());
+                self.bindings.handle_all_keys(&mut event);
+                self.arg = event.next_arg;
             }
         }
     }
@@ -92,33 +506,36 @@ impl KeyProcessor {
 
 pub struct EchoBinding;
 
-impl Binding for EchoBinding {
-    fn handler(&mut self, key_press_event: &KeyPressEvent) {
-        info!("key press: {:?}", key_press_event.key_press);
+impl<T> Binding<T> for EchoBinding {
+    fn handler(&mut self, event: &mut KeyPressEvent<'_, T>) {
+        info!("key press: {:?}", event.key_press());
     }
 }
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+
     use super::*;
+    use crate::layout::Layout;
 
     #[test]
     fn test_key_bindings_new() {
-        let bindings = KeyBindings::new();
+        let bindings = KeyBindings::<()>::new();
         assert!(bindings.bindings.is_empty());
         assert!(bindings.all_keys_bindings.is_empty());
     }
 
     #[test]
     fn test_key_bindings_add() {
-        let mut bindings = KeyBindings::new();
+        let mut bindings = KeyBindings::<()>::new();
         let echo = Box::new(EchoBinding);
-        bindings.add(Keys::Character('a'), echo);
+        bindings.add(&[Keys::Character('a')], echo);
         assert_eq!(bindings.bindings.len(), 1);
     }
 
     #[test]
     fn test_key_bindings_add_for_all_keys() {
-        let mut bindings = KeyBindings::new();
+        let mut bindings = KeyBindings::<()>::new();
         let echo = Box::new(EchoBinding);
         bindings.add_for_all_keys(echo);
         assert_eq!(bindings.all_keys_bindings.len(), 1);
@@ -126,17 +543,307 @@ mod tests {
 
     #[test]
     fn test_key_bindings_get() {
-        let mut bindings = KeyBindings::new();
+        let mut bindings = KeyBindings::<()>::new();
         let echo = Box::new(EchoBinding);
-        bindings.add(Keys::Character('a'), echo);
-        assert!(bindings.get(&Keys::Character('a')).is_some());
-        assert!(bindings.get(&Keys::Character('b')).is_none());
+        bindings.add(&[Keys::Character('a')], echo);
+        assert!(bindings.get(&[Keys::Character('a')]).is_some());
+        assert!(bindings.get(&[Keys::Character('b')]).is_none());
     }
 
     #[test]
     fn test_key_press_event_new() {
-        let key_press = KeyPress::new(Keys::Character('x'), "x".to_string());
-        let event = KeyPressEvent::new(key_press);
-        assert_eq!(event.key_press.key(), Keys::Character('x'));
+        let mut app = app();
+        let key_sequence = vec![key(Keys::ControlX), key(Keys::Character('x'))];
+        let event = KeyPressEvent::new(key_sequence, &mut app);
+        assert_eq!(event.key_press().key(), Keys::Character('x'));
+        assert_eq!(event.data(), "x");
+        assert_eq!(event.key_sequence().len(), 2);
+        assert_eq!((event.arg(), event.arg_present()), (1, false));
+        assert!(event.current_buffer().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "a key press event needs a key")]
+    fn test_key_press_event_needs_a_key() {
+        let mut app = app();
+        let _ = KeyPressEvent::new(Vec::new(), &mut app);
+    }
+
+    #[test]
+    fn test_add_fn() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let mut bindings = KeyBindings::new();
+        let recorded = Rc::clone(&calls);
+        bindings.add_fn(&[Keys::ControlX, Keys::Any], move |event| {
+            recorded.borrow_mut().push(event.data().to_string());
+        });
+        let mut processor = KeyProcessor::new(Box::new(bindings));
+        let mut app = app();
+        processor.process_key(key(Keys::ControlX), &mut app);
+        processor.process_key(key(Keys::Character('y')), &mut app);
+        assert_eq!(*calls.borrow(), vec!["y"]);
+    }
+
+    struct DigitBinding;
+
+    impl Binding<()> for DigitBinding {
+        fn handler(&mut self, event: &mut KeyPressEvent<'_, ()>) {
+            let digit = event.key_press().key();
+            if let Keys::Character(c) = digit {
+                event.append_to_arg_count(c);
+            }
+        }
+    }
+
+    struct ArgBinding(Rc<Cell<usize>>);
+
+    impl Binding<()> for ArgBinding {
+        fn handler(&mut self, event: &mut KeyPressEvent<'_, ()>) {
+            self.0.set(event.arg());
+        }
+    }
+
+    #[test]
+    fn test_repeat_count_goes_to_the_next_binding() {
+        let arg = Rc::new(Cell::new(0));
+        let mut bindings = KeyBindings::new();
+        for digit in ['1', '2'] {
+            bindings.add(
+                &[Keys::Escape, Keys::Character(digit)],
+                Box::new(DigitBinding),
+            );
+        }
+        bindings.add(&[Keys::ControlA], Box::new(ArgBinding(Rc::clone(&arg))));
+        let mut processor = KeyProcessor::new(Box::new(bindings));
+        let mut app = app();
+
+        for k in [
+            Keys::Escape,
+            Keys::Character('1'),
+            Keys::Escape,
+            Keys::Character('2'),
+        ] {
+            processor.process_key(key(k), &mut app);
+        }
+        processor.process_key(key(Keys::ControlA), &mut app);
+        assert_eq!(arg.get(), 12);
+        processor.process_key(key(Keys::ControlA), &mut app);
+        assert_eq!(arg.get(), 1);
+    }
+
+    #[test]
+    fn test_repeat_count_is_capped() {
+        let arg = Rc::new(Cell::new(0));
+        let mut bindings = KeyBindings::new();
+        bindings.add(
+            &[Keys::Escape, Keys::Character('9')],
+            Box::new(DigitBinding),
+        );
+        bindings.add(&[Keys::ControlA], Box::new(ArgBinding(Rc::clone(&arg))));
+        let mut processor = KeyProcessor::new(Box::new(bindings));
+        let mut app = app();
+
+        for _ in 0..40 {
+            processor.process_key(key(Keys::Escape), &mut app);
+            processor.process_key(key(Keys::Character('9')), &mut app);
+        }
+        processor.process_key(key(Keys::ControlA), &mut app);
+        assert_eq!(arg.get(), 999_999);
+    }
+
+    struct NamedBinding(&'static str, Rc<RefCell<Vec<String>>>);
+
+    impl Binding<()> for NamedBinding {
+        fn handler(&mut self, event: &mut KeyPressEvent<'_, ()>) {
+            let key_press = event.key_press();
+            let key = match key_press.key() {
+                Keys::Character(_) => key_press.text(),
+                key => key.as_str(),
+            };
+            self.1.borrow_mut().push(format!("{}:{key}", self.0));
+        }
+    }
+
+    fn processor() -> (Box<KeyProcessor<()>>, Rc<RefCell<Vec<String>>>) {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let binding = |name| Box::new(NamedBinding(name, Rc::clone(&calls)));
+        let mut bindings = KeyBindings::new();
+        bindings.add(&[Keys::Escape], binding("escape"));
+        bindings.add(&[Keys::Escape, Keys::Character('f')], binding("meta-f"));
+        bindings.add(&[Keys::ControlX, Keys::ControlE], binding("edit"));
+        bindings.add(&[Keys::ControlX, Keys::Any], binding("c-x any"));
+        bindings.add(&[Keys::ControlX, Keys::ControlE], binding("edit again"));
+        bindings.add_for_all_keys(binding("other"));
+        (KeyProcessor::new(Box::new(bindings)), calls)
+    }
+
+    fn app() -> Application<()> {
+        Application::for_layout(Layout::default())
+    }
+
+    fn key(key: Keys) -> KeyPress {
+        let text = match key {
+            Keys::Character(c) => c.to_string(),
+            _ => String::new(),
+        };
+        KeyPress::new(key, text)
+    }
+
+    #[test]
+    fn test_sequences_wait_for_their_last_key() {
+        let (mut processor, calls) = processor();
+        let mut app = app();
+
+        processor.process_key(key(Keys::Escape), &mut app);
+        assert!(calls.borrow().is_empty());
+        assert_eq!(processor.pending_keys().len(), 1);
+        processor.process_key(key(Keys::Character('f')), &mut app);
+
+        processor.process_key(key(Keys::ControlX), &mut app);
+        processor.process_key(key(Keys::ControlE), &mut app);
+        processor.process_key(key(Keys::ControlX), &mut app);
+        processor.process_key(key(Keys::ControlA), &mut app);
+        assert_eq!(
+            *calls.borrow(),
+            ["meta-f:f", "edit again:c-e", "c-x any:c-a"]
+        );
+    }
+
+    #[test]
+    fn test_unfinished_sequences_fall_back_to_shorter_ones() {
+        let (mut processor, calls) = processor();
+        let mut app = app();
+
+        processor.process_key(key(Keys::Escape), &mut app);
+        processor.process_key(key(Keys::Character('x')), &mut app);
+        processor.process_key(key(Keys::Escape), &mut app);
+        processor.flush(&mut app);
+        processor.process_key(key(Keys::ControlX), &mut app);
+        processor.flush(&mut app);
+        assert_eq!(
+            *calls.borrow(),
+            ["escape:escape", "other:x", "escape:escape", "other:c-x"]
+        );
+        assert!(processor.pending_keys().is_empty());
+    }
+
+    #[test]
+    fn test_merged_key_bindings() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let binding = |name| Box::new(NamedBinding(name, Rc::clone(&calls)));
+        let mut defaults = KeyBindings::new();
+        defaults.add(&[Keys::ControlA], binding("default"));
+        defaults.add(&[Keys::ControlB], binding("default"));
+        defaults.add_for_all_keys(binding("default other"));
+        let mut plugin = KeyBindings::new();
+        plugin.add(&[Keys::ControlA], binding("plugin"));
+        plugin.add(&[Keys::ControlX, Keys::ControlE], binding("plugin"));
+        plugin.add(&[Keys::Any], binding("plugin any"));
+        let merged = MergedKeyBindings::new(vec![Box::new(defaults), Box::new(plugin)]);
+        let mut processor = KeyProcessor::new(Box::new(merged));
+        let mut app = app();
+
+        for key_press in [
+            Keys::ControlA,
+            Keys::ControlB,
+            Keys::ControlX,
+            Keys::ControlE,
+        ] {
+            processor.process_key(key(key_press), &mut app);
+        }
+        processor.process_key(key(Keys::ControlC), &mut app);
+        assert_eq!(
+            *calls.borrow(),
+            vec!["plugin:c-a", "default:c-b", "plugin:c-e", "plugin any:c-c"]
+        );
+    }
+
+    #[test]
+    fn test_conditional_key_bindings() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let active = Rc::new(Cell::new(false));
+        let mut plugin = KeyBindings::new();
+        plugin.add(
+            &[Keys::ControlA],
+            Box::new(NamedBinding("plugin", Rc::clone(&calls))),
+        );
+        plugin.add_for_all_keys(Box::new(NamedBinding("plugin other", Rc::clone(&calls))));
+        let is_active = Rc::clone(&active);
+        let conditional = ConditionalKeyBindings::new(
+            Box::new(plugin),
+            Filter::condition(move || is_active.get()),
+        );
+        let mut processor = KeyProcessor::new(Box::new(conditional));
+        let mut app = app();
+
+        processor.process_key(key(Keys::ControlA), &mut app);
+        assert!(calls.borrow().is_empty());
+        active.set(true);
+        processor.process_key(key(Keys::ControlA), &mut app);
+        processor.process_key(key(Keys::ControlB), &mut app);
+        assert_eq!(*calls.borrow(), vec!["plugin:c-a", "plugin other:c-b"]);
+    }
+
+    #[test]
+    fn test_dynamic_key_bindings() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let key_map = |name| {
+            let mut bindings = KeyBindings::new();
+            bindings.add(
+                &[Keys::ControlA],
+                Box::new(NamedBinding(name, Rc::clone(&calls))),
+            );
+            let bindings: SharedKeyBindings<()> = Rc::new(RefCell::new(bindings));
+            bindings
+        };
+        let key_maps = [key_map("first"), key_map("second")];
+        let current = Rc::new(Cell::new(Some(0)));
+        let selected = Rc::clone(&current);
+        let dynamic = DynamicKeyBindings::new(move || Some(Rc::clone(&key_maps[selected.get()?])));
+        let mut processor = KeyProcessor::new(Box::new(dynamic));
+        let mut app = app();
+
+        processor.process_key(key(Keys::ControlA), &mut app);
+        current.set(Some(1));
+        processor.process_key(key(Keys::ControlA), &mut app);
+        current.set(None);
+        processor.process_key(key(Keys::ControlA), &mut app);
+        assert_eq!(*calls.borrow(), vec!["first:c-a", "second:c-a"]);
+    }
+
+    #[test]
+    fn test_filters_are_evaluated_per_key_press() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let active = Rc::new(Cell::new(false));
+        let is_active = || {
+            let active = Rc::clone(&active);
+            Filter::condition(move || active.get())
+        };
+
+        let binding = |name| Box::new(NamedBinding(name, Rc::clone(&calls)));
+        let mut bindings = KeyBindings::new();
+        bindings.add(&[Keys::ControlA], binding("plain"));
+        bindings.add_with_filter(&[Keys::ControlA], is_active(), binding("filtered"));
+        bindings.add_with_filter(
+            &[Keys::ControlB, Keys::ControlC],
+            is_active(),
+            binding("sequence"),
+        );
+        bindings.add_for_all_keys(binding("other"));
+        let mut processor = KeyProcessor::new(Box::new(bindings));
+        let mut app = app();
+
+        processor.process_key(key(Keys::ControlA), &mut app);
+        processor.process_key(key(Keys::ControlB), &mut app);
+        assert!(processor.pending_keys().is_empty());
+        active.set(true);
+        processor.process_key(key(Keys::ControlA), &mut app);
+        processor.process_key(key(Keys::ControlB), &mut app);
+        assert_eq!(processor.pending_keys().len(), 1);
+        processor.process_key(key(Keys::ControlC), &mut app);
+        assert_eq!(
+            *calls.borrow(),
+            ["plain:c-a", "other:c-b", "filtered:c-a", "sequence:c-c"]
+        );
     }
 }
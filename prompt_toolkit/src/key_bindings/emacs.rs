@@ -0,0 +1,484 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    buffer::Buffer,
+    clipboard::Clipboard,
+    document::Document,
+    key_bindings::{
+        named_commands::CommandFactory, Binding, KeyBindings, KeyPressEvent, NamedCommands,
+        MAX_ARG_COUNT,
+    },
+    keys::Keys,
+    selection::PasteMode,
+    KeyPress,
+};
+
+/// What the previous Emacs command left behind for the next one.
+#[derive(Debug, Default)]
+struct EmacsState {
+    /// Text was inserted, so further typing belongs to the same undo step.
+    inserting: bool,
+    /// The text as it was before a yank, for Meta-Y to paste something else instead.
+    document_before_paste: Option<Document>,
+}
+
+/// What a command gets to work with.
+struct Context<'a> {
+    /// Left by the previous command.
+    last: EmacsState,
+    /// Handed to the next command.
+    next: EmacsState,
+    buffer: &'a mut Buffer,
+    clipboard: &'a mut Clipboard,
+    key_press: &'a KeyPress,
+    /// The repeat count, typed as Meta and digits.
+    arg: usize,
+}
+
+type Command = fn(&mut Context<'_>);
+
+/// Runs an Emacs command on the current buffer. Does nothing when the
+/// focused window has no buffer.
+struct EmacsBinding {
+    state: Rc<RefCell<EmacsState>>,
+    command: Command,
+}
+
+impl<T> Binding<T> for EmacsBinding {
+    fn handler(&mut self, event: &mut KeyPressEvent<'_, T>) {
+        let Some(buffer) = event.current_buffer() else {
+            return;
+        };
+        let key_press = event.key_press().clone();
+        let arg = event.arg();
+        let last = std::mem::take(&mut *self.state.borrow_mut());
+        let mut context = Context {
+            last,
+            next: EmacsState::default(),
+            buffer: &mut buffer.borrow_mut(),
+            clipboard: event.app_mut().clipboard_mut(),
+            key_press: &key_press,
+            arg,
+        };
+        (self.command)(&mut context);
+        *self.state.borrow_mut() = context.next;
+    }
+}
+
+/// Meta and a digit: add the digit to the repeat count of the next command.
+struct DigitArgument;
+
+impl<T> Binding<T> for DigitArgument {
+    fn handler(&mut self, event: &mut KeyPressEvent<'_, T>) {
+        if let Keys::Character(digit) = event.key_press().key() {
+            event.append_to_arg_count(digit);
+        }
+    }
+}
+
+/// The readline-like Emacs editing bindings, acting on the current buffer.
+///
+/// | Keys | Command |
+/// |---|---|
+/// | Ctrl-A, Home / Ctrl-E, End | beginning / end of line |
+/// | Ctrl-B, Left / Ctrl-F, Right | backward / forward char |
+/// | Meta-B / Meta-F | backward / forward word |
+/// | Ctrl-H (Backspace) / Ctrl-D, Delete | delete char before / under the cursor |
+/// | Ctrl-K / Ctrl-U | kill to the end / start of the line |
+/// | Ctrl-W / Meta-D / Meta-Backspace | kill the word before / after the cursor |
+/// | Ctrl-Y / Meta-Y | yank / replace the yank with the previous kill |
+/// | Ctrl-T | transpose characters |
+/// | Ctrl-_ | undo |
+/// | Meta-0 ... Meta-9 | repeat count for the next command |
+///
+/// Other keys insert their text. Meta combinations are bound as Escape
+/// followed by the key, which is also what terminals send for Alt. Killed
+/// text goes to the application's [`Clipboard`], which acts as kill ring.
+#[must_use]
+pub fn load_emacs_bindings<T>() -> KeyBindings<T> {
+    let commands = NamedCommands::new();
+    let mut key_bindings = KeyBindings::new();
+    let bindings: [(&[Keys], &str); 23] = [
+        (&[Keys::ControlA], "beginning-of-line"),
+        (&[Keys::Home], "beginning-of-line"),
+        (&[Keys::ControlE], "end-of-line"),
+        (&[Keys::End], "end-of-line"),
+        (&[Keys::ControlB], "backward-char"),
+        (&[Keys::Left], "backward-char"),
+        (&[Keys::ControlF], "forward-char"),
+        (&[Keys::Right], "forward-char"),
+        (&[Keys::Escape, Keys::Character('b')], "backward-word"),
+        (&[Keys::Escape, Keys::Character('f')], "forward-word"),
+        (&[Keys::ControlH], "backward-delete-char"),
+        (&[Keys::ControlD], "delete-char"),
+        (&[Keys::Delete], "delete-char"),
+        (&[Keys::ControlK], "kill-line"),
+        (&[Keys::ControlU], "unix-line-discard"),
+        (&[Keys::ControlW], "unix-word-rubout"),
+        (&[Keys::Escape, Keys::Character('d')], "kill-word"),
+        (&[Keys::Escape, Keys::ControlH], "backward-kill-word"),
+        (&[Keys::ControlY], "yank"),
+        (&[Keys::Escape, Keys::Character('y')], "yank-pop"),
+        (&[Keys::ControlT], "transpose-chars"),
+        (&[Keys::ControlUnderscore], "undo"),
+        (&[Keys::BracketedPaste], "self-insert"),
+    ];
+    for (keys, name) in bindings {
+        if let Some(binding) = commands.get(name) {
+            key_bindings.add(keys, binding);
+        }
+    }
+    for digit in '0'..='9' {
+        key_bindings.add(
+            &[Keys::Escape, Keys::Character(digit)],
+            Box::new(DigitArgument),
+        );
+    }
+    if let Some(binding) = commands.get("self-insert") {
+        key_bindings.add_for_all_keys(binding);
+    }
+    key_bindings
+}
+
+/// The commands by their readline names.
+const COMMANDS: [(&str, Command); 18] = [
+    ("beginning-of-line", beginning_of_line),
+    ("end-of-line", end_of_line),
+    ("backward-char", backward_char),
+    ("forward-char", forward_char),
+    ("backward-word", backward_word),
+    ("forward-word", forward_word),
+    ("backward-delete-char", backward_delete_char),
+    ("delete-char", delete_char),
+    ("kill-line", kill_line),
+    ("unix-line-discard", unix_line_discard),
+    ("unix-word-rubout", unix_word_rubout),
+    ("kill-word", kill_word),
+    ("backward-kill-word", backward_kill_word),
+    ("yank", yank),
+    ("yank-pop", yank_pop),
+    ("transpose-chars", transpose_chars),
+    ("undo", undo),
+    ("self-insert", self_insert),
+];
+
+/// The editing commands for [`NamedCommands`]. The bindings they create
+/// share their state, which e.g. links a yank to the Meta-Y after it.
+pub(super) fn named_commands<T>() -> Vec<(&'static str, CommandFactory<T>)> {
+    let state = Rc::new(RefCell::new(EmacsState::default()));
+    COMMANDS
+        .iter()
+        .map(|&(name, command)| {
+            let state = Rc::clone(&state);
+            let factory: CommandFactory<T> = Box::new(move || {
+                Box::new(EmacsBinding {
+                    state: Rc::clone(&state),
+                    command,
+                })
+            });
+            (name, factory)
+        })
+        .collect()
+}
+
+/// Insert the text of printable keys. Consecutive insertions are undone together.
+fn self_insert(context: &mut Context<'_>) {
+    let key_press = context.key_press;
+    let printable = match key_press.key() {
+        Keys::Character(c) => !c.is_control(),
+        Keys::BracketedPaste => true,
+        _ => false,
+    };
+    if !printable {
+        return;
+    }
+    if !context.last.inserting {
+        context.buffer.save_to_undo_stack();
+    }
+    let count = context.arg.min(MAX_ARG_COUNT);
+    context
+        .buffer
+        .insert_text(&key_press.text().repeat(count), false, true);
+    context.next.inserting = true;
+}
+
+fn beginning_of_line(context: &mut Context<'_>) {
+    let position = context.buffer.document().start_of_line_position();
+    context.buffer.set_cursor_position(position);
+}
+
+fn end_of_line(context: &mut Context<'_>) {
+    let position = context.buffer.document().end_of_line_position();
+    context.buffer.set_cursor_position(position);
+}
+
+fn backward_char(context: &mut Context<'_>) {
+    context.buffer.cursor_left(context.arg);
+}
+
+fn forward_char(context: &mut Context<'_>) {
+    context.buffer.cursor_right(context.arg);
+}
+
+fn backward_word(context: &mut Context<'_>) {
+    let position = context
+        .buffer
+        .document()
+        .find_previous_word_beginning(context.arg, false)
+        .unwrap_or(0);
+    context.buffer.set_cursor_position(position);
+}
+
+fn forward_word(context: &mut Context<'_>) {
+    let document = context.buffer.document();
+    let position = document
+        .find_next_word_ending(context.arg, false, true)
+        .unwrap_or(document.text().len());
+    context.buffer.set_cursor_position(position);
+}
+
+fn backward_delete_char(context: &mut Context<'_>) {
+    context.buffer.save_to_undo_stack();
+    context.buffer.delete_before_cursor(context.arg);
+}
+
+fn delete_char(context: &mut Context<'_>) {
+    context.buffer.save_to_undo_stack();
+    context.buffer.delete(context.arg);
+}
+
+/// Delete `from..to` and put the deleted text on the kill ring.
+fn kill(context: &mut Context<'_>, from: usize, to: usize) {
+    if from == to {
+        return;
+    }
+    context.buffer.save_to_undo_stack();
+    context.buffer.set_cursor_position(from);
+    let deleted = context
+        .buffer
+        .delete(context.buffer.text()[from..to].chars().count());
+    context.clipboard.set_text(&deleted);
+}
+
+/// Kill to the end of the line, or the line break when already there.
+fn kill_line(context: &mut Context<'_>) {
+    let document = context.buffer.document();
+    let from = document.cursor_position();
+    let to = if document.is_cursor_at_the_end_of_line() {
+        from + usize::from(!document.is_cursor_at_the_end())
+    } else {
+        document.end_of_line_position()
+    };
+    kill(context, from, to);
+}
+
+/// Kill to the start of the line, or the line break when already there.
+fn unix_line_discard(context: &mut Context<'_>) {
+    let document = context.buffer.document();
+    let to = document.cursor_position();
+    let from = if document.current_line_before_cursor().is_empty() {
+        to.saturating_sub(1)
+    } else {
+        document.start_of_line_position()
+    };
+    kill(context, from, to);
+}
+
+/// Kill the whitespace-delimited words before the cursor.
+fn unix_word_rubout(context: &mut Context<'_>) {
+    let document = context.buffer.document();
+    let to = document.cursor_position();
+    let from = document
+        .find_previous_word_beginning(context.arg, true)
+        .unwrap_or(0);
+    kill(context, from, to);
+}
+
+fn backward_kill_word(context: &mut Context<'_>) {
+    let document = context.buffer.document();
+    let to = document.cursor_position();
+    let from = document
+        .find_previous_word_beginning(context.arg, false)
+        .unwrap_or(0);
+    kill(context, from, to);
+}
+
+fn kill_word(context: &mut Context<'_>) {
+    let document = context.buffer.document();
+    let from = document.cursor_position();
+    let to = document
+        .find_next_word_ending(context.arg, false, true)
+        .unwrap_or(document.text().len());
+    kill(context, from, to);
+}
+
+fn yank(context: &mut Context<'_>) {
+    context.buffer.save_to_undo_stack();
+    context.next.document_before_paste = Some(context.buffer.document());
+    let data = context.clipboard.get_data();
+    context
+        .buffer
+        .paste_clipboard_data(&data, PasteMode::Emacs, 1);
+}
+
+/// Replace the text pasted by the previous yank with the kill before it.
+fn yank_pop(context: &mut Context<'_>) {
+    let Some(document) = context.last.document_before_paste.take() else {
+        return;
+    };
+    context.buffer.set_document(&document);
+    context.next.document_before_paste = Some(document);
+    context.clipboard.rotate();
+    let data = context.clipboard.get_data();
+    context
+        .buffer
+        .paste_clipboard_data(&data, PasteMode::Emacs, 1);
+}
+
+/// Swap the characters around the cursor, or the two before it at the end of a line.
+fn transpose_chars(context: &mut Context<'_>) {
+    let document = context.buffer.document();
+    if document.cursor_position() == 0 {
+        return;
+    }
+    context.buffer.save_to_undo_stack();
+    if !document.is_cursor_at_the_end_of_line() {
+        context.buffer.cursor_right(1);
+    }
+    context.buffer.swap_characters_before_cursor();
+}
+
+fn undo(context: &mut Context<'_>) {
+    context.buffer.undo();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::key_bindings::test_editor::Editor;
+
+    fn new_editor(text: &str, cursor_position: usize) -> Editor {
+        Editor::new(text, cursor_position, load_emacs_bindings(), ())
+    }
+
+    #[test]
+    fn test_movement() {
+        let mut editor = new_editor("foo bar\nbaz", 5);
+        editor.press(&[Keys::ControlA]);
+        assert_eq!(editor.state().1, 0);
+        editor.press(&[Keys::ControlE]);
+        assert_eq!(editor.state().1, 7);
+        editor.press(&[Keys::ControlB, Keys::ControlB, Keys::ControlF]);
+        assert_eq!(editor.state().1, 6);
+        editor.press(&[Keys::Escape, Keys::Character('b')]);
+        assert_eq!(editor.state().1, 4);
+        editor.press(&[Keys::Escape, Keys::Character('b')]);
+        assert_eq!(editor.state().1, 0);
+        editor.press(&[Keys::Escape, Keys::Character('f')]);
+        assert_eq!(editor.state().1, 3);
+        assert_eq!(editor.state().0, "foo bar\nbaz");
+    }
+
+    #[test]
+    fn test_repeat_count() {
+        let mut editor = new_editor("abcdef", 6);
+        editor.press(&[Keys::Escape, Keys::Character('3'), Keys::ControlB]);
+        assert_eq!(editor.state().1, 3);
+        editor.press(&[Keys::ControlB]);
+        assert_eq!(editor.state().1, 2);
+        editor.press(&[
+            Keys::Escape,
+            Keys::Character('1'),
+            Keys::Escape,
+            Keys::Character('2'),
+            Keys::Character('-'),
+        ]);
+        assert_eq!(editor.state(), (format!("ab{}cdef", "-".repeat(12)), 14));
+    }
+
+    #[test]
+    fn test_huge_repeat_count_is_clamped() {
+        let mut buffer = Buffer::new();
+        let mut clipboard = Clipboard::default();
+        let key_press = KeyPress::new(Keys::Character('x'), "x".to_string());
+        self_insert(&mut Context {
+            last: EmacsState::default(),
+            next: EmacsState::default(),
+            buffer: &mut buffer,
+            clipboard: &mut clipboard,
+            key_press: &key_press,
+            arg: usize::MAX,
+        });
+        assert_eq!(buffer.text().len(), MAX_ARG_COUNT);
+
+        let mut editor = new_editor("", 0);
+        for _ in 0..10 {
+            editor.press(&[Keys::Escape, Keys::Character('9')]);
+        }
+        editor.type_text("y");
+        assert_eq!(editor.state().1, MAX_ARG_COUNT - 1);
+    }
+
+    #[test]
+    fn test_typing_and_undo() {
+        let mut editor = new_editor("", 0);
+        editor.type_text("hello");
+        editor.press(&[Keys::ControlH]);
+        editor.type_text("p!");
+        assert_eq!(editor.state(), ("hellp!".to_string(), 6));
+
+        editor.press(&[Keys::ControlUnderscore]);
+        assert_eq!(editor.state().0, "hell");
+        editor.press(&[Keys::ControlUnderscore]);
+        assert_eq!(editor.state().0, "hello");
+        editor.press(&[Keys::ControlUnderscore]);
+        assert_eq!(editor.state().0, "");
+    }
+
+    #[test]
+    fn test_kill_and_yank() {
+        let mut editor = new_editor("one two three", 4);
+        editor.press(&[Keys::ControlK]);
+        assert_eq!(editor.state(), ("one ".to_string(), 4));
+        editor.press(&[Keys::ControlU]);
+        assert_eq!(editor.state(), (String::new(), 0));
+
+        editor.press(&[Keys::ControlY]);
+        assert_eq!(editor.state(), ("one ".to_string(), 4));
+        editor.press(&[Keys::Escape, Keys::Character('y')]);
+        assert_eq!(editor.state(), ("two three".to_string(), 9));
+        editor.press(&[Keys::Escape, Keys::Character('y')]);
+        assert_eq!(editor.state(), ("one ".to_string(), 4));
+    }
+
+    #[test]
+    fn test_kill_words() {
+        let mut editor = new_editor("echo foo-bar baz", 12);
+        editor.press(&[Keys::ControlW]);
+        assert_eq!(editor.state(), ("echo  baz".to_string(), 5));
+        editor.press(&[Keys::ControlA, Keys::Escape, Keys::Character('d')]);
+        assert_eq!(editor.state(), ("  baz".to_string(), 0));
+        editor.press(&[Keys::ControlY]);
+        assert_eq!(editor.state(), ("echo  baz".to_string(), 4));
+
+        let mut editor = new_editor("echo foo-bar baz", 16);
+        editor.press(&[Keys::Escape, Keys::Character('2'), Keys::ControlW]);
+        assert_eq!(editor.state(), ("echo ".to_string(), 5));
+    }
+
+    #[test]
+    fn test_kill_line_joins_lines_at_end_of_line() {
+        let mut editor = new_editor("ab\ncd", 2);
+        editor.press(&[Keys::ControlK]);
+        assert_eq!(editor.state(), ("abcd".to_string(), 2));
+    }
+
+    #[test]
+    fn test_transpose_chars() {
+        let mut editor = new_editor("abc", 1);
+        editor.press(&[Keys::ControlT]);
+        assert_eq!(editor.state(), ("bac".to_string(), 2));
+        editor.press(&[Keys::ControlE, Keys::ControlT]);
+        assert_eq!(editor.state(), ("bca".to_string(), 3));
+    }
+}
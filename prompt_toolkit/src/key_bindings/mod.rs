@@ -1,3 +1,18 @@
 mod base;
+mod emacs;
+mod named_commands;
+#[cfg(test)]
+mod test_editor;
+mod vi;
 
-pub use base::{Binding, EchoBinding, KeyBindings, KeyPressEvent, KeyProcessor};
+pub(crate) use base::append_digit;
+pub use base::{
+    Binding, ConditionalKeyBindings, DynamicKeyBindings, EchoBinding, KeyBindings, KeyBindingsBase,
+    KeyPressEvent, KeyProcessor, MergedKeyBindings, SharedKeyBindings, MAX_ARG_COUNT,
+};
+pub use emacs::load_emacs_bindings;
+pub use named_commands::NamedCommands;
+pub use vi::{
+    load_vi_bindings, vi_insert_mode, vi_navigation_mode, vi_replace_mode, vi_selection_mode,
+    InputMode, ViState,
+};
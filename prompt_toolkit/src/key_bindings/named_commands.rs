@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use crate::key_bindings::{emacs, Binding, KeyPressEvent};
+
+/// Creates a fresh binding for a command each time it is bound to keys.
+pub(super) type CommandFactory<T> = Box<dyn Fn() -> Box<dyn Binding<T>>>;
+
+/// Editing commands by their readline names, e.g. `"beginning-of-line"`,
+/// so that key maps, e.g. from configuration files, can refer to them by
+/// string.
+///
+/// [`NamedCommands::new`] knows the editing commands of
+/// [`load_emacs_bindings`](super::load_emacs_bindings). More can be added
+/// with [`NamedCommands::register`], and `"accept-line"` with
+/// [`NamedCommands::with_accept_line`].
+pub struct NamedCommands<T> {
+    commands: HashMap<String, CommandFactory<T>>,
+}
+
+impl<T> Default for NamedCommands<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> NamedCommands<T> {
+    /// The editing commands. `"accept-line"` is not among them, as it
+    /// needs to turn the text into a `T`, see
+    /// [`NamedCommands::with_accept_line`].
+    #[must_use]
+    pub fn new() -> Self {
+        let mut named_commands = NamedCommands {
+            commands: HashMap::new(),
+        };
+        for (name, factory) in emacs::named_commands() {
+            named_commands.commands.insert(name.to_string(), factory);
+        }
+        named_commands
+    }
+
+    /// Add a command, or replace the one with the same name.
+    pub fn register(&mut self, name: &str, factory: impl Fn() -> Box<dyn Binding<T>> + 'static) {
+        self.commands.insert(name.to_string(), Box::new(factory));
+    }
+
+    /// A binding running the command called `name`.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<Box<dyn Binding<T>>> {
+        self.commands.get(name).map(|factory| factory())
+    }
+
+    /// The names of all commands, sorted.
+    #[must_use]
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.commands.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl<T: From<String>> NamedCommands<T> {
+    /// Add `"accept-line"`, which exits the application with the text of
+    /// the current buffer.
+    #[must_use]
+    pub fn with_accept_line(mut self) -> Self {
+        self.register("accept-line", || Box::new(AcceptLine));
+        self
+    }
+}
+
+struct AcceptLine;
+
+impl<T: From<String>> Binding<T> for AcceptLine {
+    fn handler(&mut self, event: &mut KeyPressEvent<'_, T>) {
+        let text = event
+            .current_buffer()
+            .map(|buffer| buffer.borrow().text().to_string())
+            .unwrap_or_default();
+        event.app_mut().exit(Ok(T::from(text)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{
+        application::Application,
+        buffer::Buffer,
+        document::Document,
+        key_bindings::{KeyBindings, KeyProcessor},
+        keys::Keys,
+        KeyPress,
+    };
+
+    #[test]
+    fn test_lookup_by_name() {
+        let commands = NamedCommands::<String>::new().with_accept_line();
+        assert!(commands.get("kill-word").is_some());
+        assert!(commands.get("no-such-command").is_none());
+        let names = commands.names();
+        assert!(names.contains(&"accept-line") && names.contains(&"beginning-of-line"));
+        assert!(names.is_sorted());
+    }
+
+    #[test]
+    fn test_named_commands_run_on_the_current_buffer() {
+        let commands = NamedCommands::<String>::new().with_accept_line();
+        let mut key_bindings = KeyBindings::new();
+        for (key, name) in [
+            (Keys::ControlA, "beginning-of-line"),
+            (Keys::ControlD, "kill-word"),
+            (Keys::ControlM, "accept-line"),
+        ] {
+            key_bindings.add(&[key], commands.get(name).expect("known command"));
+        }
+        let mut processor = KeyProcessor::new(Box::new(key_bindings));
+        let buffer = Rc::new(RefCell::new(Buffer::from_document(&Document::from_text(
+            "hello world",
+        ))));
+        let mut app = Application::for_buffer(&buffer);
+        for key in [Keys::ControlA, Keys::ControlD] {
+            processor.process_key(KeyPress::new(key, String::new()), &mut app);
+        }
+        assert_eq!(buffer.borrow().text(), " world");
+        assert_eq!(buffer.borrow().cursor_position(), 0);
+        processor.process_key(KeyPress::new(Keys::ControlM, String::new()), &mut app);
+        assert!(app.is_done());
+        assert_eq!(
+            app.take_result().expect("accepted").expect("no error"),
+            " world"
+        );
+    }
+
+    #[test]
+    fn test_register() {
+        let mut commands = NamedCommands::<String>::new();
+        commands.register("abort", || {
+            Box::new(|event: &mut KeyPressEvent<'_, String>| {
+                event.app_mut().exit(Ok("aborted".to_string()));
+            })
+        });
+        assert!(commands.get("abort").is_some());
+    }
+}
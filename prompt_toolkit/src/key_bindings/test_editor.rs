@@ -0,0 +1,62 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    application::Application,
+    buffer::Buffer,
+    document::Document,
+    key_bindings::{KeyBindingsBase, KeyProcessor},
+    keys::Keys,
+    KeyPress,
+};
+
+/// A buffer edited through key bindings, for the tests of the bindings.
+/// `state` is what the bindings share with the test, e.g. the Vi state.
+pub(super) struct Editor<S = ()> {
+    pub(super) app: Application<()>,
+    processor: Box<KeyProcessor<()>>,
+    pub(super) buffer: Rc<RefCell<Buffer>>,
+    pub(super) state: S,
+}
+
+impl<S> Editor<S> {
+    pub(super) fn new(
+        text: &str,
+        cursor_position: usize,
+        key_bindings: impl KeyBindingsBase<()> + 'static,
+        state: S,
+    ) -> Self {
+        let buffer = Rc::new(RefCell::new(Buffer::from_document(&Document::new(
+            text,
+            cursor_position,
+        ))));
+        Self {
+            app: Application::for_buffer(&buffer),
+            processor: KeyProcessor::new(Box::new(key_bindings)),
+            buffer,
+            state,
+        }
+    }
+
+    pub(super) fn press(&mut self, keys: &[Keys]) -> &mut Self {
+        for key in keys {
+            let text = match key {
+                Keys::Character(c) => c.to_string(),
+                _ => String::new(),
+            };
+            self.processor
+                .process_key(KeyPress::new(*key, text), &mut self.app);
+        }
+        self
+    }
+
+    pub(super) fn type_text(&mut self, text: &str) -> &mut Self {
+        let keys: Vec<Keys> = text.chars().map(Keys::Character).collect();
+        self.press(&keys)
+    }
+
+    /// The text and cursor position of the buffer.
+    pub(super) fn state(&self) -> (String, usize) {
+        let buffer = self.buffer.borrow();
+        (buffer.text().to_string(), buffer.cursor_position())
+    }
+}
@@ -0,0 +1,1271 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    buffer::Buffer,
+    clipboard::{Clipboard, ClipboardData},
+    document::Document,
+    filters::Filter,
+    key_bindings::{append_digit, Binding, KeyBindings, KeyPressEvent, MAX_ARG_COUNT},
+    keys::Keys,
+    selection::{PasteMode, SelectionType},
+    KeyPress,
+};
+
+/// The modes of the Vi state machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputMode {
+    /// Typed keys insert text. Editing starts here, as in a shell.
+    #[default]
+    Insert,
+    /// Keys are commands: counts, motions, operators and mode switches.
+    Navigation,
+    /// Typed keys overwrite the text under the cursor.
+    Replace,
+    /// Navigation that extends a selection of the given type.
+    Visual(SelectionType),
+}
+
+/// The state of the Vi bindings, shared with the filters looking at the mode.
+#[derive(Debug, Default)]
+pub struct ViState {
+    input_mode: InputMode,
+    pending: Pending,
+    registers: HashMap<char, ClipboardData>,
+    /// Keys of the change being typed, for `.` to replay it.
+    recording: Vec<KeyPress>,
+    last_change: Vec<KeyPress>,
+    replaying: bool,
+    block_insert: Option<BlockInsert>,
+}
+
+/// A block change waiting for its text: `c` on a block selection has it
+/// typed on the first row, and leaving insert mode copies it to the others.
+#[derive(Debug, Clone)]
+struct BlockInsert {
+    /// Where typing started on the first row.
+    start: usize,
+    column: usize,
+    /// The other rows that reached into the block.
+    rows: Vec<usize>,
+}
+
+impl ViState {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn input_mode(&self) -> InputMode {
+        self.input_mode
+    }
+
+    /// The contents of a named register, filled by e.g. `"ayw`.
+    #[must_use]
+    pub fn register(&self, name: char) -> Option<&ClipboardData> {
+        self.registers.get(&name)
+    }
+}
+
+/// Active in navigation mode.
+#[must_use]
+pub fn vi_navigation_mode(state: &Rc<RefCell<ViState>>) -> Filter {
+    mode_filter(state, |mode| mode == InputMode::Navigation)
+}
+
+/// Active in insert mode.
+#[must_use]
+pub fn vi_insert_mode(state: &Rc<RefCell<ViState>>) -> Filter {
+    mode_filter(state, |mode| mode == InputMode::Insert)
+}
+
+/// Active in replace mode.
+#[must_use]
+pub fn vi_replace_mode(state: &Rc<RefCell<ViState>>) -> Filter {
+    mode_filter(state, |mode| mode == InputMode::Replace)
+}
+
+/// Active in any of the visual modes.
+#[must_use]
+pub fn vi_selection_mode(state: &Rc<RefCell<ViState>>) -> Filter {
+    mode_filter(state, |mode| matches!(mode, InputMode::Visual(_)))
+}
+
+fn mode_filter(state: &Rc<RefCell<ViState>>, predicate: fn(InputMode) -> bool) -> Filter {
+    let state = Rc::clone(state);
+    Filter::condition(move || predicate(state.borrow().input_mode))
+}
+
+/// A command being typed in navigation mode, e.g. `"a2d3w`.
+#[derive(Debug, Default)]
+struct Pending {
+    register: Option<char>,
+    count: Option<usize>,
+    /// The operator, with the count typed before it.
+    operator: Option<(Operator, Option<usize>)>,
+    awaiting: Option<Awaiting>,
+}
+
+impl Pending {
+    /// The count before the operator times the count after it.
+    fn count(&self) -> Option<usize> {
+        match (self.operator.and_then(|(_, count)| count), self.count) {
+            (None, None) => None,
+            (before, after) => Some(
+                before
+                    .unwrap_or(1)
+                    .saturating_mul(after.unwrap_or(1))
+                    .min(MAX_ARG_COUNT),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+impl Operator {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            'd' => Some(Self::Delete),
+            'c' => Some(Self::Change),
+            'y' => Some(Self::Yank),
+            _ => None,
+        }
+    }
+}
+
+/// A key that is read as the argument of the previous one.
+#[derive(Debug, Clone, Copy)]
+enum Awaiting {
+    /// The name after `"`.
+    Register,
+    /// The object after the `i` or `a` following an operator.
+    TextObject { inner: bool },
+    /// The character after `f`, `t`, `F` or `T`.
+    Find(Find),
+    /// The character after `r`.
+    ReplaceChar,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Find {
+    Forward,
+    TillForward,
+    Backward,
+    TillBackward,
+}
+
+/// What an operator covers between the cursor and the target of a motion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MotionKind {
+    /// Up to the target, without it.
+    Exclusive,
+    /// Up to and including the character at the target.
+    Inclusive,
+    /// All lines from the cursor to the target.
+    Linewise,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Motion {
+    target: usize,
+    kind: MotionKind,
+}
+
+/// What a command gets to work with.
+struct Context<'a> {
+    state: &'a mut ViState,
+    buffer: &'a mut Buffer,
+    clipboard: &'a mut Clipboard,
+    key_press: &'a KeyPress,
+}
+
+impl Context<'_> {
+    fn record(&mut self) {
+        if !self.state.replaying {
+            self.state.recording.push(self.key_press.clone());
+        }
+    }
+
+    /// The command is complete. A change is kept for `.` once it is done,
+    /// which for changes entering insert mode is when Escape is pressed.
+    fn finish(&mut self, change: bool) {
+        self.state.pending = Pending::default();
+        if self.state.replaying
+            || matches!(
+                self.state.input_mode,
+                InputMode::Insert | InputMode::Replace
+            )
+        {
+            return;
+        }
+        let keys = std::mem::take(&mut self.state.recording);
+        if change && !keys.is_empty() {
+            self.state.last_change = keys;
+        }
+    }
+}
+
+type Command = fn(&mut Context<'_>);
+
+/// Runs a Vi command on the current buffer. Does nothing when the focused
+/// window has no buffer.
+struct ViBinding {
+    state: Rc<RefCell<ViState>>,
+    command: Command,
+}
+
+impl<T> Binding<T> for ViBinding {
+    fn handler(&mut self, event: &mut KeyPressEvent<'_, T>) {
+        let Some(buffer) = event.current_buffer() else {
+            return;
+        };
+        let key_press = event.key_press().clone();
+        let mut context = Context {
+            state: &mut self.state.borrow_mut(),
+            buffer: &mut buffer.borrow_mut(),
+            clipboard: event.app_mut().clipboard_mut(),
+            key_press: &key_press,
+        };
+        (self.command)(&mut context);
+    }
+}
+
+/// The command handling keys in `mode`. [`load_vi_bindings`] picks it
+/// through filters, `.` directly when replaying keys.
+fn command_for(mode: InputMode) -> Command {
+    match mode {
+        InputMode::Insert => insert_key,
+        InputMode::Replace => replace_key,
+        InputMode::Navigation | InputMode::Visual(_) => navigation_key,
+    }
+}
+
+/// Vi editing bindings, acting on the current buffer. The mode lives in
+/// `state`, which can also be handed to [`vi_insert_mode`] and friends.
+///
+/// Navigation mode understands:
+///
+/// | Keys | Command |
+/// |---|---|
+/// | `h` `l` `j` `k`, arrows | move by characters and lines |
+/// | `w` `W` / `b` `B` / `e` `E` | next word / previous word / end of word |
+/// | `0` `^` `$` `G` | start, first non-blank, end of line; last line |
+/// | `f` `t` `F` `T` + char | to (before) the next or previous char in the line |
+/// | `d` `c` `y` + motion | delete, change, yank; doubled for whole lines |
+/// | `iw` `aw` `i"` `a"` ... | text objects after an operator |
+/// | `x` `X` `D` `C` `s` `S` `Y` | short forms of `dl` `dh` `d$` `c$` `cl` `cc` `yy` |
+/// | `p` `P` | paste after / before the cursor |
+/// | `i` `a` `I` `A` `o` `O` | enter insert mode |
+/// | `R` / `r` + char | replace mode / replace characters |
+/// | `v` `V` Ctrl-V | visual mode by characters, lines or block |
+/// | `u` / Ctrl-R | undo / redo |
+/// | `.` | repeat the last change |
+///
+/// Commands take a count, e.g. `3dw` or `d3w`, and a register, e.g.
+/// `"ayy` and `"ap`. The unnamed register is the application's
+/// [`Clipboard`]. Escape goes back to navigation mode.
+#[must_use]
+pub fn load_vi_bindings<T>(state: &Rc<RefCell<ViState>>) -> KeyBindings<T> {
+    let mut key_bindings = KeyBindings::new();
+    let modes = [
+        (vi_insert_mode(state), InputMode::Insert),
+        (vi_replace_mode(state), InputMode::Replace),
+        (
+            vi_navigation_mode(state).or(vi_selection_mode(state)),
+            InputMode::Navigation,
+        ),
+    ];
+    for (filter, mode) in modes {
+        let binding = ViBinding {
+            state: Rc::clone(state),
+            command: command_for(mode),
+        };
+        key_bindings.add_with_filter(&[Keys::Any], filter, Box::new(binding));
+    }
+    key_bindings
+}
+
+fn is_printable(key_press: &KeyPress) -> bool {
+    match key_press.key() {
+        Keys::Character(c) => !c.is_control(),
+        Keys::BracketedPaste => true,
+        _ => false,
+    }
+}
+
+/// Insert mode: text goes into the buffer until Escape.
+fn insert_key(context: &mut Context<'_>) {
+    if !context.state.recording.is_empty() {
+        context.record();
+    }
+    let key_press = context.key_press;
+    match key_press.key() {
+        Keys::Escape => leave_insert_mode(context),
+        Keys::ControlH => {
+            context.buffer.delete_before_cursor(1);
+        }
+        Keys::Delete => {
+            context.buffer.delete(1);
+        }
+        Keys::ControlJ => context.buffer.newline(false),
+        Keys::Left => context.buffer.cursor_left(1),
+        Keys::Right => context.buffer.cursor_right(1),
+        Keys::Up => context.buffer.cursor_up(1),
+        Keys::Down => context.buffer.cursor_down(1),
+        _ if is_printable(key_press) => context.buffer.insert_text(key_press.text(), false, true),
+        _ => {}
+    }
+}
+
+/// Replace mode: text overwrites the current line until Escape.
+fn replace_key(context: &mut Context<'_>) {
+    if !context.state.recording.is_empty() {
+        context.record();
+    }
+    let key_press = context.key_press;
+    match key_press.key() {
+        Keys::Escape => leave_insert_mode(context),
+        Keys::ControlH | Keys::Left => context.buffer.cursor_left(1),
+        Keys::Right => context.buffer.cursor_right(1),
+        _ if is_printable(key_press) => context.buffer.insert_text(key_press.text(), true, true),
+        _ => {}
+    }
+}
+
+/// Back to navigation mode, on the last inserted character.
+fn leave_insert_mode(context: &mut Context<'_>) {
+    if let Some(block_insert) = context.state.block_insert.take() {
+        finish_block_insert(context.buffer, &block_insert);
+    }
+    context.state.input_mode = InputMode::Navigation;
+    context.buffer.cursor_left(1);
+    context.finish(true);
+}
+
+fn enter_insert_mode(context: &mut Context<'_>, mode: InputMode) {
+    context.buffer.save_to_undo_stack();
+    context.state.input_mode = mode;
+    context.finish(true);
+}
+
+/// Navigation and visual mode: keys build up commands.
+fn navigation_key(context: &mut Context<'_>) {
+    if context.state.input_mode == InputMode::Navigation {
+        context.record();
+    }
+    let key = context.key_press.key();
+    if let Some(awaiting) = context.state.pending.awaiting.take() {
+        match key {
+            Keys::Character(c) => argument(context, awaiting, c),
+            _ => context.finish(false),
+        }
+    } else if !pending_key(context, key) && !motion_key(context, key) {
+        command_key(context, key);
+    }
+    fix_cursor_position(context);
+}
+
+/// Outside of insert and replace mode, the cursor is on a character, not
+/// after the end of the line.
+fn fix_cursor_position(context: &mut Context<'_>) {
+    if matches!(
+        context.state.input_mode,
+        InputMode::Insert | InputMode::Replace
+    ) {
+        return;
+    }
+    let document = context.buffer.document();
+    if document.is_cursor_at_the_end_of_line() && !document.current_line_before_cursor().is_empty()
+    {
+        let preferred_column = context.buffer.preferred_column();
+        context.buffer.cursor_left(1);
+        context.buffer.set_preferred_column(preferred_column);
+    }
+}
+
+/// Counts and register names, which prefix a command, and Escape, which
+/// cancels it.
+fn pending_key(context: &mut Context<'_>, key: Keys) -> bool {
+    let pending = &mut context.state.pending;
+    match key {
+        Keys::Character(c @ '0'..='9') if c != '0' || pending.count.is_some() => {
+            let digit = c
+                .to_digit(10)
+                .and_then(|digit| usize::try_from(digit).ok())
+                .unwrap_or(0);
+            pending.count = Some(append_digit(pending.count, digit));
+            true
+        }
+        Keys::Character('"') if pending.count.is_none() && pending.operator.is_none() => {
+            pending.awaiting = Some(Awaiting::Register);
+            true
+        }
+        Keys::Escape => {
+            if let InputMode::Visual(_) = context.state.input_mode {
+                context.buffer.exit_selection();
+                context.state.input_mode = InputMode::Navigation;
+            }
+            context.finish(false);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The character following a key that takes one.
+fn argument(context: &mut Context<'_>, awaiting: Awaiting, c: char) {
+    match awaiting {
+        Awaiting::Register => context.state.pending.register = Some(c),
+        Awaiting::TextObject { inner } => {
+            let range = text_object(&context.buffer.document(), c, inner);
+            match (context.state.pending.operator, range) {
+                (Some((operator, _)), Some((from, to))) => {
+                    apply_operator(context, operator, from, to, false);
+                }
+                _ => context.finish(false),
+            }
+        }
+        Awaiting::Find(find) => {
+            let count = context.state.pending.count().unwrap_or(1);
+            match find_motion(&context.buffer.document(), find, c, count) {
+                Some(motion) => apply_motion(context, motion),
+                None => context.finish(false),
+            }
+        }
+        Awaiting::ReplaceChar => replace_characters(context, c),
+    }
+}
+
+fn motion_key(context: &mut Context<'_>, key: Keys) -> bool {
+    let pending = &context.state.pending;
+    let operator = pending.operator.map(|(operator, _)| operator);
+    if let Some(motion) = motion(&context.buffer.document(), key, pending.count(), operator) {
+        apply_motion(context, motion);
+        return true;
+    }
+    let find = match key {
+        Keys::Character('f') => Find::Forward,
+        Keys::Character('t') => Find::TillForward,
+        Keys::Character('F') => Find::Backward,
+        Keys::Character('T') => Find::TillBackward,
+        _ => return false,
+    };
+    context.state.pending.awaiting = Some(Awaiting::Find(find));
+    true
+}
+
+/// Where a motion key moves the cursor, `None` for other keys.
+fn motion(
+    document: &Document,
+    key: Keys,
+    count: Option<usize>,
+    operator: Option<Operator>,
+) -> Option<Motion> {
+    let n = count.unwrap_or(1);
+    let exclusive = |target| {
+        Some(Motion {
+            target,
+            kind: MotionKind::Exclusive,
+        })
+    };
+    let linewise = |target| {
+        Some(Motion {
+            target,
+            kind: MotionKind::Linewise,
+        })
+    };
+    match key {
+        Keys::Character('h') | Keys::Left | Keys::ControlH => {
+            exclusive(document.cursor_left_position(n))
+        }
+        Keys::Character('l' | ' ') | Keys::Right => exclusive(document.cursor_right_position(n)),
+        Keys::Character('j') | Keys::Down => linewise(document.cursor_down_position(n, None)),
+        Keys::Character('k') | Keys::Up => linewise(document.cursor_up_position(n, None)),
+        Keys::Character('G') => {
+            let row = count.unwrap_or(document.line_count()).saturating_sub(1);
+            linewise(document.translate_row_col_to_index(row, 0))
+        }
+        Keys::Character(c @ ('w' | 'W')) => {
+            let big_word = c == 'W';
+            if operator == Some(Operator::Change)
+                && !document.current_char().is_none_or(char::is_whitespace)
+            {
+                // `cw` changes up to the end of the word, like `ce`.
+                return Some(word_end(document, n, big_word, true));
+            }
+            let mut target = document
+                .find_next_word_beginning(n, big_word)
+                .unwrap_or(document.text().len());
+            if operator.is_some() && n == 1 {
+                // Deleting the last word of a line doesn't join the next one.
+                target = target.min(document.end_of_line_position());
+            }
+            exclusive(target)
+        }
+        Keys::Character(c @ ('b' | 'B')) => exclusive(
+            document
+                .find_previous_word_beginning(n, c == 'B')
+                .unwrap_or(0),
+        ),
+        Keys::Character(c @ ('e' | 'E')) => Some(word_end(document, n, c == 'E', false)),
+        Keys::Character('0') | Keys::Home => exclusive(document.start_of_line_position()),
+        Keys::Character('^') => exclusive(
+            document.start_of_line_position() + document.leading_whitespace_in_current_line().len(),
+        ),
+        Keys::Character('$') | Keys::End => exclusive(document.end_of_line_position()),
+        _ => None,
+    }
+}
+
+/// The last character of the `count`th word ending after the cursor, or
+/// at it with `include_current_position`.
+fn word_end(
+    document: &Document,
+    count: usize,
+    big_word: bool,
+    include_current_position: bool,
+) -> Motion {
+    let end = document
+        .find_next_word_ending(count, big_word, include_current_position)
+        .unwrap_or(document.text().len());
+    Motion {
+        target: document.text().floor_char_boundary(end.saturating_sub(1)),
+        kind: MotionKind::Inclusive,
+    }
+}
+
+/// The `count`th occurrence of `c` in the current line.
+fn find_motion(document: &Document, find: Find, c: char, count: usize) -> Option<Motion> {
+    let cursor = document.cursor_position();
+    match find {
+        Find::Forward | Find::TillForward => {
+            let (offset, _) = document
+                .current_line_after_cursor()
+                .char_indices()
+                .skip(1)
+                .filter(|(_, found)| *found == c)
+                .nth(count.checked_sub(1)?)?;
+            let mut target = cursor + offset;
+            if let Find::TillForward = find {
+                target = document.text().floor_char_boundary(target - 1);
+            }
+            Some(Motion {
+                target,
+                kind: MotionKind::Inclusive,
+            })
+        }
+        Find::Backward | Find::TillBackward => {
+            let (offset, _) = document
+                .current_line_before_cursor()
+                .char_indices()
+                .rev()
+                .filter(|(_, found)| *found == c)
+                .nth(count.checked_sub(1)?)?;
+            let mut target = document.start_of_line_position() + offset;
+            if let Find::TillBackward = find {
+                target += c.len_utf8();
+            }
+            Some(Motion {
+                target,
+                kind: MotionKind::Exclusive,
+            })
+        }
+    }
+}
+
+/// The `(from, to)` range of a text object: `w`, `W` or a quoted string.
+fn text_object(document: &Document, c: char, inner: bool) -> Option<(usize, usize)> {
+    match c {
+        'w' | 'W' => {
+            let range = document.find_boundaries_of_current_word(c == 'W', false, !inner);
+            (range.0 != range.1).then_some(range)
+        }
+        '"' | '\'' | '`' => {
+            let (open, close) = quote_boundaries(document, c)?;
+            if inner {
+                Some((open + c.len_utf8(), close))
+            } else {
+                Some((open, close + c.len_utf8()))
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The positions of the quotes around the cursor, or of the next quoted
+/// string in the line. Quotes pair up from the start of the line, as in Vim.
+fn quote_boundaries(document: &Document, quote: char) -> Option<(usize, usize)> {
+    let line_start = document.start_of_line_position();
+    let cursor = document.cursor_position() - line_start;
+    let quotes: Vec<usize> = document
+        .current_line()
+        .char_indices()
+        .filter(|(_, c)| *c == quote)
+        .map(|(i, _)| i)
+        .collect();
+    quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|(_, close)| *close >= cursor)
+        .map(|(open, close)| (line_start + open, line_start + close))
+}
+
+fn char_len_at(text: &str, position: usize) -> usize {
+    text[position..].chars().next().map_or(0, char::len_utf8)
+}
+
+/// The lines containing `from` and `to`, without the final line break.
+fn line_range(text: &str, from: usize, to: usize) -> (usize, usize) {
+    let start = text[..from].rfind('\n').map_or(0, |i| i + 1);
+    let end = text[to..].find('\n').map_or(text.len(), |i| to + i);
+    (start, end)
+}
+
+/// Move the cursor to the target of a motion, or apply the pending
+/// operator up to it.
+fn apply_motion(context: &mut Context<'_>, motion: Motion) {
+    let document = context.buffer.document();
+    let cursor = document.cursor_position();
+    let Some((operator, _)) = context.state.pending.operator else {
+        if motion.kind == MotionKind::Linewise {
+            // Through the buffer, which remembers the column to return to.
+            let row = document.cursor_position_row();
+            let (target_row, _) = document.translate_index_to_position(motion.target);
+            if target_row > row {
+                context.buffer.cursor_down(target_row - row);
+            } else {
+                context.buffer.cursor_up(row - target_row);
+            }
+        } else {
+            context.buffer.set_cursor_position(motion.target);
+        }
+        context.finish(false);
+        return;
+    };
+
+    let (from, to) = (cursor.min(motion.target), cursor.max(motion.target));
+    match motion.kind {
+        MotionKind::Exclusive => apply_operator(context, operator, from, to, false),
+        MotionKind::Inclusive => {
+            let to = to + char_len_at(document.text(), to);
+            apply_operator(context, operator, from, to, false);
+        }
+        MotionKind::Linewise => {
+            let (from, to) = line_range(document.text(), from, to);
+            apply_operator(context, operator, from, to, true);
+        }
+    }
+}
+
+/// `dd`, `cc` and `yy`: `count` lines from the current one.
+fn operate_on_lines(context: &mut Context<'_>, operator: Operator, count: usize) {
+    let document = context.buffer.document();
+    let last_row = std::cmp::min(
+        document.cursor_position_row() + count - 1,
+        document.line_count() - 1,
+    );
+    let last_line = document.translate_row_col_to_index(last_row, 0);
+    let (from, to) = line_range(document.text(), document.cursor_position(), last_line);
+    apply_operator(context, operator, from, to, true);
+}
+
+/// A short form for an operator with a motion, like `x` for `dl`.
+fn operate(context: &mut Context<'_>, operator: Operator, motion_key: char) {
+    context.state.pending.operator = Some((operator, None));
+    let count = context.state.pending.count();
+    let document = context.buffer.document();
+    if let Some(motion) = motion(
+        &document,
+        Keys::Character(motion_key),
+        count,
+        Some(operator),
+    ) {
+        apply_motion(context, motion);
+    }
+}
+
+/// Put text in the selected register. The unnamed register, the
+/// clipboard, always gets it.
+fn store_register(context: &mut Context<'_>, data: ClipboardData) {
+    if let Some(name) = context.state.pending.register.filter(|name| *name != '"') {
+        context.state.registers.insert(name, data.clone());
+    }
+    context.clipboard.set_data(data);
+}
+
+/// Delete, change or yank `from..to`, as whole lines when `linewise`.
+fn apply_operator(
+    context: &mut Context<'_>,
+    operator: Operator,
+    from: usize,
+    to: usize,
+    linewise: bool,
+) {
+    if from == to && !linewise && operator != Operator::Change {
+        context.finish(false);
+        return;
+    }
+    let selection_type = if linewise {
+        SelectionType::Lines
+    } else {
+        SelectionType::Characters
+    };
+    let data = ClipboardData::new(&context.buffer.text()[from..to], selection_type);
+    store_register(context, data);
+
+    if operator == Operator::Yank {
+        // To the start of the yanked text; whole lines only when it moves up.
+        let cursor = context.buffer.cursor_position();
+        if !linewise || cursor < from || context.buffer.text()[from..cursor].contains('\n') {
+            context.buffer.set_cursor_position(from);
+        }
+        context.finish(false);
+        return;
+    }
+
+    context.buffer.save_to_undo_stack();
+    let (from, to) = if linewise && operator == Operator::Delete {
+        // Take a line break along, the one before the last line.
+        if to < context.buffer.text().len() {
+            (from, to + 1)
+        } else {
+            (from.saturating_sub(1), to)
+        }
+    } else {
+        (from, to)
+    };
+    delete_range(context.buffer, from, to);
+
+    if operator == Operator::Change {
+        context.state.input_mode = InputMode::Insert;
+    } else if linewise {
+        let document = context.buffer.document();
+        context.buffer.set_cursor_position(
+            document.start_of_line_position() + document.leading_whitespace_in_current_line().len(),
+        );
+    }
+    context.finish(true);
+}
+
+fn delete_range(buffer: &mut Buffer, from: usize, to: usize) {
+    buffer.set_cursor_position(from);
+    let count = buffer.text()[from..to].chars().count();
+    buffer.delete(count);
+}
+
+/// Operators and commands that aren't motions.
+fn command_key(context: &mut Context<'_>, key: Keys) {
+    if let InputMode::Visual(_) = context.state.input_mode {
+        visual_command(context, key);
+        return;
+    }
+    let count = context.state.pending.count().unwrap_or(1);
+    if let Some((operator, _)) = context.state.pending.operator {
+        match key {
+            Keys::Character(c) if Operator::from_char(c) == Some(operator) => {
+                operate_on_lines(context, operator, count);
+            }
+            Keys::Character(c @ ('i' | 'a')) => {
+                context.state.pending.awaiting = Some(Awaiting::TextObject { inner: c == 'i' });
+            }
+            _ => context.finish(false),
+        }
+        return;
+    }
+
+    match key {
+        Keys::Character(c @ ('d' | 'c' | 'y')) => {
+            let pending = &mut context.state.pending;
+            pending.operator = Operator::from_char(c).map(|operator| (operator, pending.count));
+            pending.count = None;
+        }
+        Keys::Character('x') => operate(context, Operator::Delete, 'l'),
+        Keys::Character('X') => operate(context, Operator::Delete, 'h'),
+        Keys::Character('D') => operate(context, Operator::Delete, '$'),
+        Keys::Character('C') => operate(context, Operator::Change, '$'),
+        Keys::Character('s') => operate(context, Operator::Change, 'l'),
+        Keys::Character('S') => operate_on_lines(context, Operator::Change, count),
+        Keys::Character('Y') => operate_on_lines(context, Operator::Yank, count),
+        Keys::Character('p') => paste(context, PasteMode::ViAfter, count),
+        Keys::Character('P') => paste(context, PasteMode::ViBefore, count),
+        Keys::Character(c @ ('i' | 'a' | 'I' | 'A' | 'o' | 'O')) => insert(context, c),
+        Keys::Character('R') => enter_insert_mode(context, InputMode::Replace),
+        Keys::Character('r') => context.state.pending.awaiting = Some(Awaiting::ReplaceChar),
+        Keys::Character('u') => {
+            for _ in 0..count {
+                if !context.buffer.undo() {
+                    break;
+                }
+            }
+            context.finish(false);
+        }
+        Keys::ControlR => {
+            for _ in 0..count {
+                if !context.buffer.redo() {
+                    break;
+                }
+            }
+            context.finish(false);
+        }
+        Keys::Character('v') => toggle_visual(context, SelectionType::Characters),
+        Keys::Character('V') => toggle_visual(context, SelectionType::Lines),
+        Keys::ControlV => toggle_visual(context, SelectionType::Block),
+        Keys::Character('.') => repeat_last_change(context, count),
+        _ => context.finish(false),
+    }
+}
+
+/// The ways into insert mode: before or after the cursor, at the start or
+/// end of the line, or on a new line below or above.
+fn insert(context: &mut Context<'_>, c: char) {
+    let document = context.buffer.document();
+    let buffer = &mut *context.buffer;
+    match c {
+        'a' => buffer.cursor_right(1),
+        'I' => buffer.set_cursor_position(
+            document.start_of_line_position() + document.leading_whitespace_in_current_line().len(),
+        ),
+        'A' => buffer.set_cursor_position(document.end_of_line_position()),
+        'o' => {
+            buffer.save_to_undo_stack();
+            buffer.set_cursor_position(document.end_of_line_position());
+            buffer.newline(false);
+        }
+        'O' => {
+            buffer.save_to_undo_stack();
+            buffer.set_cursor_position(document.start_of_line_position());
+            buffer.insert_text("\n", false, false);
+        }
+        _ => {}
+    }
+    enter_insert_mode(context, InputMode::Insert);
+}
+
+fn paste(context: &mut Context<'_>, paste_mode: PasteMode, count: usize) {
+    let data = match context.state.pending.register.filter(|name| *name != '"') {
+        Some(name) => context.state.registers.get(&name).cloned(),
+        None => Some(context.clipboard.get_data()),
+    };
+    if let Some(data) = data {
+        context.buffer.save_to_undo_stack();
+        context
+            .buffer
+            .paste_clipboard_data(&data, paste_mode, count);
+    }
+    context.finish(true);
+}
+
+/// `r`: replace `count` characters with `c`, if the line has that many.
+fn replace_characters(context: &mut Context<'_>, c: char) {
+    let count = context.state.pending.count().unwrap_or(1);
+    let document = context.buffer.document();
+    if document.current_line_after_cursor().chars().count() < count {
+        context.finish(false);
+        return;
+    }
+    context.buffer.save_to_undo_stack();
+    context
+        .buffer
+        .insert_text(&c.to_string().repeat(count.min(MAX_ARG_COUNT)), true, true);
+    context.buffer.cursor_left(1);
+    context.finish(true);
+}
+
+/// `.`: replay the keys of the last change, `count` times.
+fn repeat_last_change(context: &mut Context<'_>, count: usize) {
+    context.finish(false);
+    let keys = context.state.last_change.clone();
+    context.state.replaying = true;
+    for _ in 0..count {
+        let before = context.buffer.document();
+        for key_press in &keys {
+            let command = command_for(context.state.input_mode);
+            command(&mut Context {
+                state: &mut *context.state,
+                buffer: &mut *context.buffer,
+                clipboard: &mut *context.clipboard,
+                key_press,
+            });
+        }
+        if context.buffer.document() == before {
+            // Repeating it again won't change anything either.
+            break;
+        }
+    }
+    context.state.replaying = false;
+}
+
+/// Start a visual mode, switch to another one, or leave it when it is the
+/// current one.
+fn toggle_visual(context: &mut Context<'_>, selection_type: SelectionType) {
+    match context.state.input_mode {
+        InputMode::Visual(current) if current == selection_type => {
+            context.buffer.exit_selection();
+            context.state.input_mode = InputMode::Navigation;
+        }
+        InputMode::Visual(_) => {
+            if let Some(selection) = context.buffer.selection_state_mut() {
+                selection.selection_type = selection_type;
+            }
+            context.state.input_mode = InputMode::Visual(selection_type);
+        }
+        _ => {
+            context.buffer.start_selection(selection_type);
+            context.state.input_mode = InputMode::Visual(selection_type);
+        }
+    }
+    context.finish(false);
+}
+
+fn visual_command(context: &mut Context<'_>, key: Keys) {
+    match key {
+        Keys::Character('d' | 'x') => visual_operator(context, Operator::Delete),
+        Keys::Character('c' | 's') => visual_operator(context, Operator::Change),
+        Keys::Character('y') => visual_operator(context, Operator::Yank),
+        Keys::Character('v') => toggle_visual(context, SelectionType::Characters),
+        Keys::Character('V') => toggle_visual(context, SelectionType::Lines),
+        Keys::ControlV => toggle_visual(context, SelectionType::Block),
+        _ => context.finish(false),
+    }
+}
+
+/// Apply an operator to the selection, which includes the character under
+/// the cursor, and go back to navigation mode.
+fn visual_operator(context: &mut Context<'_>, operator: Operator) {
+    let document = context.buffer.document();
+    context.buffer.exit_selection();
+    context.state.input_mode = InputMode::Navigation;
+    let Some(selection) = document.selection().copied() else {
+        context.finish(false);
+        return;
+    };
+    let cursor = document.cursor_position();
+    let anchor = selection.original_cursor_position;
+    let (from, to) = (cursor.min(anchor), cursor.max(anchor));
+    match selection.selection_type {
+        SelectionType::Characters => {
+            let to = to + char_len_at(document.text(), to);
+            apply_operator(context, operator, from, to, false);
+        }
+        SelectionType::Lines => {
+            let (from, to) = line_range(document.text(), from, to);
+            apply_operator(context, operator, from, to, true);
+        }
+        SelectionType::Block => block_operator(context, &document, operator, from, to),
+    }
+}
+
+/// A block covers the same columns on every line, up to and including the
+/// column of its right edge.
+fn block_operator(
+    context: &mut Context<'_>,
+    document: &Document,
+    operator: Operator,
+    from: usize,
+    to: usize,
+) {
+    let (from_row, from_col) = document.translate_index_to_position(from);
+    let (to_row, to_col) = document.translate_index_to_position(to);
+    let (left, right) = (from_col.min(to_col), from_col.max(to_col) + 1);
+    let ranges: Vec<(usize, usize)> = (from_row..=to_row)
+        .map(|row| {
+            (
+                document.translate_row_col_to_index(row, left),
+                document.translate_row_col_to_index(row, right),
+            )
+        })
+        .collect();
+    let text = document.text();
+    let parts: Vec<&str> = ranges.iter().map(|&(from, to)| &text[from..to]).collect();
+    store_register(
+        context,
+        ClipboardData::new(&parts.join("\n"), SelectionType::Block),
+    );
+
+    if operator != Operator::Yank {
+        context.buffer.save_to_undo_stack();
+        let mut remaining = text.to_string();
+        for &(from, to) in ranges.iter().rev() {
+            remaining.replace_range(from..to, "");
+        }
+        context.buffer.set_text(&remaining);
+    }
+    context
+        .buffer
+        .set_cursor_position(ranges.first().map_or(from, |(start, _)| *start));
+    if operator == Operator::Change {
+        context.state.input_mode = InputMode::Insert;
+        // Rows too short to reach the block don't get the text.
+        let lines = document.lines();
+        let rows = (from_row + 1..=to_row)
+            .filter(|row| {
+                lines
+                    .get(*row)
+                    .is_some_and(|line| line.chars().count() > left)
+            })
+            .collect();
+        context.state.block_insert = Some(BlockInsert {
+            start: context.buffer.cursor_position(),
+            column: left,
+            rows,
+        });
+    }
+    context.finish(operator != Operator::Yank);
+}
+
+/// Insert the text typed on the first row of a block change on the other
+/// rows of the block, as Vim does. Nothing is copied when typing left the
+/// first row.
+fn finish_block_insert(buffer: &mut Buffer, block_insert: &BlockInsert) {
+    let document = buffer.document();
+    let cursor_position = document.cursor_position();
+    let Some(inserted) = document.text().get(block_insert.start..cursor_position) else {
+        return;
+    };
+    if inserted.is_empty() || inserted.contains('\n') {
+        return;
+    }
+    let mut text = document.text().to_string();
+    // From the bottom up, so that earlier positions stay valid.
+    for &row in block_insert.rows.iter().rev() {
+        let index = document.translate_row_col_to_index(row, block_insert.column);
+        text.insert_str(index, inserted);
+    }
+    buffer.set_text(&text);
+    buffer.set_cursor_position(cursor_position);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::key_bindings::test_editor;
+
+    type Editor = test_editor::Editor<Rc<RefCell<ViState>>>;
+
+    /// An editor in navigation mode.
+    fn new_editor(text: &str, cursor_position: usize) -> Editor {
+        let state = Rc::new(RefCell::new(ViState::new()));
+        state.borrow_mut().input_mode = InputMode::Navigation;
+        Editor::new(text, cursor_position, load_vi_bindings(&state), state)
+    }
+
+    impl Editor {
+        fn mode(&self) -> InputMode {
+            self.state.borrow().input_mode()
+        }
+    }
+
+    #[test]
+    fn test_mode_switches_and_filters() {
+        let mut editor = new_editor("", 0);
+        let insert = vi_insert_mode(&editor.state);
+        let navigation = vi_navigation_mode(&editor.state);
+        assert!(navigation.eval() && !insert.eval());
+
+        editor.type_text("ihello").press(&[Keys::Escape]);
+        assert_eq!(editor.state(), ("hello".to_string(), 4));
+        assert!(navigation.eval());
+
+        editor.type_text("A!");
+        assert!(insert.eval());
+        editor.press(&[Keys::Escape]).type_text("0R");
+        assert!(vi_replace_mode(&editor.state).eval());
+        editor.type_text("J").press(&[Keys::Escape]);
+        assert_eq!(editor.state(), ("Jello!".to_string(), 0));
+
+        editor.type_text("v");
+        assert_eq!(editor.mode(), InputMode::Visual(SelectionType::Characters));
+        assert!(vi_selection_mode(&editor.state).eval());
+        editor.type_text("V");
+        assert_eq!(editor.mode(), InputMode::Visual(SelectionType::Lines));
+        editor.type_text("V");
+        assert_eq!(editor.mode(), InputMode::Navigation);
+        assert!(editor.buffer.borrow().selection_state().is_none());
+    }
+
+    #[test]
+    fn test_motions_and_counts() {
+        let mut editor = new_editor("foo bar-baz qux\nnext", 0);
+        editor.type_text("w");
+        assert_eq!(editor.state().1, 4);
+        editor.type_text("2w");
+        assert_eq!(editor.state().1, 8);
+        editor.type_text("b");
+        assert_eq!(editor.state().1, 7);
+        editor.type_text("e");
+        assert_eq!(editor.state().1, 10);
+        editor.type_text("$");
+        assert_eq!(editor.state().1, 14);
+        editor.type_text("0fb");
+        assert_eq!(editor.state().1, 4);
+        editor.type_text("2fa");
+        assert_eq!(editor.state().1, 9);
+        editor.type_text("j");
+        assert_eq!(editor.state().1, 19);
+        editor.type_text("k");
+        assert_eq!(editor.state().1, 9);
+    }
+
+    #[test]
+    fn test_operators_with_motions() {
+        let mut editor = new_editor("one two three four", 0);
+        editor.type_text("dw");
+        assert_eq!(editor.state(), ("two three four".to_string(), 0));
+        editor.type_text("d2e");
+        assert_eq!(editor.state(), (" four".to_string(), 0));
+        // On blanks, `cw` changes just the blanks.
+        editor.type_text("cw-").press(&[Keys::Escape]);
+        assert_eq!(editor.state(), ("-four".to_string(), 0));
+        editor.type_text("lcwsix").press(&[Keys::Escape]);
+        assert_eq!(editor.state(), ("-six".to_string(), 3));
+        editor.type_text("0ye$p");
+        assert_eq!(editor.state(), ("-six-six".to_string(), 7));
+        editor.type_text("0wd$");
+        assert_eq!(editor.state(), ("-".to_string(), 0));
+    }
+
+    #[test]
+    fn test_whole_lines() {
+        let mut editor = new_editor("a\n  b\nc\nd", 0);
+        editor.type_text("2dd");
+        assert_eq!(editor.state(), ("c\nd".to_string(), 0));
+        editor.type_text("jdd");
+        assert_eq!(editor.state(), ("c".to_string(), 0));
+        editor.type_text("P");
+        assert_eq!(editor.state(), ("d\nc".to_string(), 0));
+        editor.type_text("yyjp");
+        assert_eq!(editor.state(), ("d\nc\nd".to_string(), 4));
+        editor.type_text("ccx").press(&[Keys::Escape]);
+        assert_eq!(editor.state(), ("d\nc\nx".to_string(), 4));
+    }
+
+    #[test]
+    fn test_text_objects() {
+        let mut editor = new_editor("say \"hello world\" now", 8);
+        editor.type_text("diw");
+        assert_eq!(editor.state(), ("say \" world\" now".to_string(), 5));
+        editor.type_text("ci\"hi").press(&[Keys::Escape]);
+        assert_eq!(editor.state(), ("say \"hi\" now".to_string(), 6));
+        editor.type_text("0da\"");
+        assert_eq!(editor.state(), ("say  now".to_string(), 4));
+        editor.type_text("bdaw");
+        assert_eq!(editor.state(), ("now".to_string(), 0));
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut editor = new_editor("alpha beta", 0);
+        editor.type_text("\"ayw").type_text("wyw");
+        assert_eq!(
+            editor
+                .state
+                .borrow()
+                .register('a')
+                .map(|data| data.text.as_str()),
+            Some("alpha ")
+        );
+        editor.type_text("$\"ap");
+        assert_eq!(editor.state().0, "alpha betaalpha ");
+        editor.type_text("0P");
+        assert_eq!(editor.state().0, "betaalpha betaalpha ");
+    }
+
+    #[test]
+    fn test_repeat_last_change() {
+        let mut editor = new_editor("a b c d e f", 0);
+        editor.type_text("dw.");
+        assert_eq!(editor.state(), ("c d e f".to_string(), 0));
+        editor.type_text("2.");
+        assert_eq!(editor.state(), ("e f".to_string(), 0));
+
+        let mut editor = new_editor("x y z", 0);
+        editor.type_text("cwQ").press(&[Keys::Escape]);
+        assert_eq!(editor.state(), ("Q y z".to_string(), 0));
+        editor.type_text("w.");
+        assert_eq!(editor.state(), ("Q Q z".to_string(), 2));
+        editor.type_text("w.");
+        assert_eq!(editor.state(), ("Q Q Q".to_string(), 4));
+        editor.type_text("u");
+        assert_eq!(editor.state().0, "Q Q z");
+    }
+
+    #[test]
+    fn test_replace_and_undo() {
+        let mut editor = new_editor("abcd", 0);
+        editor.type_text("3rx");
+        assert_eq!(editor.state(), ("xxxd".to_string(), 2));
+        editor.type_text("9ry");
+        assert_eq!(editor.state().0, "xxxd");
+        editor.type_text("x");
+        assert_eq!(editor.state(), ("xxd".to_string(), 2));
+        editor.type_text("2u");
+        assert_eq!(editor.state().0, "abcd");
+        editor.press(&[Keys::ControlR]);
+        assert_eq!(editor.state().0, "xxxd");
+    }
+
+    #[test]
+    fn test_huge_counts() {
+        let mut editor = new_editor("abcd", 0);
+        editor.type_text("x").type_text("99999999999u");
+        assert_eq!(editor.state().0, "abcd");
+        editor.press(&[
+            Keys::Character('9'),
+            Keys::Character('9'),
+            Keys::Character('9'),
+            Keys::Character('9'),
+            Keys::Character('9'),
+            Keys::Character('9'),
+            Keys::Character('9'),
+            Keys::ControlR,
+        ]);
+        assert_eq!(editor.state().0, "bcd");
+        editor.type_text("99999999999.");
+        assert_eq!(editor.state().0, "");
+
+        let mut editor = new_editor("ab", 0);
+        editor.type_text("yl99999999999p");
+        assert_eq!(editor.state().0.len(), 2 + MAX_ARG_COUNT - 1);
+        editor.type_text("99999999999rz");
+        assert_eq!(editor.state().0.len(), 2 + MAX_ARG_COUNT - 1);
+    }
+
+    #[test]
+    fn test_visual_modes() {
+        let mut editor = new_editor("one two\nthree four", 0);
+        editor.type_text("ved");
+        assert_eq!(editor.state(), (" two\nthree four".to_string(), 0));
+        assert_eq!(editor.mode(), InputMode::Navigation);
+
+        editor.type_text("Vjy");
+        assert_eq!(
+            editor.app.clipboard_mut().get_data().text,
+            " two\nthree four"
+        );
+        assert_eq!(editor.state().1, 0);
+
+        let mut editor = new_editor("abcd\nefgh\nijkl", 1);
+        editor.press(&[Keys::ControlV]).type_text("jld");
+        assert_eq!(editor.state(), ("ad\neh\nijkl".to_string(), 1));
+        editor.type_text("P");
+        assert_eq!(editor.state().0, "abcd\nefgh\nijkl");
+    }
+
+    #[test]
+    fn test_block_change_types_on_every_row() {
+        let mut editor = new_editor("abcd\nefgh\ni\nmnop", 1);
+        editor.press(&[Keys::ControlV]).type_text("3jlcXY");
+        assert_eq!(editor.state().0, "aXYd\neh\ni\nmp");
+        editor.press(&[Keys::Escape]);
+        assert_eq!(editor.state(), ("aXYd\neXYh\ni\nmXYp".to_string(), 2));
+        assert_eq!(editor.mode(), InputMode::Navigation);
+        editor.type_text("u");
+        assert_eq!(editor.state().0, "abcd\nefgh\ni\nmnop");
+    }
+}
@@ -1,4 +1,10 @@
-use std::{collections::HashMap, sync::LazyLock};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    str::FromStr,
+    sync::LazyLock,
+};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Keys {
@@ -319,164 +325,164 @@ impl Keys {
     }
 }
 
-pub static ALL_KEYS: LazyLock<Vec<&'static str>> = LazyLock::new(|| {
-    // Yikes
-    let v: Vec<&'static str> = vec![
-        Keys::Escape.as_str(),
-        Keys::ShiftEscape.as_str(),
-        Keys::ControlAt.as_str(),
-        Keys::ControlA.as_str(),
-        Keys::ControlB.as_str(),
-        Keys::ControlC.as_str(),
-        Keys::ControlD.as_str(),
-        Keys::ControlE.as_str(),
-        Keys::ControlF.as_str(),
-        Keys::ControlG.as_str(),
-        Keys::ControlH.as_str(),
-        Keys::ControlI.as_str(),
-        Keys::ControlJ.as_str(),
-        Keys::ControlK.as_str(),
-        Keys::ControlL.as_str(),
-        Keys::ControlM.as_str(),
-        Keys::ControlN.as_str(),
-        Keys::ControlO.as_str(),
-        Keys::ControlP.as_str(),
-        Keys::ControlQ.as_str(),
-        Keys::ControlR.as_str(),
-        Keys::ControlS.as_str(),
-        Keys::ControlT.as_str(),
-        Keys::ControlU.as_str(),
-        Keys::ControlV.as_str(),
-        Keys::ControlW.as_str(),
-        Keys::ControlX.as_str(),
-        Keys::ControlY.as_str(),
-        Keys::ControlZ.as_str(),
-        Keys::ControlOpenBracket.as_str(),
-        Keys::ControlBackslash.as_str(),
-        Keys::ControlCloseBracket.as_str(),
-        Keys::ControlCircumflex.as_str(),
-        Keys::ControlUnderscore.as_str(),
-        Keys::Control0.as_str(),
-        Keys::Control1.as_str(),
-        Keys::Control2.as_str(),
-        Keys::Control3.as_str(),
-        Keys::Control4.as_str(),
-        Keys::Control5.as_str(),
-        Keys::Control6.as_str(),
-        Keys::Control7.as_str(),
-        Keys::Control8.as_str(),
-        Keys::Control9.as_str(),
-        Keys::ControlShift0.as_str(),
-        Keys::ControlShift1.as_str(),
-        Keys::ControlShift2.as_str(),
-        Keys::ControlShift3.as_str(),
-        Keys::ControlShift4.as_str(),
-        Keys::ControlShift5.as_str(),
-        Keys::ControlShift6.as_str(),
-        Keys::ControlShift7.as_str(),
-        Keys::ControlShift8.as_str(),
-        Keys::ControlShift9.as_str(),
-        Keys::Left.as_str(),
-        Keys::Right.as_str(),
-        Keys::Up.as_str(),
-        Keys::Down.as_str(),
-        Keys::Home.as_str(),
-        Keys::End.as_str(),
-        Keys::Insert.as_str(),
-        Keys::Delete.as_str(),
-        Keys::PageUp.as_str(),
-        Keys::PageDown.as_str(),
-        Keys::ControlLeft.as_str(),
-        Keys::ControlRight.as_str(),
-        Keys::ControlUp.as_str(),
-        Keys::ControlDown.as_str(),
-        Keys::ControlHome.as_str(),
-        Keys::ControlEnd.as_str(),
-        Keys::ControlInsert.as_str(),
-        Keys::ControlDelete.as_str(),
-        Keys::ControlPageUp.as_str(),
-        Keys::ControlPageDown.as_str(),
-        Keys::ShiftLeft.as_str(),
-        Keys::ShiftRight.as_str(),
-        Keys::ShiftUp.as_str(),
-        Keys::ShiftDown.as_str(),
-        Keys::ShiftHome.as_str(),
-        Keys::ShiftEnd.as_str(),
-        Keys::ShiftInsert.as_str(),
-        Keys::ShiftDelete.as_str(),
-        Keys::ShiftPageUp.as_str(),
-        Keys::ShiftPageDown.as_str(),
-        Keys::ControlShiftLeft.as_str(),
-        Keys::ControlShiftRight.as_str(),
-        Keys::ControlShiftUp.as_str(),
-        Keys::ControlShiftDown.as_str(),
-        Keys::ControlShiftHome.as_str(),
-        Keys::ControlShiftEnd.as_str(),
-        Keys::ControlShiftInsert.as_str(),
-        Keys::ControlShiftDelete.as_str(),
-        Keys::ControlShiftPageUp.as_str(),
-        Keys::ControlShiftPageDown.as_str(),
-        Keys::BackTab.as_str(),
-        Keys::F1.as_str(),
-        Keys::F2.as_str(),
-        Keys::F3.as_str(),
-        Keys::F4.as_str(),
-        Keys::F5.as_str(),
-        Keys::F6.as_str(),
-        Keys::F7.as_str(),
-        Keys::F8.as_str(),
-        Keys::F9.as_str(),
-        Keys::F10.as_str(),
-        Keys::F11.as_str(),
-        Keys::F12.as_str(),
-        Keys::F13.as_str(),
-        Keys::F14.as_str(),
-        Keys::F15.as_str(),
-        Keys::F16.as_str(),
-        Keys::F17.as_str(),
-        Keys::F18.as_str(),
-        Keys::F19.as_str(),
-        Keys::F20.as_str(),
-        Keys::F21.as_str(),
-        Keys::F22.as_str(),
-        Keys::F23.as_str(),
-        Keys::F24.as_str(),
-        Keys::ControlF1.as_str(),
-        Keys::ControlF2.as_str(),
-        Keys::ControlF3.as_str(),
-        Keys::ControlF4.as_str(),
-        Keys::ControlF5.as_str(),
-        Keys::ControlF6.as_str(),
-        Keys::ControlF7.as_str(),
-        Keys::ControlF8.as_str(),
-        Keys::ControlF9.as_str(),
-        Keys::ControlF10.as_str(),
-        Keys::ControlF11.as_str(),
-        Keys::ControlF12.as_str(),
-        Keys::ControlF13.as_str(),
-        Keys::ControlF14.as_str(),
-        Keys::ControlF15.as_str(),
-        Keys::ControlF16.as_str(),
-        Keys::ControlF17.as_str(),
-        Keys::ControlF18.as_str(),
-        Keys::ControlF19.as_str(),
-        Keys::ControlF20.as_str(),
-        Keys::ControlF21.as_str(),
-        Keys::ControlF22.as_str(),
-        Keys::ControlF23.as_str(),
-        Keys::ControlF24.as_str(),
-        Keys::Any.as_str(),
-        Keys::ScrollUp.as_str(),
-        Keys::ScrollDown.as_str(),
-        Keys::CPRResponse.as_str(),
-        Keys::Vt100MouseEvent.as_str(),
-        Keys::WindowsMouseEvent.as_str(),
-        Keys::BracketedPaste.as_str(),
-        Keys::SigInt.as_str(),
-        Keys::Ignore.as_str(),
-    ];
-    v
-});
+/// Every key except [`Keys::Character`].
+const NAMED_KEYS: [Keys; 152] = [
+    Keys::Escape,
+    Keys::ShiftEscape,
+    Keys::ControlAt,
+    Keys::ControlA,
+    Keys::ControlB,
+    Keys::ControlC,
+    Keys::ControlD,
+    Keys::ControlE,
+    Keys::ControlF,
+    Keys::ControlG,
+    Keys::ControlH,
+    Keys::ControlI,
+    Keys::ControlJ,
+    Keys::ControlK,
+    Keys::ControlL,
+    Keys::ControlM,
+    Keys::ControlN,
+    Keys::ControlO,
+    Keys::ControlP,
+    Keys::ControlQ,
+    Keys::ControlR,
+    Keys::ControlS,
+    Keys::ControlT,
+    Keys::ControlU,
+    Keys::ControlV,
+    Keys::ControlW,
+    Keys::ControlX,
+    Keys::ControlY,
+    Keys::ControlZ,
+    Keys::ControlOpenBracket,
+    Keys::ControlBackslash,
+    Keys::ControlCloseBracket,
+    Keys::ControlCircumflex,
+    Keys::ControlUnderscore,
+    Keys::Control0,
+    Keys::Control1,
+    Keys::Control2,
+    Keys::Control3,
+    Keys::Control4,
+    Keys::Control5,
+    Keys::Control6,
+    Keys::Control7,
+    Keys::Control8,
+    Keys::Control9,
+    Keys::ControlShift0,
+    Keys::ControlShift1,
+    Keys::ControlShift2,
+    Keys::ControlShift3,
+    Keys::ControlShift4,
+    Keys::ControlShift5,
+    Keys::ControlShift6,
+    Keys::ControlShift7,
+    Keys::ControlShift8,
+    Keys::ControlShift9,
+    Keys::Left,
+    Keys::Right,
+    Keys::Up,
+    Keys::Down,
+    Keys::Home,
+    Keys::End,
+    Keys::Insert,
+    Keys::Delete,
+    Keys::PageUp,
+    Keys::PageDown,
+    Keys::ControlLeft,
+    Keys::ControlRight,
+    Keys::ControlUp,
+    Keys::ControlDown,
+    Keys::ControlHome,
+    Keys::ControlEnd,
+    Keys::ControlInsert,
+    Keys::ControlDelete,
+    Keys::ControlPageUp,
+    Keys::ControlPageDown,
+    Keys::ShiftLeft,
+    Keys::ShiftRight,
+    Keys::ShiftUp,
+    Keys::ShiftDown,
+    Keys::ShiftHome,
+    Keys::ShiftEnd,
+    Keys::ShiftInsert,
+    Keys::ShiftDelete,
+    Keys::ShiftPageUp,
+    Keys::ShiftPageDown,
+    Keys::ControlShiftLeft,
+    Keys::ControlShiftRight,
+    Keys::ControlShiftUp,
+    Keys::ControlShiftDown,
+    Keys::ControlShiftHome,
+    Keys::ControlShiftEnd,
+    Keys::ControlShiftInsert,
+    Keys::ControlShiftDelete,
+    Keys::ControlShiftPageUp,
+    Keys::ControlShiftPageDown,
+    Keys::BackTab,
+    Keys::F1,
+    Keys::F2,
+    Keys::F3,
+    Keys::F4,
+    Keys::F5,
+    Keys::F6,
+    Keys::F7,
+    Keys::F8,
+    Keys::F9,
+    Keys::F10,
+    Keys::F11,
+    Keys::F12,
+    Keys::F13,
+    Keys::F14,
+    Keys::F15,
+    Keys::F16,
+    Keys::F17,
+    Keys::F18,
+    Keys::F19,
+    Keys::F20,
+    Keys::F21,
+    Keys::F22,
+    Keys::F23,
+    Keys::F24,
+    Keys::ControlF1,
+    Keys::ControlF2,
+    Keys::ControlF3,
+    Keys::ControlF4,
+    Keys::ControlF5,
+    Keys::ControlF6,
+    Keys::ControlF7,
+    Keys::ControlF8,
+    Keys::ControlF9,
+    Keys::ControlF10,
+    Keys::ControlF11,
+    Keys::ControlF12,
+    Keys::ControlF13,
+    Keys::ControlF14,
+    Keys::ControlF15,
+    Keys::ControlF16,
+    Keys::ControlF17,
+    Keys::ControlF18,
+    Keys::ControlF19,
+    Keys::ControlF20,
+    Keys::ControlF21,
+    Keys::ControlF22,
+    Keys::ControlF23,
+    Keys::ControlF24,
+    Keys::Any,
+    Keys::ScrollUp,
+    Keys::ScrollDown,
+    Keys::CPRResponse,
+    Keys::Vt100MouseEvent,
+    Keys::WindowsMouseEvent,
+    Keys::BracketedPaste,
+    Keys::SigInt,
+    Keys::Ignore,
+];
+
+pub static ALL_KEYS: LazyLock<Vec<&'static str>> =
+    LazyLock::new(|| NAMED_KEYS.iter().map(|key| key.as_str()).collect());
 
 pub static KEY_ALIASES: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|| {
     let mut m = HashMap::new();
@@ -490,3 +496,127 @@ pub static KEY_ALIASES: LazyLock<HashMap<&'static str, &'static str>> = LazyLock
     m.insert("s-c-end", "c-s-end");
     m
 });
+
+static KEYS_BY_NAME: LazyLock<HashMap<&'static str, Keys>> =
+    LazyLock::new(|| NAMED_KEYS.iter().map(|key| (key.as_str(), *key)).collect());
+
+/// A key name that [`Keys::from_str`] doesn't know.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidKeyError(pub String);
+
+impl Error for InvalidKeyError {}
+impl Display for InvalidKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid key: {:?}", self.0)
+    }
+}
+
+/// Parses the notation of [`Keys::as_str`], e.g. `c-a`, `s-tab`, `escape`,
+/// `f12` or `<any>`, the names in [`KEY_ALIASES`], and single characters.
+/// `space` is the space character.
+impl FromStr for Keys {
+    type Err = InvalidKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = KEY_ALIASES.get(s).copied().unwrap_or(s);
+        if name == "space" {
+            return Ok(Keys::Character(' '));
+        }
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(Keys::Character(c));
+        }
+        KEYS_BY_NAME
+            .get(name)
+            .copied()
+            .ok_or_else(|| InvalidKeyError(s.to_string()))
+    }
+}
+
+/// Writes the notation that [`Keys::from_str`] reads back.
+impl Display for Keys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Keys::Character(' ') => f.write_str("space"),
+            Keys::Character(c) => write!(f, "{c}"),
+            key => f.write_str(key.as_str()),
+        }
+    }
+}
+
+/// Parse a key sequence of keys separated by whitespace, e.g. `c-x c-e`
+/// or `escape f`.
+///
+/// # Errors
+///
+/// Returns the first key that is not valid, or an empty one if there are
+/// no keys at all.
+pub fn parse_key_sequence(s: &str) -> Result<Vec<Keys>, InvalidKeyError> {
+    let keys = s
+        .split_whitespace()
+        .map(Keys::from_str)
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err(InvalidKeyError(s.to_string()));
+    }
+    Ok(keys)
+}
+
+/// The notation of a key sequence that [`parse_key_sequence`] reads back.
+#[must_use]
+pub fn format_key_sequence(keys: &[Keys]) -> String {
+    keys.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!("c-a".parse(), Ok(Keys::ControlA));
+        assert_eq!("s-tab".parse(), Ok(Keys::BackTab));
+        assert_eq!("escape".parse(), Ok(Keys::Escape));
+        assert_eq!("f12".parse(), Ok(Keys::F12));
+        assert_eq!("<any>".parse(), Ok(Keys::Any));
+        assert_eq!("enter".parse(), Ok(Keys::ControlM));
+        assert_eq!("space".parse(), Ok(Keys::Character(' ')));
+        assert_eq!("x".parse(), Ok(Keys::Character('x')));
+        assert_eq!(
+            "c-nothing".parse::<Keys>(),
+            Err(InvalidKeyError("c-nothing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let keys = NAMED_KEYS
+            .iter()
+            .copied()
+            .chain([Keys::Character('x'), Keys::Character(' ')]);
+        for key in keys {
+            assert_eq!(key.to_string().parse(), Ok(key), "{key}");
+        }
+    }
+
+    #[test]
+    fn test_key_sequence() {
+        assert_eq!(
+            parse_key_sequence("c-x  c-e"),
+            Ok(vec![Keys::ControlX, Keys::ControlE])
+        );
+        assert_eq!(
+            parse_key_sequence("escape f"),
+            Ok(vec![Keys::Escape, Keys::Character('f')])
+        );
+        assert_eq!(
+            format_key_sequence(&[Keys::Escape, Keys::Character(' ')]),
+            "escape space"
+        );
+        assert!(parse_key_sequence("c-x c-?").is_err());
+        assert!(parse_key_sequence(" ").is_err());
+    }
+}
@@ -1 +1,130 @@
-pub struct Layout;
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    buffer::Buffer,
+    layout::{
+        containers::{Container, HSplit, Window},
+        dimension::Dimension,
+    },
+    Screen, WindowId, WritePosition,
+};
+
+/// The root of the container tree, plus the window that has the focus.
+pub struct Layout {
+    container: Box<dyn Container>,
+    current_window: Option<WindowId>,
+}
+
+impl Layout {
+    /// Wrap `container`, focusing the first window with a focusable control.
+    #[must_use]
+    pub fn new(container: Box<dyn Container>) -> Self {
+        let mut layout = Self {
+            container,
+            current_window: None,
+        };
+        layout.current_window = layout
+            .find_all_windows()
+            .into_iter()
+            .find(|window| window.content().is_focusable())
+            .map(Window::id);
+        layout
+    }
+
+    #[must_use]
+    pub fn container(&self) -> &dyn Container {
+        self.container.as_ref()
+    }
+
+    #[must_use]
+    pub fn current_window(&self) -> Option<WindowId> {
+        self.current_window
+    }
+
+    pub fn focus(&mut self, window: WindowId) {
+        self.current_window = Some(window);
+    }
+
+    /// The buffer shown in the focused window, if that window shows one.
+    #[must_use]
+    pub fn current_buffer(&self) -> Option<Rc<RefCell<Buffer>>> {
+        let current_window = self.current_window?;
+        self.find_all_windows()
+            .into_iter()
+            .find(|window| window.id() == current_window)
+            .and_then(|window| window.content().buffer())
+    }
+
+    /// All windows of the tree, in depth-first order.
+    #[must_use]
+    pub fn find_all_windows(&self) -> Vec<&Window> {
+        fn walk<'a>(container: &'a dyn Container, windows: &mut Vec<&'a Window>) {
+            if let Some(window) = container.as_window() {
+                windows.push(window);
+            }
+            for child in container.children() {
+                walk(child, windows);
+            }
+        }
+
+        let mut windows = Vec::new();
+        walk(self.container.as_ref(), &mut windows);
+        windows
+    }
+
+    #[must_use]
+    pub fn preferred_height(&self, width: usize, max_available_height: usize) -> Dimension {
+        self.container.preferred_height(width, max_available_height)
+    }
+
+    /// Draw the whole tree, floats included.
+    pub fn write_to_screen(&self, screen: &mut Screen, write_position: &WritePosition) {
+        self.container.write_to_screen(screen, write_position, "");
+        screen.draw_all_floats();
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::new(Box::new(HSplit::new(Vec::new())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::layout::{BufferControl, FormattedTextControl, VSplit};
+
+    #[test]
+    fn test_layout_focuses_first_focusable_window() {
+        let input = Window::new(FormattedTextControl::from_text("> ").with_focusable(true));
+        let input_id = input.id();
+        let layout = Layout::new(Box::new(HSplit::new(vec![
+            Box::new(Window::new(FormattedTextControl::from_text("title"))),
+            Box::new(VSplit::new(vec![Box::new(input)])),
+        ])));
+
+        assert_eq!(layout.find_all_windows().len(), 2);
+        assert!(layout.current_buffer().is_none());
+        assert_eq!(layout.current_window(), Some(input_id));
+        assert_eq!(layout.preferred_height(80, 24).preferred(), 2);
+    }
+
+    #[test]
+    fn test_current_buffer_follows_focus() {
+        let buffer = Rc::new(RefCell::new(Buffer::new()));
+        let layout = Layout::new(Box::new(HSplit::new(vec![Box::new(Window::new(
+            BufferControl::new(Rc::clone(&buffer)),
+        ))])));
+        let current = layout.current_buffer().expect("focused buffer");
+        assert!(Rc::ptr_eq(&current, &buffer));
+    }
+
+    #[test]
+    fn test_default_layout_is_empty() {
+        let layout = Layout::default();
+        assert!(layout.find_all_windows().is_empty());
+        assert_eq!(layout.current_window(), None);
+        assert!(layout.preferred_height(80, 24).is_zero());
+    }
+}
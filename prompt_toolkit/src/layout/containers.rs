@@ -0,0 +1,602 @@
+use std::rc::Rc;
+
+use crate::{
+    layout::{
+        controls::UIControl,
+        dimension::{distribute, max_layout_dimensions, sum_layout_dimensions, Dimension},
+    },
+    Char, Point, Screen, WindowId, WritePosition,
+};
+
+/// A node of the layout tree that can draw itself into a region of a [`Screen`].
+pub trait Container {
+    fn preferred_width(&self, max_available_width: usize) -> Dimension;
+    fn preferred_height(&self, width: usize, max_available_height: usize) -> Dimension;
+
+    fn write_to_screen(
+        &self,
+        screen: &mut Screen,
+        write_position: &WritePosition,
+        parent_style: &str,
+    );
+
+    fn children(&self) -> Vec<&dyn Container> {
+        Vec::new()
+    }
+
+    fn as_window(&self) -> Option<&Window> {
+        None
+    }
+}
+
+fn join_styles(parent_style: &str, style: &str) -> String {
+    format!("{parent_style} {style}").trim().to_string()
+}
+
+/// Stacks its children vertically.
+pub struct HSplit {
+    children: Vec<Box<dyn Container>>,
+    style: String,
+}
+
+impl HSplit {
+    #[must_use]
+    pub fn new(children: Vec<Box<dyn Container>>) -> Self {
+        Self {
+            children,
+            style: String::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_style(mut self, style: &str) -> Self {
+        self.style = style.to_string();
+        self
+    }
+
+    fn heights(&self, width: usize, height: usize) -> Vec<usize> {
+        let dimensions: Vec<Dimension> = self
+            .children
+            .iter()
+            .map(|child| child.preferred_height(width, height))
+            .collect();
+        distribute(&dimensions, height)
+    }
+}
+
+impl Container for HSplit {
+    fn preferred_width(&self, max_available_width: usize) -> Dimension {
+        let dimensions: Vec<Dimension> = self
+            .children
+            .iter()
+            .map(|child| child.preferred_width(max_available_width))
+            .collect();
+        max_layout_dimensions(&dimensions)
+    }
+
+    fn preferred_height(&self, width: usize, max_available_height: usize) -> Dimension {
+        let dimensions: Vec<Dimension> = self
+            .children
+            .iter()
+            .map(|child| child.preferred_height(width, max_available_height))
+            .collect();
+        sum_layout_dimensions(&dimensions)
+    }
+
+    fn write_to_screen(
+        &self,
+        screen: &mut Screen,
+        write_position: &WritePosition,
+        parent_style: &str,
+    ) {
+        let style = join_styles(parent_style, &self.style);
+        let heights = self.heights(write_position.width, write_position.height);
+
+        let mut ypos = write_position.ypos;
+        for (child, height) in self.children.iter().zip(heights) {
+            let child_position =
+                WritePosition::new(write_position.xpos, ypos, write_position.width, height);
+            child.write_to_screen(screen, &child_position, &style);
+            ypos += height;
+        }
+
+        let used = ypos - write_position.ypos;
+        let unused = WritePosition::new(
+            write_position.xpos,
+            ypos,
+            write_position.width,
+            write_position.height.saturating_sub(used),
+        );
+        screen.fill_area(&unused, &style, false);
+    }
+
+    fn children(&self) -> Vec<&dyn Container> {
+        self.children.iter().map(AsRef::as_ref).collect()
+    }
+}
+
+/// Lays out its children side by side.
+pub struct VSplit {
+    children: Vec<Box<dyn Container>>,
+    style: String,
+}
+
+impl VSplit {
+    #[must_use]
+    pub fn new(children: Vec<Box<dyn Container>>) -> Self {
+        Self {
+            children,
+            style: String::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_style(mut self, style: &str) -> Self {
+        self.style = style.to_string();
+        self
+    }
+
+    fn widths(&self, width: usize) -> Vec<usize> {
+        let dimensions: Vec<Dimension> = self
+            .children
+            .iter()
+            .map(|child| child.preferred_width(width))
+            .collect();
+        distribute(&dimensions, width)
+    }
+}
+
+impl Container for VSplit {
+    fn preferred_width(&self, max_available_width: usize) -> Dimension {
+        let dimensions: Vec<Dimension> = self
+            .children
+            .iter()
+            .map(|child| child.preferred_width(max_available_width))
+            .collect();
+        sum_layout_dimensions(&dimensions)
+    }
+
+    fn preferred_height(&self, width: usize, max_available_height: usize) -> Dimension {
+        let dimensions: Vec<Dimension> = self
+            .children
+            .iter()
+            .zip(self.widths(width))
+            .map(|(child, width)| child.preferred_height(width, max_available_height))
+            .collect();
+        max_layout_dimensions(&dimensions)
+    }
+
+    fn write_to_screen(
+        &self,
+        screen: &mut Screen,
+        write_position: &WritePosition,
+        parent_style: &str,
+    ) {
+        let style = join_styles(parent_style, &self.style);
+        let widths = self.widths(write_position.width);
+
+        let mut xpos = write_position.xpos;
+        for (child, width) in self.children.iter().zip(widths) {
+            let child_position =
+                WritePosition::new(xpos, write_position.ypos, width, write_position.height);
+            child.write_to_screen(screen, &child_position, &style);
+            xpos += width;
+        }
+
+        let used = xpos - write_position.xpos;
+        let unused = WritePosition::new(
+            xpos,
+            write_position.ypos,
+            write_position.width.saturating_sub(used),
+            write_position.height,
+        );
+        screen.fill_area(&unused, &style, false);
+    }
+
+    fn children(&self) -> Vec<&dyn Container> {
+        self.children.iter().map(AsRef::as_ref).collect()
+    }
+}
+
+/// Shows a [`UIControl`] in a rectangular region.
+pub struct Window {
+    content: Box<dyn UIControl>,
+    width: Dimension,
+    height: Dimension,
+    style: String,
+    id: WindowId,
+}
+
+impl Window {
+    pub fn new(content: impl UIControl + 'static) -> Self {
+        Self {
+            content: Box::new(content),
+            width: Dimension::default(),
+            height: Dimension::default(),
+            style: String::new(),
+            id: WindowId::next(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_width(mut self, width: impl Into<Dimension>) -> Self {
+        self.width = width.into();
+        self
+    }
+
+    #[must_use]
+    pub fn with_height(mut self, height: impl Into<Dimension>) -> Self {
+        self.height = height.into();
+        self
+    }
+
+    /// Set the weight of both the width and the height.
+    #[must_use]
+    pub fn with_weight(mut self, weight: usize) -> Self {
+        self.width = self.width.with_weight(weight);
+        self.height = self.height.with_weight(weight);
+        self
+    }
+
+    #[must_use]
+    pub fn with_style(mut self, style: &str) -> Self {
+        self.style = style.to_string();
+        self
+    }
+
+    #[must_use]
+    pub fn id(&self) -> WindowId {
+        self.id
+    }
+
+    #[must_use]
+    pub fn content(&self) -> &dyn UIControl {
+        self.content.as_ref()
+    }
+}
+
+impl Container for Window {
+    fn preferred_width(&self, max_available_width: usize) -> Dimension {
+        merge_dimension(self.width, || {
+            self.content.preferred_width(max_available_width)
+        })
+    }
+
+    fn preferred_height(&self, width: usize, max_available_height: usize) -> Dimension {
+        merge_dimension(self.height, || {
+            self.content.preferred_height(width, max_available_height)
+        })
+    }
+
+    fn write_to_screen(
+        &self,
+        screen: &mut Screen,
+        write_position: &WritePosition,
+        parent_style: &str,
+    ) {
+        screen.set_visible_window_write_position(self.id, *write_position);
+        let content = self
+            .content
+            .create_content(write_position.width, write_position.height);
+
+        for (y, line) in content.lines.iter().take(write_position.height).enumerate() {
+            let row = screen
+                .data_buffer
+                .entry(write_position.ypos + y)
+                .or_default();
+            let mut x = 0;
+            'line: for (style, text) in line {
+                for c in text.chars() {
+                    let ch = Char::new(c, style);
+                    let width = ch.width;
+                    if width == 0 {
+                        continue;
+                    }
+                    if x + width > write_position.width {
+                        break 'line;
+                    }
+                    row.insert(write_position.xpos + x, ch);
+                    x += width;
+                }
+            }
+        }
+
+        if let Some(cursor) = content.cursor_position {
+            screen.set_cursor_position(
+                self.id,
+                Point::new(
+                    write_position.xpos + cursor.x,
+                    write_position.ypos + cursor.y,
+                ),
+            );
+        }
+
+        screen.fill_area(
+            write_position,
+            &join_styles(parent_style, &self.style),
+            false,
+        );
+    }
+
+    fn as_window(&self) -> Option<&Window> {
+        Some(self)
+    }
+}
+
+/// Fill in the preferred size of a window from its content, unless the
+/// window's own dimension already sets it.
+fn merge_dimension(
+    dimension: Dimension,
+    content_preferred: impl FnOnce() -> Option<usize>,
+) -> Dimension {
+    if dimension.preferred_specified() {
+        return dimension;
+    }
+    match content_preferred() {
+        Some(preferred) => dimension.with_preferred(preferred),
+        None => dimension,
+    }
+}
+
+/// A container drawn on top of a [`FloatContainer`]'s content.
+///
+/// Offsets are relative to the edges of the float container. Without
+/// offsets on an axis, the float is centered on it.
+pub struct Float {
+    content: Rc<dyn Container>,
+    top: Option<usize>,
+    right: Option<usize>,
+    bottom: Option<usize>,
+    left: Option<usize>,
+    width: Option<usize>,
+    height: Option<usize>,
+    z_index: i32,
+}
+
+impl Float {
+    pub fn new(content: impl Container + 'static) -> Self {
+        Self {
+            content: Rc::new(content),
+            top: None,
+            right: None,
+            bottom: None,
+            left: None,
+            width: None,
+            height: None,
+            z_index: 1,
+        }
+    }
+
+    #[must_use]
+    pub fn with_top(mut self, top: usize) -> Self {
+        self.top = Some(top);
+        self
+    }
+
+    #[must_use]
+    pub fn with_right(mut self, right: usize) -> Self {
+        self.right = Some(right);
+        self
+    }
+
+    #[must_use]
+    pub fn with_bottom(mut self, bottom: usize) -> Self {
+        self.bottom = Some(bottom);
+        self
+    }
+
+    #[must_use]
+    pub fn with_left(mut self, left: usize) -> Self {
+        self.left = Some(left);
+        self
+    }
+
+    #[must_use]
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    #[must_use]
+    pub fn with_height(mut self, height: usize) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    #[must_use]
+    pub fn with_z_index(mut self, z_index: i32) -> Self {
+        self.z_index = z_index;
+        self
+    }
+
+    fn write_position(&self, container: &WritePosition) -> WritePosition {
+        let (xpos, width) =
+            place_on_axis(container.width, self.left, self.right, self.width, || {
+                self.content.preferred_width(container.width).preferred()
+            });
+        let (ypos, height) =
+            place_on_axis(container.height, self.top, self.bottom, self.height, || {
+                self.content
+                    .preferred_height(width, container.height)
+                    .preferred()
+            });
+        WritePosition::new(container.xpos + xpos, container.ypos + ypos, width, height)
+    }
+}
+
+/// Offset and size of a float along one axis of length `total`.
+fn place_on_axis(
+    total: usize,
+    start: Option<usize>,
+    end: Option<usize>,
+    size: Option<usize>,
+    preferred: impl FnOnce() -> usize,
+) -> (usize, usize) {
+    let (position, size) = match (start, end) {
+        (Some(start), Some(end)) => (start, total.saturating_sub(start + end)),
+        (Some(start), None) => (start, size.unwrap_or_else(preferred)),
+        (None, Some(end)) => {
+            let size = size.unwrap_or_else(preferred);
+            (total.saturating_sub(end + size), size)
+        }
+        (None, None) => {
+            let size = std::cmp::min(size.unwrap_or_else(preferred), total);
+            ((total - size) / 2, size)
+        }
+    };
+    let position = std::cmp::min(position, total);
+    (position, std::cmp::min(size, total - position))
+}
+
+/// Draws floating containers on top of its content.
+pub struct FloatContainer {
+    content: Box<dyn Container>,
+    floats: Vec<Float>,
+    style: String,
+}
+
+impl FloatContainer {
+    pub fn new(content: impl Container + 'static, floats: Vec<Float>) -> Self {
+        Self {
+            content: Box::new(content),
+            floats,
+            style: String::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_style(mut self, style: &str) -> Self {
+        self.style = style.to_string();
+        self
+    }
+}
+
+impl Container for FloatContainer {
+    fn preferred_width(&self, max_available_width: usize) -> Dimension {
+        self.content.preferred_width(max_available_width)
+    }
+
+    fn preferred_height(&self, width: usize, max_available_height: usize) -> Dimension {
+        self.content.preferred_height(width, max_available_height)
+    }
+
+    fn write_to_screen(
+        &self,
+        screen: &mut Screen,
+        write_position: &WritePosition,
+        parent_style: &str,
+    ) {
+        let style = join_styles(parent_style, &self.style);
+        self.content.write_to_screen(screen, write_position, &style);
+
+        for float in &self.floats {
+            let float_position = float.write_position(write_position);
+            let content = Rc::clone(&float.content);
+            let style = style.clone();
+            screen.draw_with_z_index(float.z_index, move |screen| {
+                content.write_to_screen(screen, &float_position, &style);
+            });
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn Container> {
+        let mut children = vec![self.content.as_ref()];
+        children.extend(self.floats.iter().map(|float| float.content.as_ref()));
+        children
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::layout::controls::FormattedTextControl;
+
+    fn text_window(text: &str) -> Window {
+        Window::new(FormattedTextControl::from_text(text))
+    }
+
+    #[test]
+    fn test_hsplit_stacks_children() {
+        let split = HSplit::new(vec![
+            Box::new(text_window("top")),
+            Box::new(text_window("bottom")),
+        ]);
+        assert_eq!(split.preferred_height(10, 10).preferred(), 2);
+        assert_eq!(split.preferred_width(10).preferred(), 6);
+
+        let mut screen = Screen::new(None, 10, 2);
+        split.write_to_screen(&mut screen, &WritePosition::new(0, 0, 10, 2), "");
+        assert_eq!(screen.buffer_representation(), "top       \nbottom");
+    }
+
+    #[test]
+    fn test_hsplit_fixed_and_weighted_heights() {
+        let split = HSplit::new(vec![
+            Box::new(text_window("a").with_height(1)),
+            Box::new(text_window("b")),
+            Box::new(text_window("c").with_weight(2)),
+        ]);
+        assert_eq!(split.heights(10, 7), vec![1, 2, 4]);
+        assert_eq!(split.heights(10, 2), vec![1, 1, 0]);
+    }
+
+    #[test]
+    fn test_vsplit_places_children_side_by_side() {
+        let split = VSplit::new(vec![
+            Box::new(text_window("left").with_width(5)),
+            Box::new(text_window("right")),
+        ]);
+        assert_eq!(split.preferred_width(20).preferred(), 10);
+        assert_eq!(split.widths(20), vec![5, 15]);
+
+        let mut screen = Screen::new(None, 20, 1);
+        split.write_to_screen(&mut screen, &WritePosition::new(0, 0, 20, 1), "");
+        assert_eq!(screen.buffer_representation(), "left right");
+    }
+
+    #[test]
+    fn test_window_clips_and_styles_content() {
+        let window = text_window("hello\nworld\nagain").with_style("bold");
+        let mut screen = Screen::new(None, 3, 2);
+        window.write_to_screen(&mut screen, &WritePosition::new(0, 0, 3, 2), "italic");
+        assert_eq!(screen.buffer_representation(), "hel\nwor");
+        let cell = &screen.data_buffer[&0][&0];
+        assert!(cell.style.starts_with("italic bold"));
+        assert_eq!(
+            screen.get_visible_window_write_position(&window.id()),
+            Some(WritePosition::new(0, 0, 3, 2))
+        );
+    }
+
+    #[test]
+    fn test_float_container_draws_floats_on_top() {
+        let container = FloatContainer::new(
+            text_window("..........\n..........\n.........."),
+            vec![
+                Float::new(text_window("ab")).with_top(1).with_left(2),
+                Float::new(text_window("xy")),
+            ],
+        );
+        let mut screen = Screen::new(None, 10, 3);
+        container.write_to_screen(&mut screen, &WritePosition::new(0, 0, 10, 3), "");
+        assert_eq!(
+            screen.buffer_representation(),
+            "..........\n..........\n.........."
+        );
+
+        screen.draw_all_floats();
+        assert_eq!(
+            screen.buffer_representation(),
+            "..........\n..abxy....\n.........."
+        );
+        assert_eq!(container.children().len(), 3);
+    }
+
+    #[test]
+    fn test_place_on_axis() {
+        assert_eq!(place_on_axis(10, Some(2), Some(3), None, || 0), (2, 5));
+        assert_eq!(place_on_axis(10, None, Some(1), Some(3), || 0), (6, 3));
+        assert_eq!(place_on_axis(10, None, None, None, || 4), (3, 4));
+        assert_eq!(place_on_axis(10, Some(8), None, Some(5), || 0), (8, 2));
+    }
+}
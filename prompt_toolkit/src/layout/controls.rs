@@ -0,0 +1,222 @@
+use std::{cell::RefCell, rc::Rc};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::{buffer::Buffer, Point};
+
+/// A list of `(style, text)` fragments.
+pub type StyleAndTextTuples = Vec<(String, String)>;
+
+/// What a [`UIControl`] wants to show inside a window of a given size.
+#[derive(Debug, Default)]
+pub struct UIContent {
+    pub lines: Vec<StyleAndTextTuples>,
+    pub cursor_position: Option<Point>,
+}
+
+impl UIContent {
+    /// Split fragments into lines on `'\n'`.
+    #[must_use]
+    pub fn from_fragments(fragments: &[(String, String)]) -> Self {
+        let mut lines = Vec::new();
+        let mut line = Vec::new();
+        for (style, text) in fragments {
+            for (i, part) in text.split('\n').enumerate() {
+                if i > 0 {
+                    lines.push(std::mem::take(&mut line));
+                }
+                if !part.is_empty() {
+                    line.push((style.clone(), part.to_string()));
+                }
+            }
+        }
+        lines.push(line);
+        Self {
+            lines,
+            cursor_position: None,
+        }
+    }
+
+    #[must_use]
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+}
+
+/// Line width in terminal columns.
+pub(crate) fn fragments_width(fragments: &[(String, String)]) -> usize {
+    fragments.iter().map(|(_, text)| text.width()).sum()
+}
+
+/// The content of a [`Window`](super::Window).
+pub trait UIControl {
+    fn preferred_width(&self, max_available_width: usize) -> Option<usize>;
+    fn preferred_height(&self, width: usize, max_available_height: usize) -> Option<usize>;
+    fn create_content(&self, width: usize, height: usize) -> UIContent;
+
+    /// Whether the window showing this control can receive the focus.
+    fn is_focusable(&self) -> bool {
+        false
+    }
+
+    /// The buffer edited through this control, if any.
+    fn buffer(&self) -> Option<Rc<RefCell<Buffer>>> {
+        None
+    }
+}
+
+/// Shows formatted text, either fixed or computed on every render.
+pub struct FormattedTextControl {
+    get_fragments: Box<dyn Fn() -> StyleAndTextTuples>,
+    focusable: bool,
+}
+
+impl FormattedTextControl {
+    #[must_use]
+    pub fn new(fragments: StyleAndTextTuples) -> Self {
+        Self::dynamic(move || fragments.clone())
+    }
+
+    pub fn dynamic<F>(get_fragments: F) -> Self
+    where
+        F: Fn() -> StyleAndTextTuples + 'static,
+    {
+        Self {
+            get_fragments: Box::new(get_fragments),
+            focusable: false,
+        }
+    }
+
+    /// Unstyled text.
+    #[must_use]
+    pub fn from_text(text: &str) -> Self {
+        Self::new(vec![(String::new(), text.to_string())])
+    }
+
+    #[must_use]
+    pub fn with_focusable(mut self, focusable: bool) -> Self {
+        self.focusable = focusable;
+        self
+    }
+
+    fn content(&self) -> UIContent {
+        UIContent::from_fragments(&(self.get_fragments)())
+    }
+}
+
+impl UIControl for FormattedTextControl {
+    fn preferred_width(&self, _max_available_width: usize) -> Option<usize> {
+        self.content()
+            .lines
+            .iter()
+            .map(|line| fragments_width(line))
+            .max()
+    }
+
+    fn preferred_height(&self, _width: usize, _max_available_height: usize) -> Option<usize> {
+        Some(self.content().line_count())
+    }
+
+    fn create_content(&self, _width: usize, _height: usize) -> UIContent {
+        self.content()
+    }
+
+    fn is_focusable(&self) -> bool {
+        self.focusable
+    }
+}
+
+/// Shows the text of a [`Buffer`] and places the cursor in it.
+///
+/// The buffer is shared: key bindings edit it through
+/// [`Layout::current_buffer`](super::Layout::current_buffer) while the control
+/// displays it.
+pub struct BufferControl {
+    buffer: Rc<RefCell<Buffer>>,
+}
+
+impl BufferControl {
+    #[must_use]
+    pub fn new(buffer: Rc<RefCell<Buffer>>) -> Self {
+        Self { buffer }
+    }
+}
+
+impl UIControl for BufferControl {
+    fn preferred_width(&self, _max_available_width: usize) -> Option<usize> {
+        None
+    }
+
+    fn preferred_height(&self, _width: usize, _max_available_height: usize) -> Option<usize> {
+        Some(self.buffer.borrow().document().line_count())
+    }
+
+    fn create_content(&self, _width: usize, _height: usize) -> UIContent {
+        let document = self.buffer.borrow().document();
+        let mut content =
+            UIContent::from_fragments(&[(String::new(), document.text().to_string())]);
+        content.cursor_position = Some(Point::new(
+            document.current_line_before_cursor().width(),
+            document.cursor_position_row(),
+        ));
+        content
+    }
+
+    fn is_focusable(&self) -> bool {
+        true
+    }
+
+    fn buffer(&self) -> Option<Rc<RefCell<Buffer>>> {
+        Some(Rc::clone(&self.buffer))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_content_from_fragments() {
+        let content = UIContent::from_fragments(&[
+            ("bold".to_string(), "ab\ncd".to_string()),
+            (String::new(), "ef".to_string()),
+        ]);
+        assert_eq!(content.line_count(), 2);
+        assert_eq!(
+            content.lines[0],
+            vec![("bold".to_string(), "ab".to_string())]
+        );
+        assert_eq!(
+            content.lines[1],
+            vec![
+                ("bold".to_string(), "cd".to_string()),
+                (String::new(), "ef".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_formatted_text_control_preferred_size() {
+        let control = FormattedTextControl::from_text("hello\nhi");
+        assert_eq!(control.preferred_width(80), Some(5));
+        assert_eq!(control.preferred_height(80, 10), Some(2));
+        assert!(!control.is_focusable());
+    }
+
+    #[test]
+    fn test_buffer_control_places_cursor() {
+        let buffer = Rc::new(RefCell::new(Buffer::from_document(
+            &crate::document::Document::new("ab\n日本語", 6),
+        )));
+        let control = BufferControl::new(Rc::clone(&buffer));
+        let content = control.create_content(80, 10);
+        assert_eq!(content.line_count(), 2);
+        assert_eq!(content.cursor_position, Some(Point::new(2, 1)));
+
+        buffer.borrow_mut().insert_text("!", false, true);
+        assert_eq!(control.preferred_height(80, 10), Some(2));
+        assert!(control
+            .buffer()
+            .is_some_and(|shared| Rc::ptr_eq(&shared, &buffer)));
+    }
+}
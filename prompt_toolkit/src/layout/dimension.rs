@@ -0,0 +1,316 @@
+/// Size constraints of a container along one axis.
+///
+/// `preferred` is clamped between `min` and `max`, whatever order the
+/// builder methods are called in. `weight` decides how eagerly a container
+/// grows compared to its siblings when a split hands out space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dimension {
+    min: usize,
+    max: usize,
+    preferred: usize,
+    weight: usize,
+    preferred_specified: bool,
+}
+
+/// The `max` of a dimension without an upper bound.
+const UNBOUNDED: usize = 1_000_000_000;
+
+impl Default for Dimension {
+    fn default() -> Self {
+        Self {
+            min: 0,
+            max: UNBOUNDED,
+            preferred: 0,
+            weight: 1,
+            preferred_specified: false,
+        }
+    }
+}
+
+impl From<usize> for Dimension {
+    fn from(size: usize) -> Self {
+        Self::exact(size)
+    }
+}
+
+impl Dimension {
+    /// Exactly `size` cells, no more, no less.
+    #[must_use]
+    pub fn exact(size: usize) -> Self {
+        Self::default()
+            .with_min(size)
+            .with_max(size)
+            .with_preferred(size)
+    }
+
+    /// A dimension that takes no space at all.
+    #[must_use]
+    pub fn zero() -> Self {
+        Self::exact(0)
+    }
+
+    #[must_use]
+    pub fn with_min(mut self, min: usize) -> Self {
+        self.min = min;
+        self
+    }
+
+    #[must_use]
+    pub fn with_max(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+
+    #[must_use]
+    pub fn with_preferred(mut self, preferred: usize) -> Self {
+        self.preferred = preferred;
+        self.preferred_specified = true;
+        self
+    }
+
+    #[must_use]
+    pub fn with_weight(mut self, weight: usize) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    #[must_use]
+    pub fn min(&self) -> usize {
+        self.min
+    }
+
+    #[must_use]
+    pub fn max(&self) -> usize {
+        std::cmp::max(self.max, self.min)
+    }
+
+    #[must_use]
+    pub fn preferred(&self) -> usize {
+        self.preferred.clamp(self.min(), self.max())
+    }
+
+    #[must_use]
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    #[must_use]
+    pub fn preferred_specified(&self) -> bool {
+        self.preferred_specified
+    }
+
+    #[must_use]
+    pub fn is_zero(&self) -> bool {
+        self.max() == 0
+    }
+}
+
+/// The dimension of containers placed one after the other.
+#[must_use]
+pub fn sum_layout_dimensions(dimensions: &[Dimension]) -> Dimension {
+    let sum = |field: fn(&Dimension) -> usize| {
+        dimensions.iter().map(field).fold(0, usize::saturating_add)
+    };
+    Dimension::default()
+        .with_min(sum(Dimension::min))
+        .with_max(std::cmp::min(sum(Dimension::max), UNBOUNDED))
+        .with_preferred(sum(Dimension::preferred))
+}
+
+/// The dimension of containers placed next to each other, sharing this axis.
+///
+/// Zero dimensions are ignored, so that hidden containers don't limit
+/// their siblings.
+#[must_use]
+pub fn max_layout_dimensions(dimensions: &[Dimension]) -> Dimension {
+    let visible: Vec<&Dimension> = dimensions.iter().filter(|d| !d.is_zero()).collect();
+    if visible.is_empty() {
+        return Dimension::zero();
+    }
+    let highest = |field: fn(&Dimension) -> usize| visible.iter().map(|d| field(d)).max();
+    Dimension::default()
+        .with_min(highest(Dimension::min).unwrap_or(0))
+        .with_max(highest(Dimension::max).unwrap_or(0))
+        .with_preferred(highest(Dimension::preferred).unwrap_or(0))
+}
+
+/// Divide `total` cells between containers with the given dimensions.
+///
+/// Every container first gets its minimum. If the minimums don't fit
+/// (over-constrained), earlier containers keep theirs and later ones are
+/// clipped, down to zero. Otherwise the remaining space is handed out one
+/// cell at a time, in proportion to the weights: first until every
+/// container reaches its preferred size, then until it reaches its
+/// maximum. Space left after that (under-constrained) stays unused.
+#[must_use]
+pub fn distribute(dimensions: &[Dimension], total: usize) -> Vec<usize> {
+    let mut remaining = total;
+    let mut sizes: Vec<usize> = dimensions
+        .iter()
+        .map(|dimension| {
+            let size = std::cmp::min(dimension.min(), remaining);
+            remaining -= size;
+            size
+        })
+        .collect();
+
+    let weights: Vec<usize> = dimensions.iter().map(Dimension::weight).collect();
+    let preferred: Vec<usize> = dimensions.iter().map(Dimension::preferred).collect();
+    let max: Vec<usize> = dimensions.iter().map(Dimension::max).collect();
+    let mut order = WeightedRoundRobin::new(&weights);
+    grow(&mut sizes, &preferred, &mut order, &mut remaining);
+    grow(&mut sizes, &max, &mut order, &mut remaining);
+    sizes
+}
+
+/// Give cells to containers in weighted turns, until every container has
+/// reached its target or no space remains.
+fn grow(
+    sizes: &mut [usize],
+    targets: &[usize],
+    order: &mut WeightedRoundRobin,
+    remaining: &mut usize,
+) {
+    let weights = order.weights.clone();
+    let growable = |sizes: &[usize], i: usize| weights[i] > 0 && sizes[i] < targets[i];
+    while *remaining > 0 {
+        if !(0..sizes.len()).any(|i| growable(sizes, i)) {
+            return;
+        }
+        let i = order.next_index();
+        if growable(sizes, i) {
+            sizes[i] += 1;
+            *remaining -= 1;
+        }
+    }
+}
+
+/// Yields indices so that, over time, each is taken in proportion to its
+/// weight. Indices with a zero weight are never taken.
+struct WeightedRoundRobin {
+    weights: Vec<usize>,
+    taken: Vec<usize>,
+    max_weight: usize,
+    round: usize,
+    position: usize,
+    took_in_pass: bool,
+}
+
+impl WeightedRoundRobin {
+    fn new(weights: &[usize]) -> Self {
+        Self {
+            weights: weights.to_vec(),
+            taken: vec![0; weights.len()],
+            max_weight: weights.iter().copied().max().unwrap_or(0),
+            round: 0,
+            position: 0,
+            took_in_pass: false,
+        }
+    }
+
+    /// Callers make sure that at least one weight is non-zero.
+    fn next_index(&mut self) -> usize {
+        loop {
+            if self.position == self.weights.len() {
+                self.position = 0;
+                if !self.took_in_pass {
+                    self.round += 1;
+                }
+                self.took_in_pass = false;
+            }
+            let i = self.position;
+            self.position += 1;
+            // Take `i` while it is behind its share of `round * weight / max_weight`.
+            if self.taken[i] * self.max_weight < self.round * self.weights[i] {
+                self.taken[i] += 1;
+                self.took_in_pass = true;
+                return i;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dimension_normalizes_preferred() {
+        let dimension = Dimension::default()
+            .with_preferred(20)
+            .with_max(10)
+            .with_min(2);
+        assert_eq!(dimension.preferred(), 10);
+        assert_eq!(Dimension::default().with_min(5).preferred(), 5);
+        assert_eq!(Dimension::default().with_min(5).with_max(3).max(), 5);
+        assert!(Dimension::zero().is_zero());
+        assert_eq!(Dimension::from(4), Dimension::exact(4));
+    }
+
+    #[test]
+    fn test_sum_and_max_layout_dimensions() {
+        let dimensions = [
+            Dimension::exact(2),
+            Dimension::default().with_min(1).with_preferred(5),
+            Dimension::zero(),
+        ];
+        let sum = sum_layout_dimensions(&dimensions);
+        assert_eq!((sum.min(), sum.preferred()), (3, 7));
+        assert_eq!(sum.max(), UNBOUNDED);
+
+        let max = max_layout_dimensions(&dimensions);
+        assert_eq!((max.min(), max.preferred(), max.max()), (2, 5, UNBOUNDED));
+        assert!(max_layout_dimensions(&[Dimension::zero()]).is_zero());
+        assert!(max_layout_dimensions(&[]).is_zero());
+    }
+
+    #[test]
+    fn test_distribute_exact_fit() {
+        let dimensions = [Dimension::exact(3), Dimension::exact(7)];
+        assert_eq!(distribute(&dimensions, 10), vec![3, 7]);
+    }
+
+    #[test]
+    fn test_distribute_over_constrained() {
+        // The minimums need 9 cells: earlier containers win.
+        let dimensions = [
+            Dimension::exact(4),
+            Dimension::default().with_min(3),
+            Dimension::exact(2),
+        ];
+        assert_eq!(distribute(&dimensions, 6), vec![4, 2, 0]);
+        assert_eq!(distribute(&dimensions, 0), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn test_distribute_reaches_preferred_before_max() {
+        let dimensions = [
+            Dimension::default().with_preferred(2),
+            Dimension::default().with_preferred(6),
+        ];
+        // Not enough room for both preferred sizes: share evenly.
+        assert_eq!(distribute(&dimensions, 6), vec![2, 4]);
+        // Both preferred sizes fit, the rest is handed out in turns.
+        assert_eq!(distribute(&dimensions, 12), vec![4, 8]);
+    }
+
+    #[test]
+    fn test_distribute_uses_weights() {
+        let dimensions = [Dimension::default(), Dimension::default().with_weight(3)];
+        assert_eq!(distribute(&dimensions, 8), vec![2, 6]);
+
+        let dimensions = [Dimension::default().with_weight(0), Dimension::default()];
+        assert_eq!(distribute(&dimensions, 5), vec![0, 5]);
+    }
+
+    #[test]
+    fn test_distribute_under_constrained() {
+        // The maximums only need 5 cells: the rest stays unused.
+        let dimensions = [
+            Dimension::default().with_max(2),
+            Dimension::default().with_max(3),
+        ];
+        assert_eq!(distribute(&dimensions, 20), vec![2, 3]);
+        assert!(distribute(&[], 20).is_empty());
+    }
+}
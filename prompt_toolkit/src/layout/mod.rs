@@ -1,2 +1,8 @@
 mod base;
+mod containers;
+mod controls;
+mod dimension;
 pub use base::Layout;
+pub use containers::{Container, Float, FloatContainer, HSplit, VSplit, Window};
+pub use controls::{BufferControl, FormattedTextControl, StyleAndTextTuples, UIContent, UIControl};
+pub use dimension::{distribute, max_layout_dimensions, sum_layout_dimensions, Dimension};
@@ -2,10 +2,12 @@
 #![expect(clippy::module_name_repetitions)]
 
 pub(crate) mod screen;
-pub(crate) mod styles;
 
 pub mod application;
+pub mod buffer;
 pub mod clipboard;
+pub mod config;
+pub mod document;
 pub mod filters;
 pub mod input;
 pub mod key_bindings;
@@ -13,8 +15,10 @@ pub mod keys;
 pub mod layout;
 pub mod output;
 pub mod render;
+pub mod selection;
 pub mod shortcuts;
+pub mod styles;
 
 pub use input::{Input, KeyPress};
 pub use output::{Output, Size};
-pub use screen::{Char, Point, Screen, WritePosition};
+pub use screen::{Char, Point, Screen, WindowId, WritePosition};
@@ -1,16 +1,25 @@
+use std::collections::HashMap;
+
 use crate::styles::{AnsiColor, Attrs, Color};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Size {
     pub rows: usize,
     pub columns: usize,
 }
+/// Size assumed for outputs that don't know their own, e.g. files and pipes.
+pub(crate) const DEFAULT_SIZE: Size = Size {
+    rows: 40,
+    columns: 80,
+};
+
 impl std::fmt::Display for Size {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "Size(rows={}, columns={})", self.rows, self.columns)
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum CursorShape {
     #[default]
     NeverChange,
@@ -22,7 +31,7 @@ pub enum CursorShape {
     BlinkingUnderline,
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ColorDepth {
     Monochrome,
     Ansi,
@@ -32,6 +41,53 @@ pub enum ColorDepth {
 }
 
 impl ColorDepth {
+    /// The color depth the terminal described by the process environment
+    /// supports, see [`ColorDepth::from_env_map`].
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self::from_env_map(&environment())
+    }
+
+    /// The color depth for the environment variables in `env`. The first
+    /// of these that applies wins:
+    ///
+    /// 1. `NO_COLOR`, when set to anything but an empty string: monochrome.
+    /// 2. `PROMPT_TOOLKIT_COLOR_DEPTH`: `DEPTH_1_BIT`, `DEPTH_4_BIT`,
+    ///    `DEPTH_8_BIT` or `DEPTH_24_BIT`. Other values are ignored.
+    /// 3. `COLORTERM` of `truecolor` or `24bit`: true color.
+    /// 4. `TERM`: monochrome for `dumb`, ANSI colors for `linux` and
+    ///    `eterm-color`, 256 colors for `*-256color`.
+    /// 5. Otherwise 256 colors, [`ColorDepth::Default`].
+    #[must_use]
+    pub fn from_env_map(env: &HashMap<String, String>) -> Self {
+        let var = |name: &str| env.get(name).map(String::as_str);
+        if wants_no_color(env) {
+            return Self::Monochrome;
+        }
+        if let Some(depth) = var("PROMPT_TOOLKIT_COLOR_DEPTH").and_then(Self::from_name) {
+            return depth;
+        }
+        if matches!(var("COLORTERM"), Some("truecolor" | "24bit")) {
+            return Self::True;
+        }
+        match var("TERM") {
+            Some("dumb") => Self::Monochrome,
+            Some("linux" | "eterm-color") => Self::Ansi,
+            _ => Self::Default,
+        }
+    }
+
+    /// Parse the names `PROMPT_TOOLKIT_COLOR_DEPTH` accepts.
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "DEPTH_1_BIT" => Some(Self::Monochrome),
+            "DEPTH_4_BIT" => Some(Self::Ansi),
+            "DEPTH_8_BIT" => Some(Self::Default),
+            "DEPTH_24_BIT" => Some(Self::True),
+            _ => None,
+        }
+    }
+
     #[must_use]
     pub fn bit_depth(&self) -> usize {
         match self {
@@ -79,8 +135,8 @@ impl ColorDepth {
                     first.to_string(),
                     "2".to_string(),
                     r.to_string(),
-                    b.to_string(),
                     g.to_string(),
+                    b.to_string(),
                 ]
             }
         }
@@ -141,6 +197,18 @@ impl ColorDepth {
     }
 }
 
+/// The environment variables of the process that are valid unicode.
+pub(crate) fn environment() -> HashMap<String, String> {
+    std::env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .collect()
+}
+
+/// Whether the user asked for no colors at all, see <https://no-color.org>.
+pub(crate) fn wants_no_color(env: &HashMap<String, String>) -> bool {
+    env.get("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
 pub trait Output {
     fn fileno(&self) -> i32;
     fn encoding(&self) -> &'static str;
@@ -169,6 +237,9 @@ pub trait Output {
     fn show_cursor(&mut self);
     fn set_cursor_shape(&mut self, shape: CursorShape);
     fn reset_cursor_shape(&mut self);
+    /// Whether the cursor can move back up to overwrite what was written,
+    /// as on a terminal. Without it, the layout is written as plain lines.
+    fn supports_cursor_movement(&self) -> bool;
     fn supports_cursor_position_requests(&self) -> bool;
     fn request_cursor_position(&mut self);
     fn get_size(&self) -> Size;
@@ -236,6 +307,10 @@ impl Output for DummyOutput {
 
     fn reset_cursor_shape(&mut self) {}
 
+    fn supports_cursor_movement(&self) -> bool {
+        true
+    }
+
     fn supports_cursor_position_requests(&self) -> bool {
         false
     }
@@ -258,6 +333,61 @@ impl Output for DummyOutput {
 mod test {
     use super::*;
 
+    fn depth_for(vars: &[(&str, &str)]) -> ColorDepth {
+        let env = vars
+            .iter()
+            .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+            .collect();
+        ColorDepth::from_env_map(&env)
+    }
+
+    #[test]
+    fn test_color_depth_from_env() {
+        assert_eq!(depth_for(&[]), ColorDepth::Default);
+        assert_eq!(
+            depth_for(&[("TERM", "xterm-256color")]),
+            ColorDepth::Default
+        );
+        assert_eq!(depth_for(&[("TERM", "dumb")]), ColorDepth::Monochrome);
+        assert_eq!(depth_for(&[("TERM", "linux")]), ColorDepth::Ansi);
+        assert_eq!(
+            depth_for(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")]),
+            ColorDepth::True
+        );
+        assert_eq!(depth_for(&[("COLORTERM", "24bit")]), ColorDepth::True);
+        assert_eq!(depth_for(&[("COLORTERM", "yes")]), ColorDepth::Default);
+    }
+
+    #[test]
+    fn test_color_depth_precedence() {
+        assert_eq!(
+            depth_for(&[("NO_COLOR", "1"), ("COLORTERM", "truecolor")]),
+            ColorDepth::Monochrome
+        );
+        assert_eq!(
+            depth_for(&[("NO_COLOR", ""), ("TERM", "linux")]),
+            ColorDepth::Ansi
+        );
+        assert_eq!(
+            depth_for(&[
+                ("PROMPT_TOOLKIT_COLOR_DEPTH", "DEPTH_24_BIT"),
+                ("NO_COLOR", "1")
+            ]),
+            ColorDepth::Monochrome
+        );
+        assert_eq!(
+            depth_for(&[
+                ("PROMPT_TOOLKIT_COLOR_DEPTH", "DEPTH_4_BIT"),
+                ("TERM", "dumb")
+            ]),
+            ColorDepth::Ansi
+        );
+        assert_eq!(
+            depth_for(&[("PROMPT_TOOLKIT_COLOR_DEPTH", "lots"), ("TERM", "dumb")]),
+            ColorDepth::Monochrome
+        );
+    }
+
     #[test]
     fn test_cursor_shapes() {
         let mut out = DummyOutput;
@@ -1,6 +1,10 @@
 mod base;
 mod plaintext;
+mod recording;
 mod vt100;
 
-pub use base::{ColorDepth, CursorShape, Output, Size};
+pub(crate) use base::{environment, wants_no_color, DEFAULT_SIZE};
+pub use base::{ColorDepth, CursorShape, DummyOutput, Output, Size};
+pub use plaintext::PlainText;
+pub use recording::{OutputEvent, RecordingOutput, TerminalCell, VirtualTerminal};
 pub use vt100::VT100;
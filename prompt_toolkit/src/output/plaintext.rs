@@ -1 +1,177 @@
+use std::os::unix::io::RawFd;
 
+use crate::output::Output;
+use crate::styles::Attrs;
+
+use super::{vt100::write_all, ColorDepth, CursorShape, Size, DEFAULT_SIZE};
+
+/// Output for files and pipes: only the text is written. Cursor movement
+/// down becomes newlines and forward becomes spaces; colors, titles, mouse
+/// support and everything else that needs a terminal is left out. The
+/// layout is rendered to it as plain lines.
+#[derive(Debug)]
+pub struct PlainText {
+    out: RawFd,
+    buffer: String,
+    size: Size,
+}
+
+impl PlainText {
+    /// Write to `out`, reporting a size of 40 rows of 80 columns.
+    #[must_use]
+    pub fn new(out: RawFd) -> Self {
+        Self {
+            out,
+            buffer: String::with_capacity(1024),
+            size: DEFAULT_SIZE,
+        }
+    }
+
+    /// Report `size`, which the layout is rendered in, instead.
+    #[must_use]
+    pub fn with_size(mut self, size: Size) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+impl Output for PlainText {
+    fn fileno(&self) -> i32 {
+        self.out
+    }
+
+    fn encoding(&self) -> &'static str {
+        "utf-8"
+    }
+
+    fn write(&mut self, data: &str) {
+        self.buffer.push_str(data);
+    }
+
+    fn write_raw(&mut self, data: &str) {
+        self.buffer.push_str(data);
+    }
+
+    fn set_title(&mut self, _title: &str) {}
+
+    fn clear_title(&mut self) {}
+
+    fn flush(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        if write_all(self.out, self.buffer.as_bytes()) {
+            self.buffer.clear();
+        }
+    }
+
+    fn erase_screen(&mut self) {}
+
+    fn enter_alternate_screen(&mut self) {}
+
+    fn quit_alternate_screen(&mut self) {}
+
+    fn enable_mouse_support(&mut self) {}
+
+    fn disable_mouse_support(&mut self) {}
+
+    fn erase_end_of_line(&mut self) {}
+
+    fn erase_down(&mut self) {}
+
+    fn reset_attributes(&mut self) {}
+
+    fn set_attributes(&mut self, _attrs: Attrs, _color_depth: ColorDepth) {}
+
+    fn disable_autowrap(&mut self) {}
+
+    fn enable_autowrap(&mut self) {}
+
+    fn cursor_goto(&mut self, _row: usize, _column: usize) {}
+
+    fn cursor_up(&mut self, _amount: usize) {}
+
+    fn cursor_down(&mut self, amount: usize) {
+        self.buffer.push_str(&"\n".repeat(amount));
+    }
+
+    fn cursor_forward(&mut self, amount: usize) {
+        self.buffer.push_str(&" ".repeat(amount));
+    }
+
+    fn cursor_back(&mut self, _amount: usize) {}
+
+    fn hide_cursor(&mut self) {}
+
+    fn show_cursor(&mut self) {}
+
+    fn set_cursor_shape(&mut self, _shape: CursorShape) {}
+
+    fn reset_cursor_shape(&mut self) {}
+
+    fn supports_cursor_movement(&self) -> bool {
+        false
+    }
+
+    fn supports_cursor_position_requests(&self) -> bool {
+        false
+    }
+
+    fn request_cursor_position(&mut self) {}
+
+    fn get_size(&self) -> Size {
+        self.size
+    }
+
+    fn get_default_color_depth(&self) -> ColorDepth {
+        ColorDepth::Monochrome
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Seek};
+    use std::os::fd::IntoRawFd;
+
+    use tempfile::tempfile;
+
+    use super::*;
+    use crate::styles::Attrs;
+
+    #[test]
+    fn test_only_text_is_written() {
+        let file = tempfile().expect("output temp file");
+        let mut file_clone = file.try_clone().expect("cloned output temp file");
+        let mut output = PlainText::new(file.into_raw_fd());
+
+        output.set_title("title");
+        output.enable_mouse_support();
+        output.hide_cursor();
+        output.set_attributes(Attrs::default(), ColorDepth::True);
+        output.write("hello");
+        output.cursor_up(1);
+        output.cursor_down(2);
+        output.cursor_forward(2);
+        output.write("world");
+        output.erase_down();
+        output.flush();
+
+        file_clone
+            .seek(std::io::SeekFrom::Start(0))
+            .expect("seek error");
+        let mut result = String::new();
+        file_clone.read_to_string(&mut result).expect("read error");
+        assert_eq!(result, "hello\n\n  world");
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!(PlainText::new(-1).get_size(), DEFAULT_SIZE);
+        assert!(!PlainText::new(-1).supports_cursor_movement());
+        let size = Size {
+            rows: 5,
+            columns: 20,
+        };
+        assert_eq!(PlainText::new(-1).with_size(size).get_size(), size);
+    }
+}
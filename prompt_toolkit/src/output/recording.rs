@@ -0,0 +1,518 @@
+use std::{cell::RefCell, rc::Rc};
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::output::Output;
+use crate::styles::Attrs;
+
+use super::{ColorDepth, CursorShape, Size};
+
+/// A call made on a [`RecordingOutput`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputEvent {
+    Write(String),
+    WriteRaw(String),
+    SetTitle(String),
+    ClearTitle,
+    Flush,
+    EraseScreen,
+    EnterAlternateScreen,
+    QuitAlternateScreen,
+    EnableMouseSupport,
+    DisableMouseSupport,
+    EraseEndOfLine,
+    EraseDown,
+    ResetAttributes,
+    SetAttributes(Attrs, ColorDepth),
+    DisableAutowrap,
+    EnableAutowrap,
+    /// 1-based, like the escape sequence.
+    CursorGoto {
+        row: usize,
+        column: usize,
+    },
+    CursorUp(usize),
+    CursorDown(usize),
+    CursorForward(usize),
+    CursorBack(usize),
+    HideCursor,
+    ShowCursor,
+    SetCursorShape(CursorShape),
+    ResetCursorShape,
+    RequestCursorPosition,
+}
+
+/// Output that records every call instead of writing anywhere, for tests.
+///
+/// Clones share the recording: keep one to inspect what an application
+/// wrote to the other, either as [`RecordingOutput::events`] or as the
+/// terminal they would have drawn, see [`RecordingOutput::terminal`].
+#[derive(Debug, Clone)]
+pub struct RecordingOutput {
+    events: Rc<RefCell<Vec<OutputEvent>>>,
+    size: Size,
+}
+
+impl RecordingOutput {
+    #[must_use]
+    pub fn new(size: Size) -> Self {
+        Self {
+            events: Rc::new(RefCell::new(Vec::new())),
+            size,
+        }
+    }
+
+    /// Every call so far, in order.
+    #[must_use]
+    pub fn events(&self) -> Vec<OutputEvent> {
+        self.events.borrow().clone()
+    }
+
+    /// Forget the calls so far.
+    pub fn clear(&self) {
+        self.events.borrow_mut().clear();
+    }
+
+    /// A terminal of the output's size with all calls so far replayed on it.
+    #[must_use]
+    pub fn terminal(&self) -> VirtualTerminal {
+        let mut terminal = VirtualTerminal::new(self.size);
+        for event in self.events.borrow().iter() {
+            terminal.apply(event);
+        }
+        terminal
+    }
+
+    fn record(&self, event: OutputEvent) {
+        self.events.borrow_mut().push(event);
+    }
+}
+
+impl Output for RecordingOutput {
+    fn fileno(&self) -> i32 {
+        panic!("no sensible default for recording output fileno")
+    }
+
+    fn encoding(&self) -> &'static str {
+        "utf-8"
+    }
+
+    fn write(&mut self, data: &str) {
+        self.record(OutputEvent::Write(data.to_string()));
+    }
+
+    fn write_raw(&mut self, data: &str) {
+        self.record(OutputEvent::WriteRaw(data.to_string()));
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.record(OutputEvent::SetTitle(title.to_string()));
+    }
+
+    fn clear_title(&mut self) {
+        self.record(OutputEvent::ClearTitle);
+    }
+
+    fn flush(&mut self) {
+        self.record(OutputEvent::Flush);
+    }
+
+    fn erase_screen(&mut self) {
+        self.record(OutputEvent::EraseScreen);
+    }
+
+    fn enter_alternate_screen(&mut self) {
+        self.record(OutputEvent::EnterAlternateScreen);
+    }
+
+    fn quit_alternate_screen(&mut self) {
+        self.record(OutputEvent::QuitAlternateScreen);
+    }
+
+    fn enable_mouse_support(&mut self) {
+        self.record(OutputEvent::EnableMouseSupport);
+    }
+
+    fn disable_mouse_support(&mut self) {
+        self.record(OutputEvent::DisableMouseSupport);
+    }
+
+    fn erase_end_of_line(&mut self) {
+        self.record(OutputEvent::EraseEndOfLine);
+    }
+
+    fn erase_down(&mut self) {
+        self.record(OutputEvent::EraseDown);
+    }
+
+    fn reset_attributes(&mut self) {
+        self.record(OutputEvent::ResetAttributes);
+    }
+
+    fn set_attributes(&mut self, attrs: Attrs, color_depth: ColorDepth) {
+        self.record(OutputEvent::SetAttributes(attrs, color_depth));
+    }
+
+    fn disable_autowrap(&mut self) {
+        self.record(OutputEvent::DisableAutowrap);
+    }
+
+    fn enable_autowrap(&mut self) {
+        self.record(OutputEvent::EnableAutowrap);
+    }
+
+    fn cursor_goto(&mut self, row: usize, column: usize) {
+        self.record(OutputEvent::CursorGoto { row, column });
+    }
+
+    fn cursor_up(&mut self, amount: usize) {
+        self.record(OutputEvent::CursorUp(amount));
+    }
+
+    fn cursor_down(&mut self, amount: usize) {
+        self.record(OutputEvent::CursorDown(amount));
+    }
+
+    fn cursor_forward(&mut self, amount: usize) {
+        self.record(OutputEvent::CursorForward(amount));
+    }
+
+    fn cursor_back(&mut self, amount: usize) {
+        self.record(OutputEvent::CursorBack(amount));
+    }
+
+    fn hide_cursor(&mut self) {
+        self.record(OutputEvent::HideCursor);
+    }
+
+    fn show_cursor(&mut self) {
+        self.record(OutputEvent::ShowCursor);
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) {
+        self.record(OutputEvent::SetCursorShape(shape));
+    }
+
+    fn reset_cursor_shape(&mut self) {
+        self.record(OutputEvent::ResetCursorShape);
+    }
+
+    fn supports_cursor_movement(&self) -> bool {
+        true
+    }
+
+    fn supports_cursor_position_requests(&self) -> bool {
+        false
+    }
+
+    fn request_cursor_position(&mut self) {
+        self.record(OutputEvent::RequestCursorPosition);
+    }
+
+    fn get_size(&self) -> Size {
+        self.size
+    }
+
+    fn get_default_color_depth(&self) -> ColorDepth {
+        ColorDepth::True
+    }
+}
+
+/// A character on a [`VirtualTerminal`] and the attributes it was written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalCell {
+    pub char: char,
+    pub attrs: Attrs,
+    /// Covered by the wide character before it.
+    is_continuation: bool,
+}
+
+impl Default for TerminalCell {
+    fn default() -> Self {
+        Self {
+            char: ' ',
+            attrs: Attrs::default(),
+            is_continuation: false,
+        }
+    }
+}
+
+/// A grid of cells that [`OutputEvent`]s are applied to like a terminal
+/// would, to check what an application shows rather than the escape
+/// sequences it took. Escape sequences inside written text are not
+/// interpreted.
+#[derive(Debug, Clone)]
+pub struct VirtualTerminal {
+    size: Size,
+    rows: Vec<Vec<TerminalCell>>,
+    /// The main screen while the alternate screen is shown.
+    main_screen: Option<Vec<Vec<TerminalCell>>>,
+    cursor_row: usize,
+    cursor_column: usize,
+    attrs: Attrs,
+    autowrap: bool,
+    cursor_visible: bool,
+    title: String,
+}
+
+impl VirtualTerminal {
+    #[must_use]
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            rows: vec![vec![TerminalCell::default(); size.columns]; size.rows],
+            main_screen: None,
+            cursor_row: 0,
+            cursor_column: 0,
+            attrs: Attrs::default(),
+            autowrap: true,
+            cursor_visible: true,
+            title: String::new(),
+        }
+    }
+
+    /// The text of `row`, without trailing blanks.
+    ///
+    /// # Panics
+    ///
+    /// If `row` is outside of the terminal.
+    #[must_use]
+    pub fn row_text(&self, row: usize) -> String {
+        let text: String = self.rows[row]
+            .iter()
+            .filter(|cell| !cell.is_continuation)
+            .map(|cell| cell.char)
+            .collect();
+        text.trim_end().to_string()
+    }
+
+    /// The text of every row, without trailing blanks.
+    #[must_use]
+    pub fn lines(&self) -> Vec<String> {
+        (0..self.rows.len()).map(|row| self.row_text(row)).collect()
+    }
+
+    /// # Panics
+    ///
+    /// If the position is outside of the terminal.
+    #[must_use]
+    pub fn cell(&self, row: usize, column: usize) -> &TerminalCell {
+        &self.rows[row][column]
+    }
+
+    /// Row and column of the cursor, 0-based.
+    #[must_use]
+    pub fn cursor_position(&self) -> (usize, usize) {
+        (self.cursor_row, self.cursor_column)
+    }
+
+    #[must_use]
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    #[must_use]
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn apply(&mut self, event: &OutputEvent) {
+        let last_row = self.size.rows.saturating_sub(1);
+        let last_column = self.size.columns.saturating_sub(1);
+        match event {
+            OutputEvent::Write(text) | OutputEvent::WriteRaw(text) => self.write(text),
+            OutputEvent::SetTitle(title) => self.title.clone_from(title),
+            OutputEvent::ClearTitle => self.title.clear(),
+            OutputEvent::EraseScreen => {
+                for row in &mut self.rows {
+                    row.fill(TerminalCell::default());
+                }
+            }
+            OutputEvent::EnterAlternateScreen => {
+                if self.main_screen.is_none() {
+                    let blank =
+                        vec![vec![TerminalCell::default(); self.size.columns]; self.size.rows];
+                    self.main_screen = Some(std::mem::replace(&mut self.rows, blank));
+                    self.cursor_row = 0;
+                    self.cursor_column = 0;
+                }
+            }
+            OutputEvent::QuitAlternateScreen => {
+                if let Some(main_screen) = self.main_screen.take() {
+                    self.rows = main_screen;
+                }
+            }
+            OutputEvent::EraseEndOfLine => self.erase_end_of_line(),
+            OutputEvent::EraseDown => {
+                self.erase_end_of_line();
+                for row in self.rows.iter_mut().skip(self.cursor_row + 1) {
+                    row.fill(TerminalCell::default());
+                }
+            }
+            OutputEvent::ResetAttributes => self.attrs = Attrs::default(),
+            OutputEvent::SetAttributes(attrs, _) => self.attrs = *attrs,
+            OutputEvent::DisableAutowrap => self.autowrap = false,
+            OutputEvent::EnableAutowrap => self.autowrap = true,
+            OutputEvent::CursorGoto { row, column } => {
+                self.cursor_row = row.saturating_sub(1).min(last_row);
+                self.cursor_column = column.saturating_sub(1).min(last_column);
+            }
+            OutputEvent::CursorUp(amount) => {
+                self.cursor_row = self.cursor_row.saturating_sub(*amount);
+            }
+            OutputEvent::CursorDown(amount) => {
+                self.cursor_row = (self.cursor_row + amount).min(last_row);
+            }
+            OutputEvent::CursorForward(amount) => {
+                self.cursor_column = (self.cursor_column + amount).min(last_column);
+            }
+            OutputEvent::CursorBack(amount) => {
+                self.cursor_column = self.cursor_column.saturating_sub(*amount);
+            }
+            OutputEvent::HideCursor => self.cursor_visible = false,
+            OutputEvent::ShowCursor => self.cursor_visible = true,
+            OutputEvent::Flush
+            | OutputEvent::EnableMouseSupport
+            | OutputEvent::DisableMouseSupport
+            | OutputEvent::SetCursorShape(_)
+            | OutputEvent::ResetCursorShape
+            | OutputEvent::RequestCursorPosition => {}
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        for char in text.chars() {
+            match char {
+                '\r' => self.cursor_column = 0,
+                '\n' => self.line_feed(),
+                _ => self.put_char(char),
+            }
+        }
+    }
+
+    /// Move the cursor down, scrolling the rows up at the bottom.
+    fn line_feed(&mut self) {
+        if self.cursor_row + 1 < self.size.rows {
+            self.cursor_row += 1;
+        } else if !self.rows.is_empty() {
+            self.rows.remove(0);
+            self.rows
+                .push(vec![TerminalCell::default(); self.size.columns]);
+        }
+    }
+
+    fn put_char(&mut self, char: char) {
+        let width = char.width().unwrap_or(0);
+        if width == 0 || self.size.rows == 0 {
+            return;
+        }
+        if self.cursor_column + width > self.size.columns {
+            if self.autowrap {
+                self.cursor_column = 0;
+                self.line_feed();
+            } else {
+                self.cursor_column = self.size.columns.saturating_sub(width);
+            }
+        }
+        let attrs = self.attrs;
+        let row = &mut self.rows[self.cursor_row];
+        for (offset, cell) in row
+            .iter_mut()
+            .skip(self.cursor_column)
+            .take(width)
+            .enumerate()
+        {
+            *cell = TerminalCell {
+                char,
+                attrs,
+                is_continuation: offset > 0,
+            };
+        }
+        self.cursor_column += width;
+        if !self.autowrap {
+            self.cursor_column = self.cursor_column.min(self.size.columns.saturating_sub(1));
+        }
+    }
+
+    fn erase_end_of_line(&mut self) {
+        if let Some(row) = self.rows.get_mut(self.cursor_row) {
+            for cell in row.iter_mut().skip(self.cursor_column) {
+                *cell = TerminalCell::default();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::styles::AttrSetting;
+
+    fn size(rows: usize, columns: usize) -> Size {
+        Size { rows, columns }
+    }
+
+    #[test]
+    fn test_events_are_recorded_in_order() {
+        let recording = RecordingOutput::new(size(5, 20));
+        let mut output = recording.clone();
+        output.hide_cursor();
+        output.write("hi");
+        output.cursor_down(2);
+        output.flush();
+        assert_eq!(
+            recording.events(),
+            vec![
+                OutputEvent::HideCursor,
+                OutputEvent::Write("hi".to_string()),
+                OutputEvent::CursorDown(2),
+                OutputEvent::Flush,
+            ]
+        );
+        recording.clear();
+        assert!(recording.events().is_empty());
+    }
+
+    #[test]
+    fn test_replay_into_terminal() {
+        let recording = RecordingOutput::new(size(3, 10));
+        let mut output = recording.clone();
+        let bold = Attrs {
+            bold: AttrSetting::Enabled,
+            ..Attrs::default()
+        };
+        output.write("first\r\n");
+        output.set_attributes(bold, ColorDepth::True);
+        output.write("> ");
+        output.reset_attributes();
+        output.write("hello");
+        output.cursor_up(1);
+        output.write_raw("\r");
+        output.cursor_forward(2);
+        output.erase_end_of_line();
+        output.hide_cursor();
+
+        let terminal = recording.terminal();
+        assert_eq!(terminal.lines(), vec!["fi", "> hello", ""]);
+        assert!(terminal.cell(1, 0).attrs.bold.is_on());
+        assert!(!terminal.cell(1, 2).attrs.bold.is_on());
+        assert_eq!(terminal.cursor_position(), (0, 2));
+        assert!(!terminal.cursor_visible());
+    }
+
+    #[test]
+    fn test_wrapping_and_scrolling() {
+        let mut terminal = VirtualTerminal::new(size(2, 4));
+        terminal.apply(&OutputEvent::Write("abcdef\r\nxy".to_string()));
+        assert_eq!(terminal.lines(), vec!["ef", "xy"]);
+
+        let mut terminal = VirtualTerminal::new(size(2, 4));
+        terminal.apply(&OutputEvent::DisableAutowrap);
+        terminal.apply(&OutputEvent::Write("abcdef".to_string()));
+        assert_eq!(terminal.lines(), vec!["abcf", ""]);
+
+        let mut terminal = VirtualTerminal::new(size(1, 4));
+        terminal.apply(&OutputEvent::Write("日本".to_string()));
+        assert_eq!(terminal.row_text(0), "日本");
+    }
+}
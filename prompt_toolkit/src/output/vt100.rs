@@ -1,18 +1,21 @@
 use nix::errno::Errno;
 use nix::libc::{ioctl, winsize, TIOCGWINSZ};
-use nix::unistd::write;
+use nix::unistd::{isatty, write};
 use tracing::{error, warn};
 
 use crate::output::Output;
 use crate::styles::Attrs;
+use std::collections::HashMap;
 use std::os::{fd::BorrowedFd, unix::io::RawFd};
 
-use super::{ColorDepth, CursorShape};
+use super::{environment, ColorDepth, CursorShape};
 
 #[derive(Debug)]
 pub struct VT100 {
     out: RawFd,
     buffer: String,
+    /// Escape sequences already built by [`ColorDepth::escape_code`].
+    escape_codes: HashMap<(Attrs, ColorDepth), String>,
 }
 
 impl VT100 {
@@ -21,10 +24,37 @@ impl VT100 {
         Self {
             out,
             buffer: String::with_capacity(1024),
+            escape_codes: HashMap::new(),
         }
     }
 }
 
+/// Whether `TERM` names a terminal that only prints lines, like an Emacs
+/// shell buffer.
+fn is_dumb_terminal(env: &HashMap<String, String>) -> bool {
+    env.get("TERM").is_some_and(|term| term == "dumb")
+}
+
+/// Write all of `bytes` to `out`, retrying after interrupts. Returns
+/// whether everything was written.
+pub(super) fn write_all(out: RawFd, mut bytes: &[u8]) -> bool {
+    while !bytes.is_empty() {
+        match write(unsafe { BorrowedFd::borrow_raw(out) }, bytes) {
+            Ok(0) => {
+                warn!("wrote 0 bytes!");
+                return false;
+            }
+            Ok(n) => bytes = &bytes[n..],
+            Err(Errno::EINTR) => {}
+            Err(r) => {
+                error!("unspecified error writing to output: {}", r);
+                return false;
+            }
+        }
+    }
+    true
+}
+
 impl Output for VT100 {
     fn fileno(&self) -> i32 {
         self.out
@@ -41,7 +71,6 @@ impl Output for VT100 {
 
     fn write_raw(&mut self, data: &str) {
         self.buffer.push_str(data);
-        self.flush();
     }
 
     fn set_title(&mut self, title: &str) {
@@ -60,22 +89,9 @@ impl Output for VT100 {
             return;
         }
 
-        let mut bytes = self.buffer.as_bytes();
-        while !bytes.is_empty() {
-            match write(unsafe { BorrowedFd::borrow_raw(self.out) }, bytes) {
-                Ok(0) => {
-                    warn!("wrote 0 bytes!");
-                    return;
-                }
-                Ok(n) => bytes = &bytes[n..],
-                Err(Errno::EINTR) => {}
-                Err(r) => {
-                    error!("unspecified error writing to output: {}", r);
-                    return;
-                }
-            }
+        if write_all(self.out, self.buffer.as_bytes()) {
+            self.buffer.clear();
         }
-        self.buffer.clear();
     }
 
     fn erase_screen(&mut self) {
@@ -123,8 +139,11 @@ impl Output for VT100 {
     }
 
     fn set_attributes(&mut self, attrs: Attrs, color_depth: ColorDepth) {
-        let escape_code = color_depth.escape_code(attrs);
-        self.write_raw(&escape_code);
+        let escape_code = self
+            .escape_codes
+            .entry((attrs, color_depth))
+            .or_insert_with(|| color_depth.escape_code(attrs));
+        self.buffer.push_str(escape_code);
     }
 
     fn disable_autowrap(&mut self) {
@@ -192,8 +211,14 @@ impl Output for VT100 {
         self.write_raw("\x1b[0 q");
     }
 
+    /// Not for files, pipes and dumb terminals.
+    fn supports_cursor_movement(&self) -> bool {
+        isatty(self.out).unwrap_or(false) && !is_dumb_terminal(&environment())
+    }
+
     fn supports_cursor_position_requests(&self) -> bool {
-        true
+        // Only a terminal will answer; files and pipes never send a response.
+        isatty(self.out).unwrap_or(false)
     }
 
     fn request_cursor_position(&mut self) {
@@ -225,6 +250,26 @@ impl Output for VT100 {
     }
 
     fn get_default_color_depth(&self) -> super::ColorDepth {
-        super::ColorDepth::Default
+        super::ColorDepth::from_env()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::fd::IntoRawFd;
+
+    use tempfile::tempfile;
+
+    use super::*;
+
+    #[test]
+    fn test_cursor_movement_needs_a_terminal() {
+        let env = |term: &str| HashMap::from([("TERM".to_string(), term.to_string())]);
+        assert!(is_dumb_terminal(&env("dumb")));
+        assert!(!is_dumb_terminal(&env("xterm-256color")));
+        assert!(!is_dumb_terminal(&HashMap::new()));
+
+        let file = tempfile().expect("output temp file");
+        assert!(!VT100::new(file.into_raw_fd()).supports_cursor_movement());
     }
 }
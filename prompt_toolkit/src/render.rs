@@ -1,139 +1,892 @@
 #![expect(dead_code)]
-#![expect(unused_variables)]
-#![expect(clippy::unused_self)]
 
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Result};
+use tracing::warn;
 
 use crate::{
-    application::Application,
     layout::Layout,
-    styles::{Attrs, Style},
-    Char, Output, Point, Screen, Size,
+    output::{ColorDepth, DEFAULT_SIZE},
+    styles::{Attrs, DummyStyleTransformation, Style, StyleTransformation},
+    Char, Output, Point, Screen, Size, WritePosition,
 };
 
-#[derive(Debug)]
+/// How long to wait for a Cursor Position Response before deciding the
+/// terminal does not answer them.
+const CPR_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, PartialEq, Eq)]
 enum CPRSupport {
     Unknown,
     Supported,
     Unsupported,
 }
 
-struct Renderer<O: Output> {
+pub(crate) struct Renderer {
     style: Box<dyn Style>,
-    output: O,
+    style_transformation: Box<dyn StyleTransformation>,
+    output: Box<dyn Output>,
     bracketed_paste_enabled: bool,
     cursor_key_mode_reset: bool,
     cpr_support: CPRSupport,
+    /// Cursor position relative to the top-left corner of the last rendered screen.
+    cursor_pos: Point,
+    /// The last rendered screen and terminal width, used to only repaint what changed.
+    last_screen: Option<Screen>,
+    last_width: usize,
+    /// Rows between the cursor and the bottom of the terminal, learned through
+    /// a Cursor Position Request. Zero while unknown.
+    min_available_height: usize,
+    waiting_for_cpr_since: Option<Instant>,
+    attrs_cache: AttrsCache,
+    /// Invalidation hashes of the style and the style transformation, and
+    /// the color depth, of the last screen. When they change, everything is
+    /// redrawn.
+    last_style_hashes: Option<(u64, u64)>,
+    last_color_depth: Option<ColorDepth>,
+    /// Write plain text only, without cursor movement or escape sequences.
+    line_oriented: bool,
+    /// Text already written by line-oriented rendering since the last reset.
+    written_text: String,
 }
 
-impl<O: Output> Renderer<O> {
-    pub fn new(output: O, style: Box<dyn Style>) -> Self {
-        let cpr_support = output.supports_cursor_position_requests();
+impl Renderer {
+    pub fn new(output: Box<dyn Output>, style: Box<dyn Style>) -> Self {
+        let line_oriented = !output.supports_cursor_movement();
         let mut renderer = Self {
             style,
+            style_transformation: Box::new(DummyStyleTransformation),
             output,
             bracketed_paste_enabled: false,
             cursor_key_mode_reset: false,
-            cpr_support: if cpr_support {
-                CPRSupport::Unknown
-            } else {
-                CPRSupport::Unsupported
-            },
+            cpr_support: CPRSupport::Unknown,
+            cursor_pos: Point::new(0, 0),
+            last_screen: None,
+            last_width: 0,
+            min_available_height: 0,
+            waiting_for_cpr_since: None,
+            attrs_cache: AttrsCache::default(),
+            last_style_hashes: None,
+            last_color_depth: None,
+            line_oriented,
+            written_text: String::new(),
         };
+        renderer.cpr_support = renderer.initial_cpr_support();
         renderer.reset();
         renderer
     }
 
+    fn initial_cpr_support(&self) -> CPRSupport {
+        if !self.line_oriented && self.output.supports_cursor_position_requests() {
+            CPRSupport::Unknown
+        } else {
+            CPRSupport::Unsupported
+        }
+    }
+
+    /// Draw to `output` from now on, as plain lines when it doesn't
+    /// support cursor movement.
+    pub fn set_output(&mut self, output: Box<dyn Output>) {
+        self.line_oriented = !output.supports_cursor_movement();
+        self.output = output;
+        self.cpr_support = self.initial_cpr_support();
+        self.reset();
+    }
+
+    /// Write the layout as plain lines of text rather than drawing it with
+    /// cursor movement and colors. Chosen for outputs that don't support
+    /// cursor movement, e.g. files, pipes and dumb terminals.
+    ///
+    /// Text is only ever appended: the part of the layout before the
+    /// cursor is written as it grows, e.g. echoing what the user types,
+    /// and the rest follows with a newline once the application is done.
+    pub fn set_line_oriented(&mut self, line_oriented: bool) {
+        self.line_oriented = line_oriented;
+        self.cpr_support = self.initial_cpr_support();
+        self.reset();
+    }
+
+    /// Resolve the style strings of the layout through `style`.
+    pub fn set_style(&mut self, style: Box<dyn Style>) {
+        self.style = style;
+    }
+
+    /// Change the attributes of every style before they are written.
+    pub fn set_style_transformation(&mut self, style_transformation: Box<dyn StyleTransformation>) {
+        self.style_transformation = style_transformation;
+    }
+
+    pub fn output_mut(&mut self) -> &mut dyn Output {
+        self.output.as_mut()
+    }
+
+    /// Forget everything about the previous frame; the next render starts at the current cursor.
     pub fn reset(&mut self) {
+        self.cursor_pos = Point::new(0, 0);
+        self.last_screen = None;
+        self.last_width = 0;
+        self.min_available_height = 0;
+        self.written_text.clear();
         self.output.flush();
     }
 
     pub fn height_is_known(&self) -> bool {
-        false
+        self.min_available_height > 0
     }
 
     /// Number of rows visible to the terminal above the layout
     pub fn rows_above_layout(&self) -> Result<usize> {
-        Err(anyhow!("unknown rows above layout"))
+        if self.min_available_height == 0 {
+            return Err(anyhow!("unknown rows above layout"));
+        }
+        let total_rows = self.output.get_size().rows;
+        let last_screen_height = self.last_screen.as_ref().map_or(0, |screen| screen.height);
+        Ok(total_rows.saturating_sub(std::cmp::max(self.min_available_height, last_screen_height)))
+    }
+
+    /// Ask the terminal where the cursor is. The answer arrives through the
+    /// input as a `Keys::CPRResponse` and must be handed to
+    /// [`Renderer::report_absolute_cursor_row`].
+    ///
+    /// Only meaningful while the cursor is on the first row of the layout,
+    /// i.e. before the first render or right after a reset.
+    pub fn request_absolute_cursor_position(&mut self) {
+        match self.cpr_support {
+            CPRSupport::Unsupported => {}
+            CPRSupport::Unknown if self.waiting_for_cpr() => {}
+            CPRSupport::Unknown | CPRSupport::Supported => {
+                self.waiting_for_cpr_since = Some(Instant::now());
+                self.output.request_cursor_position();
+            }
+        }
     }
 
-    /// TODO: handle async flow for Cursor Position Requests/Responses
-    pub fn request_absolute_cursor_position(&self) {}
+    /// Record the 1-based cursor row reported by the terminal, reserving the
+    /// rows below it for the layout.
+    pub fn report_absolute_cursor_row(&mut self, row: usize) {
+        self.cpr_support = CPRSupport::Supported;
+        self.waiting_for_cpr_since = None;
+
+        let total_rows = self.output.get_size().rows;
+        self.min_available_height = (total_rows + 1).saturating_sub(row);
+    }
+
+    pub fn waiting_for_cpr(&self) -> bool {
+        self.waiting_for_cpr_since.is_some()
+    }
+
+    /// Stop waiting for a Cursor Position Response that did not arrive in
+    /// time. A terminal that never answered is marked as not supporting them.
+    pub fn check_cpr_timeout(&mut self) {
+        let Some(since) = self.waiting_for_cpr_since else {
+            return;
+        };
+        if since.elapsed() < CPR_TIMEOUT {
+            return;
+        }
+        self.waiting_for_cpr_since = None;
+        if self.cpr_support == CPRSupport::Unknown {
+            warn!("no cursor position response received, disabling cursor position requests");
+            self.cpr_support = CPRSupport::Unsupported;
+        }
+    }
+
+    /// Draw the layout, only repainting the cells that changed since the previous call.
+    ///
+    /// When `is_done` is set the cursor is left below the output so that the
+    /// terminal can continue printing after the application exits. Colors
+    /// are written with no more than `color_depth`.
+    pub fn render(&mut self, layout: &Layout, color_depth: ColorDepth, is_done: bool) {
+        if self.line_oriented {
+            self.render_lines(layout, is_done);
+            return;
+        }
+        let size = self.output.get_size();
+        let preferred_height = layout.preferred_height(size.columns, size.rows).preferred();
+        let height = if is_done {
+            // Don't keep the reserved rows around once the application is done.
+            preferred_height
+        } else {
+            let last_height = self.last_screen.as_ref().map_or(0, |screen| screen.height);
+            std::cmp::max(
+                preferred_height,
+                std::cmp::max(self.min_available_height, last_height),
+            )
+        };
+        let height = std::cmp::min(height, size.rows);
+        let mut screen = Screen::new(None, size.columns, height);
+        layout.write_to_screen(&mut screen, &WritePosition::new(0, 0, size.columns, height));
+        let cursor_position = layout.current_window().map_or(Point::new(0, 0), |window| {
+            screen.get_cursor_position(&window)
+        });
+
+        let style_hashes = (
+            self.style.invalidation_hash(),
+            self.style_transformation.invalidation_hash(),
+        );
+        if self.last_style_hashes != Some(style_hashes)
+            || self.last_color_depth != Some(color_depth)
+        {
+            self.last_style_hashes = Some(style_hashes);
+            self.last_color_depth = Some(color_depth);
+            self.last_screen = None;
+        }
+        self.attrs_cache.validate(style_hashes.0);
+        let styling = Styling {
+            style: self.style.as_ref(),
+            style_transformation: self.style_transformation.as_ref(),
+            color_depth,
+            attrs_cache: &mut self.attrs_cache,
+        };
+        self.cursor_pos = output_screen_diff(
+            self.output.as_mut(),
+            styling,
+            &screen,
+            self.cursor_pos,
+            self.last_screen.as_ref(),
+            self.last_width,
+            cursor_position,
+            &size,
+            is_done,
+        );
+        self.output.flush();
 
-    pub fn render(&mut self, app: &Application, layout: &Layout, is_done: bool) {
         if is_done {
             self.reset();
+        } else {
+            self.last_screen = Some(screen);
+            self.last_width = size.columns;
         }
     }
+
+    /// See [`Renderer::set_line_oriented`].
+    fn render_lines(&mut self, layout: &Layout, is_done: bool) {
+        let size = self.output.get_size();
+        let size = if size.rows == 0 || size.columns == 0 {
+            DEFAULT_SIZE
+        } else {
+            size
+        };
+        let height = layout.preferred_height(size.columns, size.rows).preferred();
+        let height = std::cmp::min(height, size.rows);
+        let mut screen = Screen::new(None, size.columns, height);
+        layout.write_to_screen(&mut screen, &WritePosition::new(0, 0, size.columns, height));
+        let cursor_position = layout.current_window().map_or(Point::new(0, 0), |window| {
+            screen.get_cursor_position(&window)
+        });
+
+        let text = screen_text(&screen, cursor_position, is_done);
+        if let Some(appended) = text.strip_prefix(&self.written_text) {
+            self.output.write(appended);
+            self.written_text = text;
+        } else if is_done {
+            // Something before the cursor changed, e.g. a deleted
+            // character: repeat the final text on a line of its own.
+            self.output.write("\n");
+            self.output.write(&text);
+            self.written_text = text;
+        }
+        if is_done {
+            if !self.written_text.is_empty() {
+                self.output.write("\n");
+            }
+            self.reset();
+        } else {
+            self.output.flush();
+        }
+    }
+
+    /// Remove the last rendered output from the terminal.
+    ///
+    /// Line-oriented output can't be removed, it is only ended.
+    pub fn erase(&mut self) {
+        if self.line_oriented {
+            if !self.written_text.is_empty() {
+                self.output.write("\n");
+            }
+            self.reset();
+            return;
+        }
+        self.move_to_origin();
+        self.output.erase_down();
+        self.output.reset_attributes();
+        self.reset();
+    }
+
+    fn move_to_origin(&mut self) {
+        self.output.cursor_up(self.cursor_pos.y);
+        self.output.write_raw("\r");
+        self.cursor_pos = Point::new(0, 0);
+    }
+}
+
+/// The attributes of style strings, which repeat for most cells of a
+/// screen, resolved once per style. Keyed by the style string, for the
+/// style with `invalidation_hash`.
+#[derive(Default)]
+struct AttrsCache {
+    invalidation_hash: Option<u64>,
+    attrs: HashMap<String, Attrs>,
+}
+
+impl AttrsCache {
+    /// Forget everything when the style changed.
+    fn validate(&mut self, invalidation_hash: u64) {
+        if self.invalidation_hash != Some(invalidation_hash) {
+            self.invalidation_hash = Some(invalidation_hash);
+            self.attrs.clear();
+        }
+    }
+}
+
+/// How the style strings of cells become attributes on the output.
+struct Styling<'a> {
+    style: &'a dyn Style,
+    style_transformation: &'a dyn StyleTransformation,
+    color_depth: ColorDepth,
+    attrs_cache: &'a mut AttrsCache,
+}
+
+impl Styling<'_> {
+    /// The attributes of `style_str`, before the style transformation.
+    fn attrs(&mut self, style_str: &str) -> Attrs {
+        if let Some(attrs) = self.attrs_cache.attrs.get(style_str) {
+            return *attrs;
+        }
+        let attrs = self.style.get_attrs(style_str, Attrs::default());
+        self.attrs_cache.attrs.insert(style_str.to_string(), attrs);
+        attrs
+    }
+
+    /// Whether `style_str` looks different from no style at all.
+    fn has_style(&mut self, style_str: &str) -> bool {
+        self.attrs(style_str) != self.attrs("")
+    }
+}
+
+/// The characters of `screen` up to `cursor_position`, or all of them
+/// when `is_done`, one line per row. Trailing blanks are left out, except
+/// those before the cursor.
+fn screen_text(screen: &Screen, cursor_position: Point, is_done: bool) -> String {
+    let blank_row = HashMap::new();
+    let default_char = screen.default_char();
+    let last_row = if is_done {
+        screen.height.saturating_sub(1)
+    } else {
+        cursor_position.y
+    };
+    let mut lines = Vec::new();
+    for y in 0..=last_row {
+        let row = screen.data_buffer.get(&y).unwrap_or(&blank_row);
+        let mut line = String::new();
+        let mut cursor_len = 0;
+        let mut x = 0;
+        while x < screen.width {
+            let char = row.get(&x).unwrap_or(&default_char);
+            line.push(char.char);
+            x += std::cmp::max(char.width, 1);
+            if x <= cursor_position.x {
+                cursor_len = line.len();
+            }
+        }
+        let trimmed_len = line.trim_end().len();
+        if y == cursor_position.y {
+            // The cursor may be behind blanks, e.g. those after a prompt.
+            let len = if is_done {
+                std::cmp::max(trimmed_len, cursor_len)
+            } else {
+                cursor_len
+            };
+            line.truncate(len);
+        } else {
+            line.truncate(trimmed_len);
+        }
+        lines.push(line);
+    }
+    if is_done {
+        while lines.len() > 1 && lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+    }
+    lines.join("\n")
 }
 
-fn get_max_column_index(row: &HashMap<usize, Char>, width: usize) -> usize {
-    row.keys().max().copied().unwrap_or(width - 1)
+/// Column of the last cell worth drawing: trailing unstyled blanks are skipped.
+fn get_max_column_index(row: &HashMap<usize, Char>, styling: &mut Styling<'_>) -> usize {
+    row.iter()
+        .filter(|(_, cell)| cell.char != ' ' || styling.has_style(&cell.style))
+        .map(|(index, _)| *index)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Cursor bookkeeping while writing the difference between two screens.
+struct ScreenDiff<'a> {
+    output: &'a mut dyn Output,
+    styling: Styling<'a>,
+    size: &'a Size,
+    current_pos: Point,
+    /// Attributes of the last written cell, before the style transformation.
+    last_attrs: Option<Attrs>,
+}
+
+impl ScreenDiff<'_> {
+    fn reset_attributes(&mut self) {
+        self.output.reset_attributes();
+        self.last_attrs = None;
+    }
+
+    fn move_cursor(&mut self, new: Point) {
+        let current = self.current_pos;
+        self.current_pos = new;
+
+        if new.y > current.y {
+            // Newlines rather than cursor-down: they scroll the terminal when needed.
+            self.reset_attributes();
+            self.output.write("\r\n".repeat(new.y - current.y).as_str());
+            self.output.cursor_forward(new.x);
+            return;
+        }
+        if new.y < current.y {
+            self.output.cursor_up(current.y - new.y);
+        }
+
+        if current.x >= self.size.columns.saturating_sub(1) {
+            self.output.write("\r");
+            self.output.cursor_forward(new.x);
+        } else if new.x < current.x {
+            self.output.cursor_back(current.x - new.x);
+        } else if new.x > current.x {
+            self.output.cursor_forward(new.x - current.x);
+        }
+    }
+
+    fn output_char(&mut self, char: &Char) {
+        let new_attrs = self.styling.attrs(&char.style);
+        if self.last_attrs != Some(new_attrs) {
+            self.output.set_attributes(
+                self.styling.style_transformation.transform_attrs(new_attrs),
+                self.styling.color_depth,
+            );
+            self.last_attrs = Some(new_attrs);
+        }
+        self.output.write(&char.char.to_string());
+    }
 }
 
-fn move_cursor(
+/// Write only the cells of `screen` that differ from `previous_screen`.
+///
+/// `current_pos` is where the terminal cursor is, relative to the top-left
+/// corner of the screen. Without a previous screen, or when the width
+/// changed, everything is redrawn. Returns the new cursor position.
+#[expect(clippy::too_many_arguments)]
+fn output_screen_diff(
     output: &mut dyn Output,
+    styling: Styling<'_>,
+    screen: &Screen,
+    current_pos: Point,
+    previous_screen: Option<&Screen>,
+    previous_width: usize,
+    cursor_position: Point,
     size: &Size,
-    new: Point,
-    mut current_position: Point,
+    is_done: bool,
 ) -> Point {
-    if new.y > current_position.y {
-        output.reset_attributes();
-        let newlines = "\r\n".repeat(new.y - current_position.y);
-        output.write(&newlines);
-        current_position.x = 0;
-        output.cursor_forward(new.x);
-        return new;
-    }
-    if new.y < current_position.y {
-        output.cursor_up(current_position.y - new.y);
-    }
-
-    if current_position.x >= size.columns - 1 {
-        output.write("\r");
-        output.cursor_forward(new.x);
-    } else if new.x < current_position.x || current_position.x >= size.columns - 1 {
-        output.cursor_back(current_position.x - new.x);
-    } else if new.x > current_position.x {
-        output.cursor_forward(new.x - current_position.x);
-    }
-    new
-}
+    let width = size.columns;
+    let mut diff = ScreenDiff {
+        output,
+        styling,
+        size,
+        current_pos,
+        last_attrs: None,
+    };
 
-pub fn output_screen(output: &mut dyn Output, screen: &Screen, size: &Size) -> Point {
-    let mut current_position = Point::new(0, 0);
-    output.hide_cursor();
-    output.disable_autowrap();
+    diff.output.hide_cursor();
+    diff.output.disable_autowrap();
 
-    let width = size.columns;
-    let current_height = std::cmp::min(size.rows, screen.height);
-    let row_count = current_height;
+    let blank_screen = Screen::new(None, 0, 0);
+    let previous_screen = match previous_screen {
+        Some(previous_screen) if !is_done && previous_width == width => previous_screen,
+        _ => {
+            // Nothing to compare against: start over from the top-left corner.
+            diff.move_cursor(Point::new(0, 0));
+            diff.reset_attributes();
+            diff.output.erase_down();
+            &blank_screen
+        }
+    };
+
+    let current_height = std::cmp::min(screen.height, size.rows);
+    let row_count = std::cmp::min(
+        std::cmp::max(screen.height, previous_screen.height),
+        size.rows,
+    );
 
     let blank_row = HashMap::new();
     let default_char = screen.default_char();
+    let previous_default_char = previous_screen.default_char();
 
     for y in 0..row_count {
-        let row = screen.data_buffer.get(&y).unwrap_or(&blank_row);
-        let max_line_len = std::cmp::min(width - 1, get_max_column_index(row, width));
+        let new_row = screen.data_buffer.get(&y).unwrap_or(&blank_row);
+        let previous_row = previous_screen.data_buffer.get(&y).unwrap_or(&blank_row);
+
+        let new_max_line_len = std::cmp::min(
+            width.saturating_sub(1),
+            get_max_column_index(new_row, &mut diff.styling),
+        );
+        let previous_max_line_len = std::cmp::min(
+            width.saturating_sub(1),
+            get_max_column_index(previous_row, &mut diff.styling),
+        );
+
         let mut c = 0usize;
+        while c <= new_max_line_len {
+            let new_char = new_row.get(&c).unwrap_or(&default_char);
+            let old_char = previous_row.get(&c).unwrap_or(&previous_default_char);
+            let char_width = std::cmp::max(new_char.width, 1);
 
-        while c <= max_line_len {
-            let render_char = row.get(&c).unwrap_or(&default_char);
-            let attrs = Attrs::from_style_string(&render_char.style);
-            let char_width = render_char.width;
-            current_position = move_cursor(output, size, Point::new(c, y), current_position);
-            output.set_attributes(attrs, crate::output::ColorDepth::True);
-            output.write(&render_char.char.to_string());
-            current_position.x += char_width;
+            if new_char != old_char {
+                diff.move_cursor(Point::new(c, y));
+                diff.output_char(new_char);
+                diff.current_pos.x += char_width;
+            }
             c += char_width;
         }
+
+        // The new line is shorter: clear what is left of the old one.
+        if new_max_line_len < previous_max_line_len {
+            diff.move_cursor(Point::new(new_max_line_len + 1, y));
+            diff.reset_attributes();
+            diff.output.erase_end_of_line();
+        }
+    }
+
+    // Move to the bottom once when the screen grew, so the terminal scrolls
+    // and reserves the rows even if they only contain whitespace.
+    if current_height > previous_screen.height {
+        diff.move_cursor(Point::new(0, current_height - 1));
+    }
+
+    if is_done {
+        diff.move_cursor(Point::new(0, current_height));
+        diff.output.erase_down();
+    } else {
+        diff.move_cursor(cursor_position);
+    }
+
+    diff.output.enable_autowrap();
+    diff.reset_attributes();
+
+    if screen.show_cursor() || is_done {
+        diff.output.show_cursor();
+    }
+    diff.current_pos
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Seek};
+    use std::os::fd::IntoRawFd;
+
+    use tempfile::tempfile;
+
+    use super::*;
+    use crate::{
+        output::DummyOutput,
+        output::VT100,
+        styles::{DummyStyle, StandardStyle},
+        WritePosition,
+    };
+
+    fn screen_with(text: &str) -> Screen {
+        let mut screen = Screen::new(None, 20, 2);
+        screen.direct_draw(&WritePosition::new(0, 0, 20, 2), text, "");
+        screen
+    }
+
+    fn diff_to_string(screen: &Screen, previous_screen: Option<&Screen>) -> String {
+        styled_diff_to_string(
+            screen,
+            previous_screen,
+            &StandardStyle::default(),
+            ColorDepth::True,
+        )
+    }
+
+    fn styled_diff_to_string(
+        screen: &Screen,
+        previous_screen: Option<&Screen>,
+        style: &dyn Style,
+        color_depth: ColorDepth,
+    ) -> String {
+        let file = tempfile().expect("output temp file");
+        let mut file_clone = file.try_clone().expect("cloned output temp file");
+        let mut output = VT100::new(file.into_raw_fd());
+        let size = Size {
+            rows: 10,
+            columns: 20,
+        };
+
+        output_screen_diff(
+            &mut output,
+            Styling {
+                style,
+                style_transformation: &DummyStyleTransformation,
+                color_depth,
+                attrs_cache: &mut AttrsCache::default(),
+            },
+            screen,
+            Point::new(0, 0),
+            previous_screen,
+            20,
+            Point::new(0, 0),
+            &size,
+            false,
+        );
+        output.flush();
+
+        file_clone
+            .seek(std::io::SeekFrom::Start(0))
+            .expect("seek error");
+        let mut result = String::new();
+        file_clone.read_to_string(&mut result).expect("read error");
+        result
     }
 
-    output.reset_attributes();
-    if screen.show_cursor() {
-        output.show_cursor();
+    #[test]
+    fn test_first_render_draws_everything() {
+        let screen = screen_with("hello\nworld");
+        let result = diff_to_string(&screen, None);
+        assert!(result.contains("hello"));
+        assert!(result.contains("world"));
+        assert!(result.contains("\x1b[J"));
+    }
+
+    #[test]
+    fn test_only_changed_cells_are_written() {
+        let previous = screen_with("hello\nworld");
+        let screen = screen_with("help!\nworld");
+        let result = diff_to_string(&screen, Some(&previous));
+        assert!(result.contains("p!"));
+        assert!(!result.contains("hel"));
+        assert!(!result.contains("world"));
+        assert!(!result.contains("\x1b[J"));
+    }
+
+    #[test]
+    fn test_identical_screens_write_no_characters() {
+        let previous = screen_with("hello");
+        let screen = screen_with("hello");
+        let result = diff_to_string(&screen, Some(&previous));
+        // Only autowrap, attributes and the cursor are touched.
+        assert_eq!(result, "\x1b[?25l\x1b[?7l\x1b[?7h\x1b[0m\x1b[?25l\x1b[?25h");
+    }
+
+    #[test]
+    fn test_shorter_row_is_erased() {
+        let previous = screen_with("hello world");
+        let screen = screen_with("hello");
+        let result = diff_to_string(&screen, Some(&previous));
+        assert!(result.contains("\x1b[5C\x1b[0m\x1b[K"));
+        assert!(!result.contains("hello"));
+    }
+
+    #[test]
+    fn test_attributes_are_set_when_they_change() {
+        let mut screen = Screen::new(None, 20, 2);
+        screen.direct_draw(&WritePosition::new(0, 0, 20, 1), "ab", "bold");
+        screen.direct_draw(&WritePosition::new(2, 0, 18, 1), "cd", "bold bold");
+        screen.direct_draw(&WritePosition::new(4, 0, 16, 1), "ef", "italic");
+        let result = diff_to_string(&screen, None);
+        assert_eq!(result.matches("\x1b[0;1m").count(), 1);
+        assert!(result.contains("\x1b[0;1mabcd\x1b[0;3mef"));
+    }
+
+    #[test]
+    fn test_attrs_cache_follows_the_style() {
+        let mut cache = AttrsCache::default();
+        cache.validate(1);
+        let mut styling = Styling {
+            style: &StandardStyle::default(),
+            style_transformation: &DummyStyleTransformation,
+            color_depth: ColorDepth::True,
+            attrs_cache: &mut cache,
+        };
+        assert!(styling.has_style("bold"));
+        assert!(!styling.has_style(""));
+        assert_eq!(cache.attrs.len(), 2);
+        cache.validate(1);
+        assert_eq!(cache.attrs.len(), 2);
+        cache.validate(2);
+        assert!(cache.attrs.is_empty());
+
+        let mut renderer = Renderer::new(Box::new(DummyOutput), Box::new(DummyStyle));
+        renderer.render(&Layout::default(), ColorDepth::True, false);
+        let hash = renderer.attrs_cache.invalidation_hash;
+        let style = StandardStyle::new(vec![("prompt".to_string(), "bold".to_string())])
+            .expect("valid class names");
+        renderer.set_style(Box::new(style));
+        renderer.render(&Layout::default(), ColorDepth::True, false);
+        assert!(renderer.attrs_cache.invalidation_hash.is_some());
+        assert_ne!(renderer.attrs_cache.invalidation_hash, hash);
+    }
+
+    #[test]
+    fn test_cells_are_styled_through_the_style() {
+        let style = StandardStyle::new(vec![
+            ("prompt".to_string(), "bold".to_string()),
+            ("selected".to_string(), "bg:ansiblue".to_string()),
+        ])
+        .expect("valid class names");
+        let mut screen = Screen::new(None, 20, 2);
+        screen.direct_draw(&WritePosition::new(0, 0, 20, 1), ">", "class:prompt");
+        screen.direct_draw(&WritePosition::new(0, 1, 20, 1), "  ", "class:selected");
+        let result = styled_diff_to_string(&screen, None, &style, ColorDepth::True);
+        assert!(result.contains("\x1b[0;1m>"));
+        // Blanks with a background are drawn, unstyled ones are not.
+        assert!(result.contains("\x1b[0;44m "));
+    }
+
+    #[test]
+    fn test_colors_honor_the_color_depth() {
+        let mut screen = Screen::new(None, 20, 1);
+        screen.direct_draw(&WritePosition::new(0, 0, 20, 1), "x", "fg:#ff0000");
+        let style = StandardStyle::default();
+        let true_color = styled_diff_to_string(&screen, None, &style, ColorDepth::True);
+        assert!(true_color.contains("\x1b[0;38;2;255;0;0m"));
+        let ansi = styled_diff_to_string(&screen, None, &style, ColorDepth::Ansi);
+        assert!(ansi.contains("\x1b[0;91m"));
+        let monochrome = styled_diff_to_string(&screen, None, &style, ColorDepth::Monochrome);
+        assert!(!monochrome.contains(";38;") && !monochrome.contains(";91"));
+    }
+
+    #[test]
+    fn test_true_colors_are_written_as_rgb() {
+        let mut screen = Screen::new(None, 20, 1);
+        screen.direct_draw(
+            &WritePosition::new(0, 0, 20, 1),
+            "x",
+            "fg:#102030 bg:#405060",
+        );
+        let result =
+            styled_diff_to_string(&screen, None, &StandardStyle::default(), ColorDepth::True);
+        assert!(result.contains("\x1b[0;38;2;16;32;48;48;2;64;80;96m"));
+    }
+
+    #[test]
+    fn test_color_depth_change_redraws_everything() {
+        let mut renderer = Renderer::new(Box::new(DummyOutput), Box::new(DummyStyle));
+        renderer.render(&Layout::default(), ColorDepth::True, false);
+        assert_eq!(renderer.last_color_depth, Some(ColorDepth::True));
+        renderer.render(&Layout::default(), ColorDepth::Ansi, false);
+        assert_eq!(renderer.last_color_depth, Some(ColorDepth::Ansi));
+    }
+
+    #[test]
+    fn test_cpr_not_requested_when_unsupported() {
+        let mut renderer = Renderer::new(Box::new(DummyOutput), Box::new(DummyStyle));
+        renderer.request_absolute_cursor_position();
+        assert!(!renderer.waiting_for_cpr());
+        assert!(!renderer.height_is_known());
+        assert!(renderer.rows_above_layout().is_err());
+    }
+
+    #[test]
+    fn test_cpr_response_reserves_rows_below_cursor() {
+        let mut renderer = Renderer::new(Box::new(DummyOutput), Box::new(DummyStyle));
+        renderer.cpr_support = CPRSupport::Unknown;
+
+        renderer.request_absolute_cursor_position();
+        assert!(renderer.waiting_for_cpr());
+
+        // DummyOutput is 40 rows high: a cursor on row 31 leaves 10 rows.
+        renderer.report_absolute_cursor_row(31);
+        assert!(!renderer.waiting_for_cpr());
+        assert_eq!(renderer.cpr_support, CPRSupport::Supported);
+        assert!(renderer.height_is_known());
+        assert_eq!(renderer.rows_above_layout().expect("known height"), 30);
+
+        renderer.render(&Layout::default(), ColorDepth::True, false);
+        assert_eq!(renderer.last_screen.as_ref().map(|s| s.height), Some(10));
+
+        renderer.render(&Layout::default(), ColorDepth::True, true);
+        assert!(!renderer.height_is_known());
+    }
+
+    #[test]
+    fn test_style_transformation_change_redraws_everything() {
+        let mut renderer = Renderer::new(Box::new(DummyOutput), Box::new(DummyStyle));
+        renderer.render(&Layout::default(), ColorDepth::True, false);
+        let hashes = renderer.last_style_hashes;
+        renderer.render(&Layout::default(), ColorDepth::True, false);
+        assert_eq!(renderer.last_style_hashes, hashes);
+
+        renderer.set_style_transformation(Box::new(crate::styles::ReverseStyleTransformation));
+        renderer.render(&Layout::default(), ColorDepth::True, false);
+        assert_ne!(renderer.last_style_hashes, hashes);
+    }
+
+    #[test]
+    fn test_screen_text_stops_at_the_cursor() {
+        let mut screen = Screen::new(None, 20, 3);
+        screen.direct_draw(&WritePosition::new(0, 0, 20, 3), "title\n> ab\nstatus", "");
+        assert_eq!(screen_text(&screen, Point::new(2, 1), false), "title\n> ");
+        assert_eq!(screen_text(&screen, Point::new(4, 1), false), "title\n> ab");
+        assert_eq!(
+            screen_text(&screen, Point::new(2, 1), true),
+            "title\n> ab\nstatus"
+        );
+        // Blanks before the cursor are kept, those after the text are not.
+        assert_eq!(
+            screen_text(&screen, Point::new(6, 1), true),
+            "title\n> ab  \nstatus"
+        );
+    }
+
+    #[test]
+    fn test_line_oriented_render_writes_no_escapes() {
+        let file = tempfile().expect("output temp file");
+        let mut file_clone = file.try_clone().expect("cloned output temp file");
+        let mut renderer = Renderer::new(
+            Box::new(VT100::new(file.into_raw_fd())),
+            Box::new(StandardStyle::default()),
+        );
+        renderer.set_line_oriented(true);
+        let layout = Layout::new(Box::new(crate::layout::Window::new(
+            crate::layout::FormattedTextControl::new(vec![(
+                "bold fg:ansired".to_string(),
+                "hello".to_string(),
+            )]),
+        )));
+        renderer.request_absolute_cursor_position();
+        assert!(!renderer.waiting_for_cpr());
+        renderer.render(&layout, ColorDepth::True, false);
+        renderer.render(&layout, ColorDepth::True, false);
+        renderer.render(&layout, ColorDepth::True, true);
+
+        file_clone
+            .seek(std::io::SeekFrom::Start(0))
+            .expect("seek error");
+        let mut result = String::new();
+        file_clone.read_to_string(&mut result).expect("read error");
+        assert_eq!(result, "hello\n");
+    }
+
+    #[test]
+    fn test_cpr_timeout_marks_unsupported() {
+        let mut renderer = Renderer::new(Box::new(DummyOutput), Box::new(DummyStyle));
+        renderer.cpr_support = CPRSupport::Unknown;
+        renderer.request_absolute_cursor_position();
+
+        renderer.check_cpr_timeout();
+        assert!(renderer.waiting_for_cpr());
+
+        renderer.waiting_for_cpr_since = Instant::now().checked_sub(CPR_TIMEOUT);
+        renderer.check_cpr_timeout();
+        assert!(!renderer.waiting_for_cpr());
+        assert_eq!(renderer.cpr_support, CPRSupport::Unsupported);
     }
-    current_position
 }
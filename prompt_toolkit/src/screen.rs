@@ -2,6 +2,7 @@
 
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use std::sync::LazyLock;
 
@@ -51,7 +52,8 @@ pub struct Char {
 }
 
 impl Char {
-    fn new(c: char, style: &str) -> Self {
+    #[must_use]
+    pub fn new(c: char, style: &str) -> Self {
         let (c, style) = if let Some(mapped) = DISPLAY_MAPPINGS.get(&c) {
             ((*mapped).to_string(), style.to_string())
         } else {
@@ -80,17 +82,20 @@ impl PartialEq for Char {
 }
 impl Eq for Char {}
 
+/// A deferred drawing function, see [`Screen::draw_with_z_index`].
+type DrawFunc = Box<dyn FnOnce(&mut Screen)>;
+
 pub struct Screen {
     pub data_buffer: HashMap<usize, HashMap<usize, Char>>,
     default_char: Char,
     zero_width_escapes: HashMap<usize, HashMap<usize, String>>,
-    cursor_positions: HashMap<Window, Point>,
+    cursor_positions: HashMap<WindowId, Point>,
     show_cursor: bool,
-    menu_positions: HashMap<Window, Point>,
+    menu_positions: HashMap<WindowId, Point>,
     pub width: usize,
     pub height: usize,
-    visible_windows_to_write_positions: HashMap<Window, WritePosition>,
-    draw_float_functions: Vec<(i32, Box<dyn Fn()>)>,
+    visible_windows_to_write_positions: HashMap<WindowId, WritePosition>,
+    draw_float_functions: Vec<(i32, DrawFunc)>,
 }
 
 impl Screen {
@@ -121,16 +126,16 @@ impl Screen {
         self.show_cursor
     }
 
-    pub fn set_cursor_position(&mut self, window: Window, position: Point) {
+    pub fn set_cursor_position(&mut self, window: WindowId, position: Point) {
         self.cursor_positions.insert(window, position);
     }
 
-    pub fn set_menu_position(&mut self, window: Window, position: Point) {
+    pub fn set_menu_position(&mut self, window: WindowId, position: Point) {
         self.menu_positions.insert(window, position);
     }
 
     #[must_use]
-    pub fn get_cursor_position(&self, window: &Window) -> Point {
+    pub fn get_cursor_position(&self, window: &WindowId) -> Point {
         self.cursor_positions
             .get(window)
             .copied()
@@ -138,7 +143,7 @@ impl Screen {
     }
 
     #[must_use]
-    pub fn get_menu_position(&self, window: &Window) -> Point {
+    pub fn get_menu_position(&self, window: &WindowId) -> Point {
         self.menu_positions
             .get(window)
             .copied()
@@ -146,19 +151,35 @@ impl Screen {
             .unwrap_or(Point::new(0, 0))
     }
 
+    pub fn set_visible_window_write_position(
+        &mut self,
+        window: WindowId,
+        write_position: WritePosition,
+    ) {
+        self.visible_windows_to_write_positions
+            .insert(window, write_position);
+    }
+
+    #[must_use]
+    pub fn get_visible_window_write_position(&self, window: &WindowId) -> Option<WritePosition> {
+        self.visible_windows_to_write_positions.get(window).copied()
+    }
+
+    /// Queue a drawing function to run after the main content, lowest `z_index` first.
     pub fn draw_with_z_index<F>(&mut self, z_index: i32, draw_func: F)
     where
-        F: Fn() + 'static,
+        F: FnOnce(&mut Screen) + 'static,
     {
         self.draw_float_functions
             .push((z_index, Box::new(draw_func)));
     }
 
+    /// Run the queued drawing functions. Functions queued while drawing are run as well.
     pub fn draw_all_floats(&mut self) {
         while !self.draw_float_functions.is_empty() {
             self.draw_float_functions.sort_by_key(|item| item.0);
             let func = self.draw_float_functions.remove(0).1;
-            func();
+            func(self);
         }
     }
 
@@ -237,7 +258,7 @@ impl Screen {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Point {
     pub x: usize,
     pub y: usize,
@@ -250,6 +271,7 @@ impl Point {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct WritePosition {
     pub xpos: usize,
     pub ypos: usize,
@@ -269,7 +291,15 @@ impl WritePosition {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub struct Window {
-    content: String,
+/// Identifies a layout window, e.g. to look up its cursor position on a [`Screen`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct WindowId(usize);
+
+impl WindowId {
+    /// A new identifier, distinct from every identifier created before.
+    #[must_use]
+    pub fn next() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        WindowId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
 }
@@ -0,0 +1,40 @@
+/// How the text between the selection start and the cursor is selected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SelectionType {
+    /// Everything between the two positions.
+    #[default]
+    Characters,
+    /// Whole lines, from the line of the first position to the line of the second.
+    Lines,
+    /// A rectangle, with the two positions as opposite corners.
+    Block,
+}
+
+/// A selection in progress: the other end of it is the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectionState {
+    pub original_cursor_position: usize,
+    pub selection_type: SelectionType,
+}
+
+impl SelectionState {
+    #[must_use]
+    pub fn new(original_cursor_position: usize, selection_type: SelectionType) -> Self {
+        Self {
+            original_cursor_position,
+            selection_type,
+        }
+    }
+}
+
+/// Where pasted text goes, relative to the cursor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PasteMode {
+    /// At the cursor, which ends up after the pasted text.
+    #[default]
+    Emacs,
+    /// After the character (or line) under the cursor, as vi's `p`.
+    ViAfter,
+    /// Before the character (or line) under the cursor, as vi's `P`.
+    ViBefore,
+}
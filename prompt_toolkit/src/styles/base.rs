@@ -1,15 +1,21 @@
-#![expect(dead_code)]
-
 use crate::styles::{AnsiColor, Color};
 use anyhow::Result;
 use regex::Regex;
-use std::{collections::HashSet, default, error::Error, fmt::Display, sync::LazyLock};
-use tracing::warn;
+use std::{
+    cell::RefCell, collections::HashSet, default, error::Error, fmt::Display, rc::Rc,
+    sync::LazyLock,
+};
 
 static CLASS_NAMES_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^[a-z0-9.\s_-]*$").expect("valid regex"));
 
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+/// Whether the class names of a style rule are valid, see
+/// [`StandardStyle::new`].
+pub(crate) fn is_valid_class_names(class_names: &str) -> bool {
+    CLASS_NAMES_REGEX.is_match(class_names)
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum AttrSetting {
     Enabled,
     Disabled,
@@ -18,6 +24,7 @@ pub enum AttrSetting {
 }
 
 impl AttrSetting {
+    #[must_use]
     pub fn merge(self, other: Self) -> Self {
         if self == AttrSetting::Automatic {
             other
@@ -26,12 +33,13 @@ impl AttrSetting {
         }
     }
 
+    #[must_use]
     pub fn is_on(self) -> bool {
         matches!(self, Self::Enabled)
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Attrs {
     pub color: Option<Color>,
     pub background_color: Option<Color>,
@@ -61,6 +69,7 @@ impl Default for Attrs {
 }
 
 impl Attrs {
+    #[must_use]
     pub fn merge(attrs: &[Self]) -> Self {
         let mut empty = Self::empty();
         let default_attr = Self::default();
@@ -85,6 +94,7 @@ impl Attrs {
         empty
     }
 
+    #[must_use]
     pub fn empty() -> Self {
         Self {
             color: None,
@@ -99,6 +109,7 @@ impl Attrs {
         }
     }
 
+    #[must_use]
     pub fn from_style_string(style: &str) -> Self {
         let mut attr = if style.contains("noinherit") {
             Attrs::default()
@@ -198,10 +209,17 @@ pub struct StandardStyle {
 }
 
 impl StandardStyle {
+    /// A style from `(class names, style string)` rules, e.g.
+    /// `("completion-menu.completion", "bg:ansiblue")`.
+    ///
+    /// # Errors
+    ///
+    /// When class names have characters other than lowercase letters,
+    /// digits, `.`, `_`, `-` and whitespace.
     pub fn new(style_rules: Vec<(String, String)>) -> Result<Self> {
         let mut class_names_and_attrs: Vec<(HashSet<String>, Attrs)> = Vec::new();
         for (class_names, style_string) in style_rules.clone() {
-            if CLASS_NAMES_REGEX.is_match(&class_names) {
+            if is_valid_class_names(&class_names) {
                 let class_names_set: HashSet<String> = class_names
                     .to_lowercase()
                     .split_whitespace()
@@ -220,29 +238,57 @@ impl StandardStyle {
     }
 }
 
+/// No rules: only inline styles like `bold` or `fg:ansired` apply.
+impl Default for StandardStyle {
+    fn default() -> Self {
+        Self {
+            style_rules: Vec::new(),
+            class_names_and_attrs: Vec::new(),
+        }
+    }
+}
+
 impl Style for StandardStyle {
+    /// Resolve `class:` parts, e.g. `class:a,b` or the dotted `class:a.b`,
+    /// which stands for `class:a,a.b`, like prompt-toolkit does. A rule
+    /// applies once all of its class names were requested, and rules are
+    /// applied in the order they were given, rules without class names
+    /// first. Inline attributes like `bold` or `fg:ansired` are applied
+    /// last.
     fn get_attrs(&self, style_str: &str, default: Attrs) -> Attrs {
         let mut attrs_vec = vec![default];
         for (class_names, attrs) in &self.class_names_and_attrs {
-            if !class_names.is_empty() {
+            if class_names.is_empty() {
                 attrs_vec.push(*attrs);
             }
         }
 
+        let mut requested: HashSet<String> = HashSet::new();
+        let mut inline_attrs = Vec::new();
         for part in style_str.split_whitespace() {
             if let Some(stripped_part) = part.strip_prefix("class:") {
-                warn!("classes not supported yet");
-                let part_without_prefix = &stripped_part.to_lowercase();
-                let _new_class_names: Vec<String> = part_without_prefix
+                let part_without_prefix = stripped_part.to_lowercase();
+                let new_class_names = part_without_prefix
                     .split(',')
-                    .map(|s| s.trim().to_string())
-                    .collect();
-                // TODO: Combinations. class lookups
+                    .flat_map(expand_class_name)
+                    .collect::<Vec<_>>();
+                for new_class_name in new_class_names {
+                    for (class_names, attrs) in &self.class_names_and_attrs {
+                        let applies = class_names.contains(&new_class_name)
+                            && class_names.iter().all(|class_name| {
+                                *class_name == new_class_name || requested.contains(class_name)
+                            });
+                        if applies {
+                            attrs_vec.push(*attrs);
+                        }
+                    }
+                    requested.insert(new_class_name);
+                }
             } else {
-                let inline_attrs = Attrs::from_style_string(part);
-                attrs_vec.push(inline_attrs);
+                inline_attrs.push(Attrs::from_style_string(part));
             }
         }
+        attrs_vec.extend(inline_attrs);
 
         Attrs::merge(&attrs_vec)
     }
@@ -267,8 +313,80 @@ impl Style for StandardStyle {
     }
 }
 
+/// A dotted class name with the classes it implies, e.g. `a.b.c` gives
+/// `a`, `a.b` and `a.b.c`.
+fn expand_class_name(class_name: &str) -> Vec<String> {
+    class_name
+        .match_indices('.')
+        .map(|(i, _)| &class_name[..i])
+        .chain([class_name])
+        .filter(|class_name| !class_name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Several styles used as one, e.g. a theme over built-in defaults. The
+/// rules of later styles come after those of earlier ones, so they win.
 pub struct MergedStyle {
     styles: Vec<Box<dyn Style>>,
+    /// The combined rules and the invalidation hash they were built for.
+    merged: RefCell<Option<(u64, Rc<StandardStyle>)>>,
+}
+
+impl MergedStyle {
+    #[must_use]
+    pub fn new(styles: Vec<Box<dyn Style>>) -> Self {
+        Self {
+            styles,
+            merged: RefCell::new(None),
+        }
+    }
+
+    fn merged_style(&self) -> Rc<StandardStyle> {
+        let hash = self.invalidation_hash();
+        if let Some((merged_hash, style)) = &*self.merged.borrow() {
+            if *merged_hash == hash {
+                return Rc::clone(style);
+            }
+        }
+        let style_rules = self
+            .style_rules()
+            .into_iter()
+            .filter(|(class_names, _)| is_valid_class_names(class_names))
+            .collect();
+        let style = Rc::new(StandardStyle::new(style_rules).expect("class names are validated"));
+        *self.merged.borrow_mut() = Some((hash, Rc::clone(&style)));
+        style
+    }
+}
+
+impl Style for MergedStyle {
+    fn get_attrs(&self, style_str: &str, default: Attrs) -> Attrs {
+        self.merged_style().get_attrs(style_str, default)
+    }
+
+    fn style_rules(&self) -> Vec<(String, String)> {
+        self.styles
+            .iter()
+            .flat_map(|style| style.style_rules())
+            .collect()
+    }
+
+    fn invalidation_hash(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for style in &self.styles {
+            style.invalidation_hash().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+/// Merge styles with later ones winning, skipping the `None`s, e.g. for a
+/// user style that may not be configured.
+#[must_use]
+pub fn merge_styles(styles: Vec<Option<Box<dyn Style>>>) -> MergedStyle {
+    MergedStyle::new(styles.into_iter().flatten().collect())
 }
 
 #[cfg(test)]
@@ -378,6 +496,127 @@ mod test {
         assert_eq!(merged.hidden, AttrSetting::Disabled);
     }
 
+    fn standard_style(rules: &[(&str, &str)]) -> StandardStyle {
+        StandardStyle::new(
+            rules
+                .iter()
+                .map(|(class_names, style)| ((*class_names).to_string(), (*style).to_string()))
+                .collect(),
+        )
+        .expect("valid class names")
+    }
+
+    fn get_attrs(style: &StandardStyle, style_str: &str) -> Attrs {
+        style.get_attrs(style_str, Attrs::default())
+    }
+
+    #[test]
+    fn test_class_lookup() {
+        let style = standard_style(&[
+            ("", "italic"),
+            ("prompt", "bold fg:ansired"),
+            ("other", "underline"),
+        ]);
+        let attrs = get_attrs(&style, "class:prompt");
+        assert_eq!(attrs.italic, AttrSetting::Enabled);
+        assert_eq!(attrs.bold, AttrSetting::Enabled);
+        assert_eq!(attrs.color, Some(Color::Ansi(AnsiColor::Red)));
+        assert_eq!(attrs.underline, AttrSetting::Disabled);
+        assert_eq!(
+            get_attrs(&style, "class:Other").underline,
+            AttrSetting::Enabled
+        );
+        assert_eq!(
+            get_attrs(&style, "class:unknown").bold,
+            AttrSetting::Disabled
+        );
+    }
+
+    #[test]
+    fn test_class_combinations() {
+        let style = standard_style(&[("a b", "bold"), ("b", "underline")]);
+        assert_eq!(get_attrs(&style, "class:a").bold, AttrSetting::Disabled);
+        assert_eq!(get_attrs(&style, "class:a,b").bold, AttrSetting::Enabled);
+        assert_eq!(
+            get_attrs(&style, "class:b class:a").bold,
+            AttrSetting::Enabled
+        );
+        assert_eq!(get_attrs(&style, "class:b").underline, AttrSetting::Enabled);
+    }
+
+    #[test]
+    fn test_dotted_class_names() {
+        let style = standard_style(&[
+            ("menu", "bg:ansiblue"),
+            ("menu.item", "bold"),
+            ("menu.item.current", "reverse"),
+        ]);
+        let attrs = get_attrs(&style, "class:menu.item");
+        assert_eq!(attrs.background_color, Some(Color::Ansi(AnsiColor::Blue)));
+        assert_eq!(attrs.bold, AttrSetting::Enabled);
+        assert_eq!(attrs.reverse, AttrSetting::Disabled);
+        assert_eq!(get_attrs(&style, "class:item").bold, AttrSetting::Disabled);
+    }
+
+    #[test]
+    fn test_later_rules_and_inline_attrs_win() {
+        let style = standard_style(&[("a", "bold"), ("b", "nobold"), ("a", "fg:ansired")]);
+        let attrs = get_attrs(&style, "class:a,b");
+        assert_eq!(attrs.bold, AttrSetting::Disabled);
+        assert_eq!(attrs.color, Some(Color::Ansi(AnsiColor::Red)));
+        let attrs = get_attrs(&style, "fg:ansigreen class:a,b");
+        assert_eq!(attrs.bold, AttrSetting::Disabled);
+        assert_eq!(attrs.color, Some(Color::Ansi(AnsiColor::Green)));
+    }
+
+    #[test]
+    fn test_expand_class_name() {
+        assert_eq!(expand_class_name("a.b.c"), vec!["a", "a.b", "a.b.c"]);
+        assert_eq!(expand_class_name("a"), vec!["a"]);
+    }
+
+    #[test]
+    fn test_merged_style_later_styles_win() {
+        let defaults = standard_style(&[("prompt", "bold fg:ansired"), ("menu", "underline")]);
+        let theme = standard_style(&[("prompt", "nobold")]);
+        let merged = merge_styles(vec![Some(Box::new(defaults)), None, Some(Box::new(theme))]);
+        let attrs = merged.get_attrs("class:prompt", Attrs::default());
+        assert_eq!(attrs.bold, AttrSetting::Disabled);
+        assert_eq!(attrs.color, Some(Color::Ansi(AnsiColor::Red)));
+        assert_eq!(
+            merged.get_attrs("class:menu", Attrs::default()).underline,
+            AttrSetting::Enabled
+        );
+        assert_eq!(merged.style_rules().len(), 3);
+    }
+
+    #[test]
+    fn test_merged_style_follows_dynamic_styles() {
+        let theme = Rc::new(RefCell::new(vec![(
+            "prompt".to_string(),
+            "bold".to_string(),
+        )]));
+        let rules = Rc::clone(&theme);
+        let dynamic = DynamicStyle::new(move || {
+            Some(Box::new(StandardStyle::new(rules.borrow().clone()).ok()?))
+        });
+        let merged = MergedStyle::new(vec![Box::new(DummyStyle), Box::new(dynamic)]);
+        let hash = merged.invalidation_hash();
+        assert_eq!(
+            merged.get_attrs("class:prompt", Attrs::default()).bold,
+            AttrSetting::Enabled
+        );
+        assert_eq!(merged.invalidation_hash(), hash);
+
+        theme.borrow_mut()[0].1 = "italic".to_string();
+        assert_ne!(merged.invalidation_hash(), hash);
+        let attrs = merged.get_attrs("class:prompt", Attrs::default());
+        assert_eq!(
+            (attrs.bold, attrs.italic),
+            (AttrSetting::Disabled, AttrSetting::Enabled)
+        );
+    }
+
     #[test]
     fn test_attr_setting_is_on() {
         assert!(AttrSetting::Enabled.is_on());
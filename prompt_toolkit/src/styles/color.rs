@@ -153,7 +153,7 @@ pub static NAMED_COLORS: LazyLock<HashMap<&str, Color>> = LazyLock::new(|| {
     m
 });
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Color {
     #[default]
     Default,
@@ -187,6 +187,7 @@ impl Color {
         }
     }
 
+    #[must_use]
     pub fn rgb(self) -> (u8, u8, u8) {
         match self {
             Color::Default => (0, 0, 0),
@@ -195,6 +196,7 @@ impl Color {
         }
     }
 
+    #[must_use]
     pub fn closest_ansi(self) -> AnsiColor {
         match self {
             Color::Default => AnsiColor::Default,
@@ -221,7 +223,7 @@ impl Display for ColorParseError {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AnsiColor {
     #[default]
     Default,
@@ -244,6 +246,7 @@ pub enum AnsiColor {
 }
 
 impl AnsiColor {
+    #[must_use]
     pub fn code(self) -> i32 {
         match self {
             AnsiColor::Default => 39,
@@ -265,6 +268,7 @@ impl AnsiColor {
             AnsiColor::BrightWhite => 97,
         }
     }
+    #[must_use]
     pub fn background_code(self) -> i32 {
         match self {
             AnsiColor::Default => 49,
@@ -287,6 +291,7 @@ impl AnsiColor {
         }
     }
 
+    #[must_use]
     pub fn rgb(self) -> (u8, u8, u8) {
         match self {
             AnsiColor::Default | AnsiColor::Black => (0, 0, 0), // Default to black
@@ -331,6 +336,7 @@ impl AnsiColor {
         }
     }
 
+    #[must_use]
     pub fn closest_from_rgb(r: u8, g: u8, b: u8, exclude: &[Self]) -> Self {
         let (r, g, b) = (i32::from(r), i32::from(g), i32::from(b));
 
@@ -2,6 +2,14 @@
 
 mod base;
 mod color;
+mod style_transformation;
 
-pub use base::{Attrs, DummyStyle, DynamicStyle, Style};
+pub(crate) use base::is_valid_class_names;
+pub use base::{
+    merge_styles, AttrSetting, Attrs, DummyStyle, DynamicStyle, MergedStyle, StandardStyle, Style,
+};
 pub use color::{AnsiColor, Color, NAMED_COLORS};
+pub use style_transformation::{
+    AdjustBrightnessStyleTransformation, ConditionalStyleTransformation, DummyStyleTransformation,
+    ReverseStyleTransformation, StyleTransformation, SwapLightAndDarkStyleTransformation,
+};
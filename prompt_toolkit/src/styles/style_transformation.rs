@@ -0,0 +1,359 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::{
+    filters::Filter,
+    styles::{AnsiColor, AttrSetting, Attrs, Color},
+};
+
+/// Changes the attributes resolved from a style right before they are
+/// written to the output, e.g. to make a theme readable on a light
+/// terminal.
+pub trait StyleTransformation {
+    fn transform_attrs(&self, attrs: Attrs) -> Attrs;
+    /// Changes whenever the transformation would give other attributes.
+    fn invalidation_hash(&self) -> u64;
+}
+
+pub struct DummyStyleTransformation;
+
+impl StyleTransformation for DummyStyleTransformation {
+    fn transform_attrs(&self, attrs: Attrs) -> Attrs {
+        attrs
+    }
+
+    fn invalidation_hash(&self) -> u64 {
+        1
+    }
+}
+
+/// Turn dark colors into light ones and the other way around, keeping
+/// their hue. ANSI colors are swapped with their bright counterparts.
+pub struct SwapLightAndDarkStyleTransformation;
+
+impl StyleTransformation for SwapLightAndDarkStyleTransformation {
+    fn transform_attrs(&self, mut attrs: Attrs) -> Attrs {
+        attrs.color = attrs.color.map(opposite_color);
+        attrs.background_color = attrs.background_color.map(opposite_color);
+        attrs
+    }
+
+    fn invalidation_hash(&self) -> u64 {
+        2
+    }
+}
+
+fn opposite_color(color: Color) -> Color {
+    match color {
+        Color::Default => Color::Default,
+        Color::Ansi(ansi_color) => Color::Ansi(match ansi_color {
+            AnsiColor::Default => AnsiColor::Default,
+            AnsiColor::Black => AnsiColor::BrightWhite,
+            AnsiColor::Red => AnsiColor::BrightRed,
+            AnsiColor::Green => AnsiColor::BrightGreen,
+            AnsiColor::Yellow => AnsiColor::BrightYellow,
+            AnsiColor::Blue => AnsiColor::BrightBlue,
+            AnsiColor::Magenta => AnsiColor::BrightMagenta,
+            AnsiColor::Cyan => AnsiColor::BrightCyan,
+            AnsiColor::White => AnsiColor::BrightBlack,
+            AnsiColor::BrightBlack => AnsiColor::White,
+            AnsiColor::BrightRed => AnsiColor::Red,
+            AnsiColor::BrightGreen => AnsiColor::Green,
+            AnsiColor::BrightYellow => AnsiColor::Yellow,
+            AnsiColor::BrightBlue => AnsiColor::Blue,
+            AnsiColor::BrightMagenta => AnsiColor::Magenta,
+            AnsiColor::BrightCyan => AnsiColor::Cyan,
+            AnsiColor::BrightWhite => AnsiColor::Black,
+        }),
+        Color::Hex(r, g, b) => {
+            let (hue, lightness, saturation) = rgb_to_hls(r, g, b);
+            let (r, g, b) = hls_to_rgb(hue, 1.0 - lightness, saturation);
+            Color::Hex(r, g, b)
+        }
+    }
+}
+
+/// Keep the brightness of foreground colors within `min..=max`, with 0.0
+/// for black and 1.0 for white, so text stays readable on both dark and
+/// light terminals. Brightness is scaled into the range rather than cut
+/// off, so differences between colors remain.
+///
+/// Only text without a background color is changed, because a background
+/// chosen by the style shows the text as intended on any terminal.
+pub struct AdjustBrightnessStyleTransformation {
+    min_brightness: f64,
+    max_brightness: f64,
+}
+
+impl AdjustBrightnessStyleTransformation {
+    /// Bounds given the wrong way around are swapped.
+    #[must_use]
+    pub fn new(min_brightness: f64, max_brightness: f64) -> Self {
+        let min_brightness = min_brightness.clamp(0.0, 1.0);
+        let max_brightness = max_brightness.clamp(0.0, 1.0);
+        Self {
+            min_brightness: min_brightness.min(max_brightness),
+            max_brightness: min_brightness.max(max_brightness),
+        }
+    }
+}
+
+/// The terminal's own color, which has no brightness to adjust.
+fn is_default_color(color: Color) -> bool {
+    matches!(color, Color::Default | Color::Ansi(AnsiColor::Default))
+}
+
+impl StyleTransformation for AdjustBrightnessStyleTransformation {
+    fn transform_attrs(&self, mut attrs: Attrs) -> Attrs {
+        // The whole range is fine: keep ANSI colors rather than turning
+        // them into RGB.
+        if self.min_brightness <= 0.0 && self.max_brightness >= 1.0 {
+            return attrs;
+        }
+        let no_background = attrs.background_color.is_none_or(is_default_color);
+        match attrs.color {
+            Some(color) if !is_default_color(color) && no_background => {
+                let (r, g, b) = color.rgb();
+                let (hue, brightness, saturation) = rgb_to_hls(r, g, b);
+                let brightness =
+                    self.min_brightness + (self.max_brightness - self.min_brightness) * brightness;
+                let (r, g, b) = hls_to_rgb(hue, brightness, saturation);
+                attrs.color = Some(Color::Hex(r, g, b));
+            }
+            _ => {}
+        }
+        attrs
+    }
+
+    fn invalidation_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        3.hash(&mut hasher);
+        self.min_brightness.to_bits().hash(&mut hasher);
+        self.max_brightness.to_bits().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Swap the foreground and background colors of everything.
+pub struct ReverseStyleTransformation;
+
+impl StyleTransformation for ReverseStyleTransformation {
+    fn transform_attrs(&self, mut attrs: Attrs) -> Attrs {
+        attrs.reverse = if attrs.reverse.is_on() {
+            AttrSetting::Disabled
+        } else {
+            AttrSetting::Enabled
+        };
+        attrs
+    }
+
+    fn invalidation_hash(&self) -> u64 {
+        4
+    }
+}
+
+/// Apply a transformation only while `filter` evaluates to true, e.g.
+/// while the user asked for a light theme.
+pub struct ConditionalStyleTransformation {
+    style_transformation: Box<dyn StyleTransformation>,
+    filter: Filter,
+}
+
+impl ConditionalStyleTransformation {
+    #[must_use]
+    pub fn new(style_transformation: Box<dyn StyleTransformation>, filter: Filter) -> Self {
+        Self {
+            style_transformation,
+            filter,
+        }
+    }
+}
+
+impl StyleTransformation for ConditionalStyleTransformation {
+    fn transform_attrs(&self, attrs: Attrs) -> Attrs {
+        if self.filter.eval() {
+            self.style_transformation.transform_attrs(attrs)
+        } else {
+            attrs
+        }
+    }
+
+    fn invalidation_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.filter.eval().hash(&mut hasher);
+        self.style_transformation
+            .invalidation_hash()
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Hue, lightness and saturation, each in `0.0..=1.0`, as in Python's
+/// `colorsys`.
+fn rgb_to_hls(r: u8, g: u8, b: u8) -> (f64, f64, f64) {
+    let max_byte = r.max(g).max(b);
+    let min_byte = r.min(g).min(b);
+    let max = f64::from(max_byte) / 255.0;
+    let min = f64::from(min_byte) / 255.0;
+    let lightness = f64::midpoint(min, max);
+    if max_byte == min_byte {
+        return (0.0, lightness, 0.0);
+    }
+    let delta = max - min;
+    let saturation = if lightness <= 0.5 {
+        delta / (max + min)
+    } else {
+        delta / (2.0 - max - min)
+    };
+    let distance = |value: u8| (max - f64::from(value) / 255.0) / delta;
+    let (rc, gc, bc) = (distance(r), distance(g), distance(b));
+    let hue = if r == max_byte {
+        bc - gc
+    } else if g == max_byte {
+        2.0 + rc - bc
+    } else {
+        4.0 + gc - rc
+    };
+    ((hue / 6.0).rem_euclid(1.0), lightness, saturation)
+}
+
+fn hls_to_rgb(hue: f64, lightness: f64, saturation: f64) -> (u8, u8, u8) {
+    if saturation <= 0.0 {
+        let value = to_byte(lightness);
+        return (value, value, value);
+    }
+    let m2 = if lightness <= 0.5 {
+        lightness * (1.0 + saturation)
+    } else {
+        lightness + saturation - lightness * saturation
+    };
+    let m1 = 2.0 * lightness - m2;
+    (
+        to_byte(hue_to_value(m1, m2, hue + 1.0 / 3.0)),
+        to_byte(hue_to_value(m1, m2, hue)),
+        to_byte(hue_to_value(m1, m2, hue - 1.0 / 3.0)),
+    )
+}
+
+fn hue_to_value(m1: f64, m2: f64, hue: f64) -> f64 {
+    let hue = hue.rem_euclid(1.0);
+    if hue < 1.0 / 6.0 {
+        m1 + (m2 - m1) * hue * 6.0
+    } else if hue < 0.5 {
+        m2
+    } else if hue < 2.0 / 3.0 {
+        m1 + (m2 - m1) * (2.0 / 3.0 - hue) * 6.0
+    } else {
+        m1
+    }
+}
+
+#[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_byte(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    fn with_colors(color: Color, background_color: Option<Color>) -> Attrs {
+        Attrs {
+            color: Some(color),
+            background_color,
+            ..Attrs::empty()
+        }
+    }
+
+    #[test]
+    fn test_hls_round_trip() {
+        for (r, g, b) in [
+            (0, 0, 0),
+            (255, 255, 255),
+            (205, 0, 0),
+            (18, 52, 86),
+            (250, 128, 114),
+        ] {
+            let (hue, lightness, saturation) = rgb_to_hls(r, g, b);
+            assert_eq!(hls_to_rgb(hue, lightness, saturation), (r, g, b));
+        }
+    }
+
+    #[test]
+    fn test_swap_light_and_dark() {
+        let swap = SwapLightAndDarkStyleTransformation;
+        let attrs = swap.transform_attrs(with_colors(
+            Color::Hex(0x00, 0x00, 0x00),
+            Some(Color::Ansi(AnsiColor::Blue)),
+        ));
+        assert_eq!(attrs.color, Some(Color::Hex(0xff, 0xff, 0xff)));
+        assert_eq!(
+            attrs.background_color,
+            Some(Color::Ansi(AnsiColor::BrightBlue))
+        );
+        let dark_red = Color::Hex(0x80, 0x00, 0x00);
+        let light_red = swap.transform_attrs(with_colors(dark_red, None)).color;
+        assert_eq!(light_red, Some(Color::Hex(0xff, 0x7f, 0x7f)));
+        assert_eq!(
+            swap.transform_attrs(swap.transform_attrs(with_colors(dark_red, None))),
+            with_colors(dark_red, None)
+        );
+        assert_eq!(swap.transform_attrs(Attrs::default()), Attrs::default());
+    }
+
+    #[test]
+    fn test_adjust_brightness() {
+        let adjust = AdjustBrightnessStyleTransformation::new(0.5, 1.0);
+        let attrs = adjust.transform_attrs(with_colors(Color::Hex(0, 0, 0), None));
+        assert_eq!(attrs.color, Some(Color::Hex(0x80, 0x80, 0x80)));
+        let attrs = adjust.transform_attrs(with_colors(Color::Ansi(AnsiColor::BrightWhite), None));
+        assert_eq!(attrs.color, Some(Color::Hex(0xff, 0xff, 0xff)));
+
+        let default = with_colors(Color::Ansi(AnsiColor::Default), None);
+        assert_eq!(adjust.transform_attrs(default), default);
+        let on_default_background =
+            with_colors(Color::Hex(0, 0, 0), Some(Color::Ansi(AnsiColor::Default)));
+        assert_eq!(
+            adjust.transform_attrs(on_default_background).color,
+            Some(Color::Hex(0x80, 0x80, 0x80))
+        );
+        let swapped = AdjustBrightnessStyleTransformation::new(1.0, 0.5);
+        assert_eq!(
+            swapped.transform_attrs(with_colors(Color::Hex(0, 0, 0), None)),
+            adjust.transform_attrs(with_colors(Color::Hex(0, 0, 0), None))
+        );
+
+        let with_background = with_colors(Color::Hex(0, 0, 0), Some(Color::Ansi(AnsiColor::White)));
+        assert_eq!(adjust.transform_attrs(with_background), with_background);
+        let ansi = with_colors(Color::Ansi(AnsiColor::Red), None);
+        let full_range = AdjustBrightnessStyleTransformation::new(0.0, 1.0);
+        assert_eq!(full_range.transform_attrs(ansi), ansi);
+        assert_ne!(adjust.invalidation_hash(), full_range.invalidation_hash());
+    }
+
+    #[test]
+    fn test_reverse() {
+        let reverse = ReverseStyleTransformation;
+        assert!(reverse.transform_attrs(Attrs::empty()).reverse.is_on());
+        assert!(!reverse
+            .transform_attrs(reverse.transform_attrs(Attrs::empty()))
+            .reverse
+            .is_on());
+    }
+
+    #[test]
+    fn test_conditional() {
+        let active = Rc::new(Cell::new(false));
+        let is_active = Rc::clone(&active);
+        let conditional = ConditionalStyleTransformation::new(
+            Box::new(ReverseStyleTransformation),
+            Filter::condition(move || is_active.get()),
+        );
+        let hash = conditional.invalidation_hash();
+        assert_eq!(conditional.transform_attrs(Attrs::empty()), Attrs::empty());
+        active.set(true);
+        assert!(conditional.transform_attrs(Attrs::empty()).reverse.is_on());
+        assert_ne!(conditional.invalidation_hash(), hash);
+    }
+}